@@ -0,0 +1,237 @@
+use criterion::{BatchSize, BenchmarkId, Criterion};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::time::SystemTime;
+use tfhe::prelude::*;
+use tfhe::{
+    generate_keys, set_server_key, ClientKey, ConfigBuilder, FheBool, FheUint128, FheUint16,
+    FheUint32, FheUint64, FheUint8,
+};
+use zvm::value::Value;
+use zvm::vm::{OpCode, VM};
+
+// Operands shared by every benchmark, truncated to each width
+const LHS: u128 = 0xA5A5_A5A5_A5A5_A5A5_A5A5_A5A5_A5A5_A5A5;
+const RHS: u128 = 3;
+
+struct Width {
+    name: &'static str,
+    cipher: fn(u128, &ClientKey) -> Value,
+    plain: fn(u128) -> Value,
+}
+
+fn widths() -> Vec<Width> {
+    vec![
+        Width {
+            name: "Euint8",
+            cipher: |val, key| Value::Euint8(FheUint8::encrypt(val as u8, key)),
+            plain: |val| Value::Uint8(val as u8),
+        },
+        Width {
+            name: "Euint16",
+            cipher: |val, key| Value::Euint16(FheUint16::encrypt(val as u16, key)),
+            plain: |val| Value::Uint16(val as u16),
+        },
+        Width {
+            name: "Euint32",
+            cipher: |val, key| Value::Euint32(FheUint32::encrypt(val as u32, key)),
+            plain: |val| Value::Uint32(val as u32),
+        },
+        Width {
+            name: "Euint64",
+            cipher: |val, key| Value::Euint64(FheUint64::encrypt(val as u64, key)),
+            plain: |val| Value::Uint64(val as u64),
+        },
+        Width {
+            name: "Euint128",
+            cipher: |val, key| Value::Euint128(FheUint128::encrypt(val, key)),
+            plain: Value::Uint128,
+        },
+    ]
+}
+
+fn binary_ops() -> Vec<OpCode> {
+    vec![
+        OpCode::Add,
+        OpCode::Sub,
+        OpCode::Mul,
+        OpCode::AddChecked,
+        OpCode::SubChecked,
+        OpCode::MulChecked,
+        OpCode::AddSat,
+        OpCode::SubSat,
+        OpCode::MulSat,
+        OpCode::Div,
+        OpCode::DivChecked,
+        OpCode::Rem,
+        OpCode::DivRem,
+        OpCode::And,
+        OpCode::Or,
+        OpCode::Xor,
+        OpCode::ShiftRight,
+        OpCode::ShiftLeft,
+        OpCode::RotateLeft,
+        OpCode::RotateRight,
+        OpCode::Eq,
+        OpCode::Neq,
+        OpCode::Lt,
+        OpCode::Lte,
+        OpCode::Gt,
+        OpCode::Gte,
+        OpCode::Min,
+        OpCode::Max,
+    ]
+}
+
+fn unary_ops() -> Vec<OpCode> {
+    vec![
+        OpCode::Neg,
+        OpCode::Inc,
+        OpCode::Dec,
+        OpCode::Not,
+        OpCode::CountOnes,
+        OpCode::LeadingZeros,
+        OpCode::TrailingZeros,
+        OpCode::ILog2,
+        OpCode::IntToBool,
+    ]
+}
+
+// One measured program: the opcode, the operand width and whether the other
+// operands are ciphertexts or plaintexts
+struct Entry {
+    opcode: String,
+    width: &'static str,
+    operands: &'static str,
+}
+
+fn bench(c: &mut Criterion, entries: &mut Vec<Entry>, program: Vec<OpCode>, entry: Entry) {
+    let mut group = c.benchmark_group(&entry.opcode);
+    group.bench_function(BenchmarkId::new(entry.width, entry.operands), |b| {
+        // Pushes move their operands, so only the opcode itself is timed
+        b.iter_batched(
+            || program.clone(),
+            |program| {
+                let mut vm = VM::new();
+                vm.execute_owned(program).unwrap();
+                vm
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+    entries.push(entry);
+}
+
+fn opcodes(c: &mut Criterion) -> Vec<Entry> {
+    let config = ConfigBuilder::default().build();
+    let (client_key, server_keys) = generate_keys(config);
+    set_server_key(server_keys);
+
+    let mut entries = Vec::new();
+    for width in widths() {
+        let lhs = (width.cipher)(LHS, &client_key);
+        let cipher_rhs = (width.cipher)(RHS, &client_key);
+        let plain_rhs = (width.plain)(RHS);
+        let entry = |opcode: &OpCode, operands| Entry {
+            opcode: format!("{:?}", opcode),
+            width: width.name,
+            operands,
+        };
+
+        for op in binary_ops() {
+            for (rhs, operands) in [(&cipher_rhs, "cipher_cipher"), (&plain_rhs, "cipher_plain")] {
+                let program = vec![
+                    OpCode::Push(lhs.clone()),
+                    OpCode::Push(rhs.clone()),
+                    op.clone(),
+                ];
+                bench(c, &mut entries, program, entry(&op, operands));
+            }
+        }
+
+        for op in unary_ops() {
+            let program = vec![OpCode::Push(lhs.clone()), op.clone()];
+            bench(c, &mut entries, program, entry(&op, "cipher"));
+        }
+
+        for op in [
+            OpCode::AddImm(plain_rhs.clone()),
+            OpCode::SubImm(plain_rhs.clone()),
+            OpCode::MulImm(plain_rhs.clone()),
+        ] {
+            let program = vec![OpCode::Push(lhs.clone()), op.clone()];
+            let mut entry = entry(&op, "cipher_plain");
+            // Group immediates by opcode rather than by operand value
+            entry.opcode.truncate(entry.opcode.find('(').unwrap());
+            bench(c, &mut entries, program, entry);
+        }
+
+        let condition = Value::Ebool(FheBool::encrypt(true, &client_key));
+        for (rhs, operands) in [(&cipher_rhs, "cipher_cipher"), (&plain_rhs, "cipher_plain")] {
+            let program = vec![
+                OpCode::Push(condition.clone()),
+                OpCode::Push(lhs.clone()),
+                OpCode::Push(rhs.clone()),
+                OpCode::Mux,
+            ];
+            bench(c, &mut entries, program, entry(&OpCode::Mux, operands));
+        }
+
+        let op = OpCode::BoolToInt(lhs.value_type().bits());
+        let program = vec![OpCode::Push(condition.clone()), op.clone()];
+        let mut entry = entry(&op, "cipher");
+        entry.opcode.truncate(entry.opcode.find('(').unwrap());
+        bench(c, &mut entries, program, entry);
+    }
+    entries
+}
+
+// Mirrors where criterion writes its results
+fn criterion_dir() -> PathBuf {
+    if let Some(home) = env::var_os("CRITERION_HOME") {
+        return PathBuf::from(home);
+    }
+    let target = env::var_os("CARGO_TARGET_DIR").unwrap_or_else(|| "target".into());
+    PathBuf::from(target).join("criterion")
+}
+
+// Collects the mean time of every benchmark that ran into a CSV table that a
+// cost model can load directly. Benchmarks skipped by a filter are left out:
+// their estimates, if any, were written by an earlier run, before `started`.
+fn write_cost_table(entries: &[Entry], started: SystemTime) {
+    let dir = criterion_dir();
+    let mut table = String::from("opcode,width,operands,mean_ns\n");
+    for entry in entries {
+        let estimates = dir
+            .join(&entry.opcode)
+            .join(entry.width)
+            .join(entry.operands)
+            .join("new/estimates.json");
+        let fresh = fs::metadata(&estimates)
+            .and_then(|metadata| metadata.modified())
+            .is_ok_and(|modified| modified >= started);
+        if !fresh {
+            continue;
+        }
+        let json = fs::read_to_string(&estimates).unwrap();
+        let estimates: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let mean = estimates["mean"]["point_estimate"].as_f64().unwrap();
+        table.push_str(&format!(
+            "{},{},{},{:.0}\n",
+            entry.opcode, entry.width, entry.operands, mean
+        ));
+    }
+    let path = dir.join("cost_table.csv");
+    fs::write(&path, table).unwrap();
+    println!("Cost table written to {}", path.display());
+}
+
+fn main() {
+    let started = SystemTime::now();
+    let mut criterion = Criterion::default().sample_size(10).configure_from_args();
+    let entries = opcodes(&mut criterion);
+    criterion.final_summary();
+    write_cost_table(&entries, started);
+}
@@ -0,0 +1,96 @@
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use tfhe::prelude::*;
+use tfhe::{generate_keys, set_server_key, ConfigBuilder, FheUint128};
+use zvm::value::Value;
+use zvm::vm::{OpCode, VM};
+
+// Number of times each program copies the ciphertext
+const COPIES: usize = 32;
+
+fn zero_copy(c: &mut Criterion) {
+    let config = ConfigBuilder::default().build();
+    let (client_key, server_keys) = generate_keys(config);
+    set_server_key(server_keys);
+
+    let value = Value::Euint128(FheUint128::encrypt(u128::MAX, &client_key));
+
+    // Pushing program constants: a borrowed program clones each one, an owned
+    // program moves it onto the stack
+    let push: Vec<OpCode> = (0..COPIES)
+        .flat_map(|_| [OpCode::Push(value.clone()), OpCode::Drop])
+        .collect();
+    let mut group = c.benchmark_group("push");
+    group.bench_function("borrowed", |b| {
+        b.iter(|| VM::new().execute(black_box(&push)))
+    });
+    group.bench_function("owned", |b| {
+        b.iter_batched(
+            || push.clone(),
+            |program| VM::new().execute_owned(program),
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+
+    // Dup and Load share the value, compared with deep-copying it as many
+    // times
+    let mut load = vec![OpCode::Push(value.clone()), OpCode::Store(0)];
+    for _ in 0..COPIES / 2 {
+        load.extend([OpCode::Load(0), OpCode::Dup, OpCode::Drop, OpCode::Drop]);
+    }
+    let mut group = c.benchmark_group("dup_load");
+    group.bench_function("shared", |b| {
+        b.iter_batched(
+            || load.clone(),
+            |program| VM::new().execute_owned(program),
+            BatchSize::LargeInput,
+        )
+    });
+    group.bench_function("deep_copy", |b| {
+        b.iter(|| {
+            for _ in 0..COPIES {
+                black_box(value.clone());
+            }
+        })
+    });
+    group.finish();
+
+    // A realistic use of loaded values: Add borrows both operands, so adding
+    // two memory cells copies neither, where pushing them as constants clones
+    // both every time
+    let mut load_add = vec![
+        OpCode::Push(value.clone()),
+        OpCode::Store(0),
+        OpCode::Push(value.clone()),
+        OpCode::Store(1),
+    ];
+    let mut push_add = Vec::new();
+    for _ in 0..COPIES / 2 {
+        load_add.extend([OpCode::Load(0), OpCode::Load(1), OpCode::Add, OpCode::Drop]);
+        push_add.extend([
+            OpCode::Push(value.clone()),
+            OpCode::Push(value.clone()),
+            OpCode::Add,
+            OpCode::Drop,
+        ]);
+    }
+    let mut group = c.benchmark_group("load_add");
+    group.bench_function("shared", |b| {
+        b.iter_batched(
+            || load_add.clone(),
+            |program| VM::new().execute_owned(program),
+            BatchSize::LargeInput,
+        )
+    });
+    group.bench_function("deep_copy", |b| {
+        b.iter(|| VM::new().execute(black_box(&push_add)))
+    });
+    group.finish();
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(10);
+    targets = zero_copy
+}
+criterion_main!(benches);
@@ -0,0 +1,12 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use zvm::vm::{deserialize, serialize};
+
+fuzz_target!(|data: &[u8]| {
+    // Decoding arbitrary bytes must never panic, and anything that decodes
+    // must encode back to exactly the same bytes.
+    if let Ok(program) = deserialize(data) {
+        assert_eq!(serialize(&program), data);
+    }
+});
@@ -0,0 +1,403 @@
+use crate::compiler::{Input, Program};
+use crate::value::{Value, ValueType};
+use crate::vm::OpCode;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::ops::{Add, BitAnd, BitOr, BitXor, Div, Mul, Neg, Not, Rem, Shl, Shr, Sub};
+use std::rc::Rc;
+
+// Builds programs from typed handles instead of raw opcodes. Every operation
+// stores its result in a fresh memory slot; `build` then removes the slots that
+// are only read once, right after they are written, so most intermediates stay
+// on the stack.
+//
+//     let builder = ProgramBuilder::new();
+//     let a = builder.input::<Euint16>("a");
+//     let b = builder.input::<Euint16>("b");
+//     builder.output(&(&a * &b + 1));
+//     let program = builder.build();
+
+// Marker types naming the type of a handle. They mirror the Value variants and
+// can't be constructed.
+pub enum Ebool {}
+pub enum Euint8 {}
+pub enum Euint16 {}
+pub enum Euint32 {}
+pub enum Euint64 {}
+pub enum Euint128 {}
+pub enum Bool {}
+pub enum Uint8 {}
+pub enum Uint16 {}
+pub enum Uint32 {}
+pub enum Uint64 {}
+pub enum Uint128 {}
+
+pub trait Type {
+    const VALUE_TYPE: ValueType;
+}
+
+pub trait Integer: Type {
+    type Bool: Type; // Result of comparing two values of this type
+    type Encrypted: Type; // Result of selecting between two values of this type
+}
+
+pub trait Boolean: Type {}
+
+pub trait Plaintext: Type {
+    type Native;
+    fn value(val: Self::Native) -> Value;
+}
+
+macro_rules! marker_type {
+    ($($marker:ident, $value_type:ident);*) => {
+        $(
+            impl Type for $marker {
+                const VALUE_TYPE: ValueType = ValueType::$value_type;
+            }
+        )*
+    };
+}
+
+marker_type!(
+    Ebool, Ebool; Euint8, Euint8; Euint16, Euint16; Euint32, Euint32; Euint64, Euint64;
+    Euint128, Euint128; Bool, Bool; Uint8, Uint8; Uint16, Uint16; Uint32, Uint32;
+    Uint64, Uint64; Uint128, Uint128
+);
+
+impl Boolean for Ebool {}
+impl Boolean for Bool {}
+
+macro_rules! integer_type {
+    ($($marker:ident, $bool:ident, $encrypted:ident);*) => {
+        $(
+            impl Integer for $marker {
+                type Bool = $bool;
+                type Encrypted = $encrypted;
+            }
+        )*
+    };
+}
+
+integer_type!(
+    Euint8, Ebool, Euint8; Euint16, Ebool, Euint16; Euint32, Ebool, Euint32;
+    Euint64, Ebool, Euint64; Euint128, Ebool, Euint128; Uint8, Bool, Euint8;
+    Uint16, Bool, Euint16; Uint32, Bool, Euint32; Uint64, Bool, Euint64;
+    Uint128, Bool, Euint128
+);
+
+macro_rules! plaintext_type {
+    ($($marker:ident, $native:ty);*) => {
+        $(
+            impl Plaintext for $marker {
+                type Native = $native;
+
+                fn value(val: $native) -> Value {
+                    Value::$marker(val)
+                }
+            }
+        )*
+    };
+}
+
+plaintext_type!(
+    Bool, bool; Uint8, u8; Uint16, u16; Uint32, u32; Uint64, u64; Uint128, u128
+);
+
+#[derive(Clone)]
+enum Source {
+    Slot(i32),
+    Constant(Value), // Pushed where it is used rather than kept in memory
+}
+
+#[derive(Default)]
+struct State {
+    inputs: Vec<Input>,
+    code: Vec<OpCode>,
+    outputs: Vec<Source>,
+    next_slot: i32,
+}
+
+impl State {
+    fn load(&mut self, source: &Source) {
+        match source {
+            Source::Slot(slot) => self.code.push(OpCode::Load(*slot)),
+            Source::Constant(value) => self.code.push(OpCode::Push(value.clone())),
+        }
+    }
+
+    fn store(&mut self) -> Source {
+        let slot = self.next_slot;
+        self.next_slot += 1;
+        self.code.push(OpCode::Store(slot));
+        Source::Slot(slot)
+    }
+}
+
+#[derive(Default)]
+pub struct ProgramBuilder {
+    state: Rc<RefCell<State>>,
+}
+
+// A typed handle to a value computed by the program
+pub struct Var<T> {
+    state: Rc<RefCell<State>>,
+    source: Source,
+    value_type: PhantomData<T>,
+}
+
+impl<T> Clone for Var<T> {
+    fn clone(&self) -> Self {
+        Var {
+            state: Rc::clone(&self.state),
+            source: self.source.clone(),
+            value_type: PhantomData,
+        }
+    }
+}
+
+impl ProgramBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn var<T>(&self, source: Source) -> Var<T> {
+        Var {
+            state: Rc::clone(&self.state),
+            source,
+            value_type: PhantomData,
+        }
+    }
+
+    // Declares an input, bound to a value with Program::bind
+    pub fn input<T: Type>(&self, name: &str) -> Var<T> {
+        let mut state = self.state.borrow_mut();
+        let slot = state.next_slot;
+        state.next_slot += 1;
+        state.inputs.push(Input {
+            name: name.to_string(),
+            value_type: T::VALUE_TYPE,
+            slot,
+        });
+        drop(state);
+        self.var(Source::Slot(slot))
+    }
+
+    pub fn constant<T: Plaintext>(&self, val: T::Native) -> Var<T> {
+        self.var(Source::Constant(T::value(val)))
+    }
+
+    // Outputs are left on the stack in the order they are declared, so the
+    // last one is on top
+    pub fn output<T>(&self, var: &Var<T>) {
+        self.state.borrow_mut().outputs.push(var.source.clone());
+    }
+
+    pub fn build(&self) -> Program {
+        let state = self.state.borrow();
+        let mut code = state.code.clone();
+        for output in &state.outputs {
+            match output {
+                Source::Slot(slot) => code.push(OpCode::Load(*slot)),
+                Source::Constant(value) => code.push(OpCode::Push(value.clone())),
+            }
+        }
+        let mut inputs = state.inputs.clone();
+        let code = optimize(code, &mut inputs);
+        Program { inputs, code }
+    }
+}
+
+// Drops stores that are read back only by the next instruction, turns stores
+// that are never read into Drop, then renumbers the remaining slots from zero
+// with the inputs first.
+fn optimize(code: Vec<OpCode>, inputs: &mut [Input]) -> Vec<OpCode> {
+    let mut loads: HashMap<i32, usize> = HashMap::new();
+    for op in code.iter() {
+        if let OpCode::Load(slot) = op {
+            *loads.entry(*slot).or_default() += 1;
+        }
+    }
+
+    let mut optimized = Vec::with_capacity(code.len());
+    let mut ops = code.into_iter().peekable();
+    while let Some(op) = ops.next() {
+        let OpCode::Store(slot) = op else {
+            optimized.push(op);
+            continue;
+        };
+        match loads.get(&slot) {
+            None => optimized.push(OpCode::Drop),
+            Some(1) if matches!(ops.peek(), Some(OpCode::Load(next)) if *next == slot) => {
+                ops.next();
+            }
+            Some(_) => optimized.push(op),
+        }
+    }
+
+    let mut slots: HashMap<i32, i32> = HashMap::new();
+    for input in inputs.iter_mut() {
+        let slot = slots.len() as i32;
+        input.slot = *slots.entry(input.slot).or_insert(slot);
+    }
+    for op in optimized.iter_mut() {
+        if let OpCode::Load(slot) | OpCode::Store(slot) = op {
+            let next = slots.len() as i32;
+            *slot = *slots.entry(*slot).or_insert(next);
+        }
+    }
+    optimized
+}
+
+impl<T> Var<T> {
+    fn emit<U>(&self, operands: &[&Source], op: OpCode) -> Var<U> {
+        let mut state = self.state.borrow_mut();
+        for operand in operands {
+            state.load(operand);
+        }
+        state.code.push(op);
+        let source = state.store();
+        Var {
+            state: Rc::clone(&self.state),
+            source,
+            value_type: PhantomData,
+        }
+    }
+}
+
+impl<T: Integer> Var<T> {
+    pub fn lt(&self, other: &Self) -> Var<T::Bool> {
+        self.emit(&[&self.source, &other.source], OpCode::Lt)
+    }
+
+    pub fn le(&self, other: &Self) -> Var<T::Bool> {
+        self.emit(&[&self.source, &other.source], OpCode::Lte)
+    }
+
+    pub fn gt(&self, other: &Self) -> Var<T::Bool> {
+        self.emit(&[&self.source, &other.source], OpCode::Gt)
+    }
+
+    pub fn ge(&self, other: &Self) -> Var<T::Bool> {
+        self.emit(&[&self.source, &other.source], OpCode::Gte)
+    }
+
+    pub fn eq(&self, other: &Self) -> Var<T::Bool> {
+        self.emit(&[&self.source, &other.source], OpCode::Eq)
+    }
+
+    pub fn ne(&self, other: &Self) -> Var<T::Bool> {
+        self.emit(&[&self.source, &other.source], OpCode::Neq)
+    }
+
+    pub fn min(&self, other: &Self) -> Self {
+        self.emit(&[&self.source, &other.source], OpCode::Min)
+    }
+
+    pub fn max(&self, other: &Self) -> Self {
+        self.emit(&[&self.source, &other.source], OpCode::Max)
+    }
+}
+
+impl<C: Boolean> Var<C> {
+    // Compiles to Mux, so the result is encrypted
+    pub fn select<T: Integer>(&self, then: &Var<T>, otherwise: &Var<T>) -> Var<T::Encrypted> {
+        self.emit(
+            &[&self.source, &then.source, &otherwise.source],
+            OpCode::Mux,
+        )
+    }
+}
+
+macro_rules! binary_operator {
+    ($($trait:ident, $method:ident, $op:ident, $bound:ident);*) => {
+        $(
+            impl<T: $bound> $trait for &Var<T> {
+                type Output = Var<T>;
+
+                fn $method(self, other: Self) -> Var<T> {
+                    self.emit(&[&self.source, &other.source], OpCode::$op)
+                }
+            }
+
+            impl<T: $bound> $trait for Var<T> {
+                type Output = Var<T>;
+
+                fn $method(self, other: Self) -> Var<T> {
+                    (&self).$method(&other)
+                }
+            }
+        )*
+    };
+}
+
+binary_operator!(
+    Add, add, Add, Integer;
+    Sub, sub, Sub, Integer;
+    Mul, mul, Mul, Integer;
+    Div, div, Div, Integer;
+    Rem, rem, Rem, Integer;
+    Shl, shl, ShiftLeft, Integer;
+    Shr, shr, ShiftRight, Integer;
+    BitAnd, bitand, And, Type;
+    BitOr, bitor, Or, Type;
+    BitXor, bitxor, Xor, Type
+);
+
+macro_rules! unary_operator {
+    ($($trait:ident, $method:ident, $op:ident, $bound:ident);*) => {
+        $(
+            impl<T: $bound> $trait for &Var<T> {
+                type Output = Var<T>;
+
+                fn $method(self) -> Var<T> {
+                    self.emit(&[&self.source], OpCode::$op)
+                }
+            }
+
+            impl<T: $bound> $trait for Var<T> {
+                type Output = Var<T>;
+
+                fn $method(self) -> Var<T> {
+                    (&self).$method()
+                }
+            }
+        )*
+    };
+}
+
+unary_operator!(Neg, neg, Neg, Integer; Not, not, Not, Type);
+
+// Plaintext right-hand sides compile to the immediate opcodes
+macro_rules! scalar_operator {
+    ($($marker:ident, $native:ident, $value:ident);*) => {
+        $(
+            scalar_operator!(@op $marker, $native, $value, Add, add, AddImm);
+            scalar_operator!(@op $marker, $native, $value, Sub, sub, SubImm);
+            scalar_operator!(@op $marker, $native, $value, Mul, mul, MulImm);
+        )*
+    };
+    (@op $marker:ident, $native:ident, $value:ident, $trait:ident, $method:ident, $op:ident) => {
+        impl $trait<$native> for &Var<$marker> {
+            type Output = Var<$marker>;
+
+            fn $method(self, other: $native) -> Var<$marker> {
+                self.emit(&[&self.source], OpCode::$op(Value::$value(other)))
+            }
+        }
+
+        impl $trait<$native> for Var<$marker> {
+            type Output = Var<$marker>;
+
+            fn $method(self, other: $native) -> Var<$marker> {
+                (&self).$method(other)
+            }
+        }
+    };
+}
+
+scalar_operator!(
+    Euint8, u8, Uint8; Euint16, u16, Uint16; Euint32, u32, Uint32; Euint64, u64, Uint64;
+    Euint128, u128, Uint128; Uint8, u8, Uint8; Uint16, u16, Uint16; Uint32, u32, Uint32;
+    Uint64, u64, Uint64; Uint128, u128, Uint128
+);
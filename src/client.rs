@@ -0,0 +1,192 @@
+use crate::error::DecodeError;
+use crate::value::{CompressedValue, Value, ValueType};
+use serde::{Deserialize, Serialize};
+use tfhe::prelude::*;
+use tfhe::{ClientKey, CompactCiphertextList, CompactPublicKey, FheTypes};
+use tfhe::{
+    CompressedFheBool, CompressedFheUint128, CompressedFheUint16, CompressedFheUint32,
+    CompressedFheUint64, CompressedFheUint8,
+};
+use tfhe::{FheBool, FheUint128, FheUint16, FheUint32, FheUint64, FheUint8};
+
+// Client-side helpers for turning plaintexts into program inputs and results
+// back into plaintexts without naming the tfhe type of each value. Inputs can
+// also be encrypted by third parties holding only a public key.
+
+// A decrypted value, mirroring the plaintext Value variants
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Plain {
+    Bool(bool),
+    Uint8(u8),
+    Uint16(u16),
+    Uint32(u32),
+    Uint64(u64),
+    Uint128(u128),
+}
+
+impl Plain {
+    // Builds a plaintext of the width of `ty`, or None if `val` doesn't fit
+    pub fn new(val: u128, ty: ValueType) -> Option<Self> {
+        Some(match ty.plain_value(val)? {
+            Value::Bool(val) => Plain::Bool(val),
+            Value::Uint8(val) => Plain::Uint8(val),
+            Value::Uint16(val) => Plain::Uint16(val),
+            Value::Uint32(val) => Plain::Uint32(val),
+            Value::Uint64(val) => Plain::Uint64(val),
+            Value::Uint128(val) => Plain::Uint128(val),
+            _ => unreachable!(),
+        })
+    }
+
+    pub fn to_u128(self) -> u128 {
+        match self {
+            Plain::Bool(val) => val as u128,
+            Plain::Uint8(val) => val as u128,
+            Plain::Uint16(val) => val as u128,
+            Plain::Uint32(val) => val as u128,
+            Plain::Uint64(val) => val as u128,
+            Plain::Uint128(val) => val,
+        }
+    }
+}
+
+// Encrypts `value` as `ty` with a ClientKey, or with a PublicKey or
+// CompressedPublicKey for data providers that must not be able to decrypt.
+// Plaintext types give a plaintext Value, so inputs can be built the same way
+// whether or not they are secret. Panics if `value` doesn't fit in `ty`; a bool
+// must be 0 or 1.
+pub fn encrypt<K>(value: u128, ty: ValueType, key: &K) -> Value
+where
+    FheBool: FheTryEncrypt<bool, K>,
+    FheUint8: FheTryEncrypt<u8, K>,
+    FheUint16: FheTryEncrypt<u16, K>,
+    FheUint32: FheTryEncrypt<u32, K>,
+    FheUint64: FheTryEncrypt<u64, K>,
+    FheUint128: FheTryEncrypt<u128, K>,
+{
+    let plain = ty
+        .plain_value(value)
+        .unwrap_or_else(|| panic!("{} does not fit in {:?}", value, ty));
+    if !ty.is_encrypted() {
+        return plain;
+    }
+    match plain {
+        Value::Bool(val) => Value::Ebool(FheBool::encrypt(val, key)),
+        Value::Uint8(val) => Value::Euint8(FheUint8::encrypt(val, key)),
+        Value::Uint16(val) => Value::Euint16(FheUint16::encrypt(val, key)),
+        Value::Uint32(val) => Value::Euint32(FheUint32::encrypt(val, key)),
+        Value::Uint64(val) => Value::Euint64(FheUint64::encrypt(val, key)),
+        Value::Uint128(val) => Value::Euint128(FheUint128::encrypt(val, key)),
+        _ => unreachable!(),
+    }
+}
+
+// Like encrypt, but gives the seeded compressed form, roughly half the size,
+// e.g. for Push constants shipped with a program. Only a ClientKey can produce
+// it, and expanding it again needs no server key or bootstrap. Plaintext types
+// give a plaintext Value as encrypt does.
+pub fn encrypt_compressed(value: u128, ty: ValueType, key: &ClientKey) -> Value {
+    let plain = ty
+        .plain_value(value)
+        .unwrap_or_else(|| panic!("{} does not fit in {:?}", value, ty));
+    if !ty.is_encrypted() {
+        return plain;
+    }
+    let compressed = match plain {
+        Value::Bool(val) => CompressedValue::Ebool(CompressedFheBool::encrypt(val, key)),
+        Value::Uint8(val) => CompressedValue::Euint8(CompressedFheUint8::encrypt(val, key)),
+        Value::Uint16(val) => CompressedValue::Euint16(CompressedFheUint16::encrypt(val, key)),
+        Value::Uint32(val) => CompressedValue::Euint32(CompressedFheUint32::encrypt(val, key)),
+        Value::Uint64(val) => CompressedValue::Euint64(CompressedFheUint64::encrypt(val, key)),
+        Value::Uint128(val) => CompressedValue::Euint128(CompressedFheUint128::encrypt(val, key)),
+        _ => unreachable!(),
+    };
+    Value::Compressed(Box::new(compressed))
+}
+
+// Named inputs encrypted together under a CompactPublicKey. A compact list is
+// far smaller than the same ciphertexts encrypted one by one; the server
+// expands it back into Values when binding it to a program.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct InputBundle {
+    names: Vec<String>,
+    list: CompactCiphertextList,
+}
+
+impl InputBundle {
+    // Panics if an input has a plaintext type or a value that doesn't fit
+    pub fn encrypt(inputs: &[(&str, u128, ValueType)], public_key: &CompactPublicKey) -> Self {
+        let mut builder = CompactCiphertextList::builder(public_key);
+        for (name, value, ty) in inputs {
+            assert!(
+                ty.is_encrypted(),
+                "input {} must have an encrypted type",
+                name
+            );
+            match ty.plain_value(*value) {
+                Some(Value::Bool(val)) => builder.push(val),
+                Some(Value::Uint8(val)) => builder.push(val),
+                Some(Value::Uint16(val)) => builder.push(val),
+                Some(Value::Uint32(val)) => builder.push(val),
+                Some(Value::Uint64(val)) => builder.push(val),
+                Some(Value::Uint128(val)) => builder.push(val),
+                _ => panic!("{} does not fit in {:?}", value, ty),
+            };
+        }
+        InputBundle {
+            names: inputs.iter().map(|(name, _, _)| name.to_string()).collect(),
+            list: builder.build(),
+        }
+    }
+
+    // Expands the list into named values, ready for Program::bind
+    pub fn expand(&self) -> Result<Vec<(String, Value)>, DecodeError> {
+        if self.names.len() != self.list.len() {
+            return Err(DecodeError::InvalidBundle(format!(
+                "{} names for {} ciphertexts",
+                self.names.len(),
+                self.list.len()
+            )));
+        }
+        let expander = self.list.expand()?;
+        let mut values = Vec::with_capacity(self.names.len());
+        for (index, name) in self.names.iter().enumerate() {
+            let value = match expander.get_kind_of(index) {
+                Some(FheTypes::Bool) => expander.get(index)?.map(Value::Ebool),
+                Some(FheTypes::Uint8) => expander.get(index)?.map(Value::Euint8),
+                Some(FheTypes::Uint16) => expander.get(index)?.map(Value::Euint16),
+                Some(FheTypes::Uint32) => expander.get(index)?.map(Value::Euint32),
+                Some(FheTypes::Uint64) => expander.get(index)?.map(Value::Euint64),
+                Some(FheTypes::Uint128) => expander.get(index)?.map(Value::Euint128),
+                kind => {
+                    return Err(DecodeError::InvalidBundle(format!(
+                        "input {} has unsupported type {:?}",
+                        name, kind
+                    )))
+                }
+            };
+            values.push((name.clone(), value.unwrap()));
+        }
+        Ok(values)
+    }
+}
+
+// Decrypts any value, whatever its width. Plaintext values are returned as
+// they are.
+pub fn decrypt(value: &Value, client_key: &ClientKey) -> Plain {
+    match value {
+        Value::Ebool(val) => Plain::Bool(val.decrypt(client_key)),
+        Value::Euint8(val) => Plain::Uint8(val.decrypt(client_key)),
+        Value::Euint16(val) => Plain::Uint16(val.decrypt(client_key)),
+        Value::Euint32(val) => Plain::Uint32(val.decrypt(client_key)),
+        Value::Euint64(val) => Plain::Uint64(val.decrypt(client_key)),
+        Value::Euint128(val) => Plain::Uint128(val.decrypt(client_key)),
+        Value::Bool(val) => Plain::Bool(*val),
+        Value::Uint8(val) => Plain::Uint8(*val),
+        Value::Uint16(val) => Plain::Uint16(*val),
+        Value::Uint32(val) => Plain::Uint32(*val),
+        Value::Uint64(val) => Plain::Uint64(*val),
+        Value::Uint128(val) => Plain::Uint128(*val),
+        Value::Compressed(val) => decrypt(&val.decompress(), client_key),
+    }
+}
@@ -0,0 +1,449 @@
+use crate::error::CompileError;
+use crate::value::{Value, ValueType};
+use crate::vm::OpCode;
+use std::collections::HashMap;
+
+// Compiles infix expressions such as `max(a * b + c, 100) >= d ? x : y` into
+// bytecode. Variables are declared inputs read from memory with Load; integer
+// literals take the width of the operand they are combined with.
+//
+// Precedence, from loosest to tightest:
+//   ?:   | ||   ^   & &&   == != < <= > >=   << >>   + -   * / %   unary - !
+//
+// Operands of a binary operator must have the same width, though either may be
+// encrypted. `&&` and `||` only take bools. Comparisons produce a bool, and
+// `c ? x : y` compiles to Mux, whose result is always encrypted.
+
+// An input the compiled program reads from memory
+#[derive(Clone, Debug, PartialEq)]
+pub struct Input {
+    pub name: String,
+    pub value_type: ValueType,
+    pub slot: i32,
+}
+
+pub struct Program {
+    pub inputs: Vec<Input>,
+    pub code: Vec<OpCode>,
+}
+
+impl Program {
+    pub fn slot(&self, name: &str) -> Option<i32> {
+        self.inputs
+            .iter()
+            .find(|input| input.name == name)
+            .map(|input| input.slot)
+    }
+
+    // Prefixes the program with a Push and Store for every input, so the result
+    // runs on a fresh VM
+    pub fn bind<S: AsRef<str>>(
+        &self,
+        values: impl IntoIterator<Item = (S, Value)>,
+    ) -> Result<Vec<OpCode>, CompileError> {
+        let mut values: HashMap<String, Value> = values
+            .into_iter()
+            .map(|(name, value)| (name.as_ref().to_string(), value))
+            .collect();
+        let mut code = Vec::new();
+        for input in &self.inputs {
+            let value = values
+                .remove(&input.name)
+                .ok_or_else(|| CompileError::MissingInput(input.name.clone()))?;
+            if value.value_type() != input.value_type {
+                return Err(CompileError::TypeMismatch(format!(
+                    "input {} is {:?}, got {:?}",
+                    input.name,
+                    input.value_type,
+                    value.value_type()
+                )));
+            }
+            code.push(OpCode::Push(value));
+            code.push(OpCode::Store(input.slot));
+        }
+        if let Some(name) = values.keys().next() {
+            return Err(CompileError::UnknownVariable(name.to_string()));
+        }
+        code.extend(self.code.iter().cloned());
+        Ok(code)
+    }
+}
+
+#[derive(Default)]
+pub struct Compiler {
+    inputs: Vec<Input>,
+}
+
+impl Compiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Declares an input; slots are assigned in declaration order
+    pub fn input(mut self, name: &str, value_type: ValueType) -> Self {
+        let slot = self.inputs.len() as i32;
+        self.inputs.push(Input {
+            name: name.to_string(),
+            value_type,
+            slot,
+        });
+        self
+    }
+
+    pub fn compile(&self, source: &str) -> Result<Program, CompileError> {
+        let mut parser = Parser {
+            tokens: lex(source)?,
+            pos: 0,
+        };
+        let expr = parser.expr()?;
+        if let Some(token) = parser.peek() {
+            return Err(CompileError::UnexpectedToken(token.to_string()));
+        }
+
+        let mut code = Vec::new();
+        let value_type = self.infer(&expr)?.unwrap_or(DEFAULT_TYPE);
+        self.emit(&expr, value_type, &mut code)?;
+        Ok(Program {
+            inputs: self.inputs.clone(),
+            code,
+        })
+    }
+
+    fn lookup(&self, name: &str) -> Result<&Input, CompileError> {
+        self.inputs
+            .iter()
+            .find(|input| input.name == name)
+            .ok_or_else(|| CompileError::UnknownVariable(name.to_string()))
+    }
+
+    // Type checks an expression and returns its type, or None when its width
+    // comes from the context: it is built only from integer literals, or is a
+    // ?: whose branches are
+    fn infer(&self, expr: &Expr) -> Result<Option<ValueType>, CompileError> {
+        match expr {
+            Expr::Literal(_) => Ok(None),
+            Expr::Bool(_) => Ok(Some(ValueType::Bool)),
+            Expr::Var(name) => Ok(Some(self.lookup(name)?.value_type)),
+            Expr::Unary(op, operand) => {
+                let value_type = self.infer(operand)?;
+                if matches!(op, OpCode::Neg) && value_type.is_some_and(ValueType::is_bool) {
+                    return Err(CompileError::TypeMismatch("cannot negate a bool".into()));
+                }
+                Ok(value_type)
+            }
+            Expr::Binary(op, lhs, rhs) => {
+                let value_type = unify(self.infer(lhs)?, self.infer(rhs)?)?;
+                let is_bool = value_type.is_some_and(ValueType::is_bool);
+                match op {
+                    OpCode::And | OpCode::Or | OpCode::Xor => Ok(value_type),
+                    OpCode::Eq | OpCode::Neq => Ok(Some(comparison_type(value_type))),
+                    OpCode::Lt | OpCode::Lte | OpCode::Gt | OpCode::Gte if !is_bool => {
+                        Ok(Some(comparison_type(value_type)))
+                    }
+                    _ if !is_bool => Ok(value_type),
+                    _ => Err(CompileError::TypeMismatch(format!(
+                        "{:?} needs integer operands",
+                        op
+                    ))),
+                }
+            }
+            Expr::Logical(op, lhs, rhs) => {
+                let (lhs, rhs) = (self.infer(lhs)?, self.infer(rhs)?);
+                if !lhs.is_some_and(ValueType::is_bool) || !rhs.is_some_and(ValueType::is_bool) {
+                    return Err(CompileError::TypeMismatch(format!(
+                        "{:?} needs bool operands",
+                        op
+                    )));
+                }
+                unify(lhs, rhs)
+            }
+            Expr::Select(condition, then, otherwise) => {
+                if !self.infer(condition)?.is_some_and(ValueType::is_bool) {
+                    return Err(CompileError::TypeMismatch(
+                        "the condition of ?: must be a bool".into(),
+                    ));
+                }
+                match unify(self.infer(then)?, self.infer(otherwise)?)? {
+                    Some(value_type) if value_type.is_bool() => Err(CompileError::TypeMismatch(
+                        "the branches of ?: must be integers".into(),
+                    )),
+                    value_type => Ok(value_type.map(ValueType::encrypted)),
+                }
+            }
+        }
+    }
+
+    // Emits an expression whose result has `value_type`, which decides the
+    // width of any literals it contains
+    fn emit(
+        &self,
+        expr: &Expr,
+        value_type: ValueType,
+        code: &mut Vec<OpCode>,
+    ) -> Result<(), CompileError> {
+        match expr {
+            Expr::Literal(val) => {
+                let value = value_type
+                    .plain_value(*val)
+                    .ok_or(CompileError::LiteralOutOfRange(*val, value_type))?;
+                code.push(OpCode::Push(value));
+            }
+            Expr::Bool(val) => code.push(OpCode::Push(Value::Bool(*val))),
+            Expr::Var(name) => code.push(OpCode::Load(self.lookup(name)?.slot)),
+            Expr::Unary(op, operand) => {
+                self.emit(operand, value_type, code)?;
+                code.push(op.clone());
+            }
+            Expr::Binary(op, lhs, rhs) | Expr::Logical(op, lhs, rhs) => {
+                // Comparisons are the only operators whose operands differ in
+                // type from their result
+                let operand_type = match op {
+                    OpCode::Eq
+                    | OpCode::Neq
+                    | OpCode::Lt
+                    | OpCode::Lte
+                    | OpCode::Gt
+                    | OpCode::Gte => {
+                        unify(self.infer(lhs)?, self.infer(rhs)?)?.unwrap_or(DEFAULT_TYPE)
+                    }
+                    _ => value_type,
+                };
+                self.emit(lhs, operand_type, code)?;
+                self.emit(rhs, operand_type, code)?;
+                code.push(op.clone());
+            }
+            Expr::Select(condition, then, otherwise) => {
+                // Literal branches take the width of the context, which may
+                // be a bool
+                if value_type.is_bool() {
+                    return Err(CompileError::TypeMismatch(
+                        "the branches of ?: must be integers".into(),
+                    ));
+                }
+                let condition_type = self.infer(condition)?.unwrap_or(ValueType::Bool);
+                self.emit(condition, condition_type, code)?;
+                self.emit(then, value_type, code)?;
+                self.emit(otherwise, value_type, code)?;
+                code.push(OpCode::Mux);
+            }
+        }
+        Ok(())
+    }
+}
+
+// Width given to expressions made only of literals, as in Rust
+const DEFAULT_TYPE: ValueType = ValueType::Uint32;
+
+fn unify(
+    lhs: Option<ValueType>,
+    rhs: Option<ValueType>,
+) -> Result<Option<ValueType>, CompileError> {
+    match (lhs, rhs) {
+        (None, value_type) | (value_type, None) => Ok(value_type),
+        (Some(lhs), Some(rhs)) if lhs.plaintext() == rhs.plaintext() => {
+            Ok(Some(if lhs.is_encrypted() { lhs } else { rhs }))
+        }
+        (Some(lhs), Some(rhs)) => Err(CompileError::TypeMismatch(format!(
+            "{:?} and {:?} have different widths",
+            lhs, rhs
+        ))),
+    }
+}
+
+fn comparison_type(operands: Option<ValueType>) -> ValueType {
+    if operands.is_some_and(ValueType::is_encrypted) {
+        ValueType::Ebool
+    } else {
+        ValueType::Bool
+    }
+}
+
+enum Expr {
+    Literal(u128),
+    Bool(bool),
+    Var(String),
+    Unary(OpCode, Box<Expr>),
+    Binary(OpCode, Box<Expr>, Box<Expr>),
+    Logical(OpCode, Box<Expr>, Box<Expr>), // && and ||, which only take bools
+    Select(Box<Expr>, Box<Expr>, Box<Expr>), // condition ? then : otherwise
+}
+
+#[derive(Clone)]
+enum Token {
+    Num(u128),
+    Ident(String),
+    Sym(&'static str),
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Num(val) => write!(f, "{}", val),
+            Token::Ident(name) => write!(f, "{}", name),
+            Token::Sym(sym) => write!(f, "{}", sym),
+        }
+    }
+}
+
+// Longer symbols come first so that `<=` isn't lexed as `<` then `=`
+const SYMBOLS: [&str; 24] = [
+    "<=", ">=", "==", "!=", "<<", ">>", "&&", "||", "+", "-", "*", "/", "%", "&", "|", "^", "!",
+    "<", ">", "(", ")", ",", "?", ":",
+];
+
+fn lex(source: &str) -> Result<Vec<Token>, CompileError> {
+    let mut tokens = Vec::new();
+    let mut rest = source;
+    while let Some(c) = rest.chars().next() {
+        let offset = source.len() - rest.len();
+        if c.is_whitespace() {
+            rest = &rest[c.len_utf8()..];
+        } else if c.is_ascii_alphanumeric() || c == '_' {
+            let end = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(rest.len());
+            let word = &rest[..end];
+            rest = &rest[end..];
+            if !c.is_ascii_digit() {
+                tokens.push(Token::Ident(word.to_string()));
+                continue;
+            }
+            let parsed = match word.strip_prefix("0x") {
+                Some(hex) => u128::from_str_radix(&hex.replace('_', ""), 16),
+                None => word.replace('_', "").parse(),
+            };
+            let val = parsed.map_err(|_| CompileError::UnexpectedToken(word.to_string()))?;
+            tokens.push(Token::Num(val));
+        } else if let Some(sym) = SYMBOLS.iter().find(|sym| rest.starts_with(*sym)) {
+            tokens.push(Token::Sym(sym));
+            rest = &rest[sym.len()..];
+        } else {
+            return Err(CompileError::UnexpectedChar(c, offset));
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Result<Token, CompileError> {
+        let token = self.peek().cloned().ok_or(CompileError::UnexpectedEnd)?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn eat(&mut self, sym: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Sym(found)) if *found == sym) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, sym: &str) -> Result<(), CompileError> {
+        match self.next()? {
+            Token::Sym(found) if found == sym => Ok(()),
+            token => Err(CompileError::UnexpectedToken(token.to_string())),
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr, CompileError> {
+        let condition = self.binary(0)?;
+        if !self.eat("?") {
+            return Ok(condition);
+        }
+        let then = self.expr()?;
+        self.expect(":")?;
+        let otherwise = self.expr()?;
+        Ok(Expr::Select(
+            Box::new(condition),
+            Box::new(then),
+            Box::new(otherwise),
+        ))
+    }
+
+    // Parses the left-associative binary operators from precedence `level` up
+    fn binary(&mut self, level: usize) -> Result<Expr, CompileError> {
+        let Some(operators) = BINARY_LEVELS.get(level) else {
+            return self.unary();
+        };
+        let mut lhs = self.binary(level + 1)?;
+        'operators: loop {
+            for (sym, op) in operators.iter() {
+                if self.eat(sym) {
+                    let rhs = self.binary(level + 1)?;
+                    lhs = match *sym {
+                        "&&" | "||" => Expr::Logical(op.clone(), Box::new(lhs), Box::new(rhs)),
+                        _ => Expr::Binary(op.clone(), Box::new(lhs), Box::new(rhs)),
+                    };
+                    continue 'operators;
+                }
+            }
+            return Ok(lhs);
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, CompileError> {
+        if self.eat("-") {
+            return Ok(Expr::Unary(OpCode::Neg, Box::new(self.unary()?)));
+        }
+        if self.eat("!") {
+            return Ok(Expr::Unary(OpCode::Not, Box::new(self.unary()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr, CompileError> {
+        match self.next()? {
+            Token::Num(val) => Ok(Expr::Literal(val)),
+            Token::Ident(name) if name == "true" => Ok(Expr::Bool(true)),
+            Token::Ident(name) if name == "false" => Ok(Expr::Bool(false)),
+            Token::Ident(name) if self.eat("(") => {
+                let op = match name.as_str() {
+                    "min" => OpCode::Min,
+                    "max" => OpCode::Max,
+                    _ => return Err(CompileError::UnknownFunction(name)),
+                };
+                let lhs = self.expr()?;
+                self.expect(",")?;
+                let rhs = self.expr()?;
+                self.expect(")")?;
+                Ok(Expr::Binary(op, Box::new(lhs), Box::new(rhs)))
+            }
+            Token::Ident(name) => Ok(Expr::Var(name)),
+            Token::Sym("(") => {
+                let expr = self.expr()?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            token => Err(CompileError::UnexpectedToken(token.to_string())),
+        }
+    }
+}
+
+// Binary operators grouped by precedence, loosest first
+const BINARY_LEVELS: [&[(&str, OpCode)]; 7] = [
+    &[("|", OpCode::Or), ("||", OpCode::Or)],
+    &[("^", OpCode::Xor)],
+    &[("&", OpCode::And), ("&&", OpCode::And)],
+    &[
+        ("==", OpCode::Eq),
+        ("!=", OpCode::Neq),
+        ("<", OpCode::Lt),
+        ("<=", OpCode::Lte),
+        (">", OpCode::Gt),
+        (">=", OpCode::Gte),
+    ],
+    &[("<<", OpCode::ShiftLeft), (">>", OpCode::ShiftRight)],
+    &[("+", OpCode::Add), ("-", OpCode::Sub)],
+    &[("*", OpCode::Mul), ("/", OpCode::Div), ("%", OpCode::Rem)],
+];
@@ -0,0 +1,177 @@
+use crate::client::{self, Plain};
+use crate::error::DecryptError;
+use crate::value::{Value, ValueType};
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::RandomState;
+use std::collections::HashSet;
+use std::hash::BuildHasher;
+use std::sync::Arc;
+use tfhe::ClientKey;
+
+// Threshold release of VM outputs. No single party holds the whole decryption
+// key: each key holder turns a result into a DecryptionShare, and a Combiner
+// recovers the plaintext once enough shares have been collected. The traits
+// don't assume a protocol, so a real threshold scheme can be plugged in behind
+// them.
+//
+//     let shares: Vec<_> = holders.iter().map(|h| h.partial_decrypt(&result)).collect();
+//     let plain = combiner.combine(&shares?)?;
+
+// One party's contribution towards decrypting a value. The payload is opaque
+// so each protocol can carry its own share format.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DecryptionShare {
+    pub party: u32,
+    pub value_type: ValueType,
+    pub payload: Vec<u8>,
+}
+
+pub trait KeyShare {
+    fn party(&self) -> u32;
+    fn partial_decrypt(&self, value: &Value) -> DecryptionShare;
+}
+
+pub trait Combiner {
+    // Number of shares needed to recover a plaintext
+    fn threshold(&self) -> usize;
+    fn combine(&self, shares: &[DecryptionShare]) -> Result<Plain, DecryptError>;
+}
+
+// In-process stand-in for a threshold protocol, for tests only. Every key
+// share holds the full ClientKey and Shamir-shares the decrypted plaintext, so
+// combining behaves like the real thing but nothing is kept secret.
+pub struct MockKeyShare {
+    party: u32,
+    threshold: usize,
+    key: Arc<ClientKey>,
+    // Shared by all parties so they derive the same polynomial for a value
+    state: RandomState,
+}
+
+pub struct MockCombiner {
+    threshold: usize,
+}
+
+// Deals `parties` key shares, any `threshold` of which can decrypt. Panics
+// unless 1 <= threshold <= parties.
+pub fn mock(
+    client_key: ClientKey,
+    parties: u32,
+    threshold: usize,
+) -> (Vec<MockKeyShare>, MockCombiner) {
+    assert!(
+        threshold >= 1 && threshold <= parties as usize,
+        "threshold must be between 1 and the number of parties"
+    );
+    let key = Arc::new(client_key);
+    let state = RandomState::new();
+    let shares = (1..=parties)
+        .map(|party| MockKeyShare {
+            party,
+            threshold,
+            key: Arc::clone(&key),
+            state: state.clone(),
+        })
+        .collect();
+    (shares, MockCombiner { threshold })
+}
+
+// Shares are computed over GF(2^61 - 1), one polynomial per 32-bit limb of
+// the plaintext.
+const PRIME: u64 = (1 << 61) - 1;
+const LIMBS: usize = 4;
+
+fn mul_mod(a: u64, b: u64) -> u64 {
+    (a as u128 * b as u128 % PRIME as u128) as u64
+}
+
+fn pow_mod(mut base: u64, mut exp: u64) -> u64 {
+    let mut acc = 1;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            acc = mul_mod(acc, base);
+        }
+        base = mul_mod(base, base);
+        exp >>= 1;
+    }
+    acc
+}
+
+fn inv_mod(a: u64) -> u64 {
+    pow_mod(a, PRIME - 2)
+}
+
+impl KeyShare for MockKeyShare {
+    fn party(&self) -> u32 {
+        self.party
+    }
+
+    fn partial_decrypt(&self, value: &Value) -> DecryptionShare {
+        let plain = client::decrypt(value, &self.key).to_u128();
+        let digest = bincode::serialize(value).unwrap();
+        let x = self.party as u64;
+        let mut points = [0u64; LIMBS];
+        for (limb, point) in points.iter_mut().enumerate() {
+            // Horner's rule over the random coefficients, highest degree first
+            let mut acc = 0;
+            for degree in (1..self.threshold).rev() {
+                let coefficient = self.state.hash_one((&digest, limb, degree)) % PRIME;
+                acc = mul_mod((acc + coefficient) % PRIME, x);
+            }
+            let secret = (plain >> (32 * limb)) as u32 as u64;
+            *point = (acc + secret) % PRIME;
+        }
+        DecryptionShare {
+            party: self.party,
+            value_type: value.value_type().plaintext(),
+            payload: bincode::serialize(&points).unwrap(),
+        }
+    }
+}
+
+impl Combiner for MockCombiner {
+    fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    fn combine(&self, shares: &[DecryptionShare]) -> Result<Plain, DecryptError> {
+        if shares.len() < self.threshold {
+            return Err(DecryptError::NotEnoughShares(self.threshold, shares.len()));
+        }
+        let mut parties = HashSet::new();
+        for share in shares {
+            if share.value_type != shares[0].value_type {
+                return Err(DecryptError::MismatchedShares);
+            }
+            if !parties.insert(share.party) {
+                return Err(DecryptError::DuplicateShare(share.party));
+            }
+        }
+
+        // Lagrange interpolation at zero over the first `threshold` shares
+        let shares = &shares[..self.threshold];
+        let mut limbs = [0u64; LIMBS];
+        for share in shares {
+            let points: [u64; LIMBS] = bincode::deserialize(&share.payload)
+                .map_err(|_| DecryptError::InvalidShare(share.party))?;
+            let x = share.party as u64 % PRIME;
+            let mut weight = 1;
+            for other in shares.iter().filter(|other| other.party != share.party) {
+                let y = other.party as u64 % PRIME;
+                weight = mul_mod(weight, mul_mod(y, inv_mod((y + PRIME - x) % PRIME)));
+            }
+            for (limb, point) in limbs.iter_mut().zip(points) {
+                *limb = (*limb + mul_mod(weight, point % PRIME)) % PRIME;
+            }
+        }
+
+        let mut plain = 0u128;
+        for (index, limb) in limbs.iter().enumerate() {
+            if *limb >> 32 != 0 {
+                return Err(DecryptError::MismatchedShares);
+            }
+            plain |= (*limb as u128) << (32 * index);
+        }
+        Plain::new(plain, shares[0].value_type).ok_or(DecryptError::MismatchedShares)
+    }
+}
@@ -0,0 +1,134 @@
+use crate::value::ValueType;
+use std::fmt;
+
+#[derive(Debug)]
+pub enum DecodeError {
+    UnexpectedEof,           // Input ended in the middle of an opcode or value
+    UnknownOpCode(u8),       // Opcode tag not recognised
+    UnknownValueTag(u8),     // Value tag not recognised
+    Bincode(bincode::Error), // Payload could not be decoded
+    Expand(tfhe::Error),     // Compact ciphertext list could not be expanded
+    InvalidBundle(String),   // Input bundle whose names and ciphertexts don't match
+    InvalidImmediate,        // Immediate operand that isn't a plaintext value
+    InvalidWidth(u32),       // Integer width other than 8, 16, 32, 64 or 128
+    NotUnary(u8),            // VMap over an opcode that isn't unary
+    UnknownTable(u32),       // Lut id past the end of the table section
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::UnexpectedEof => write!(f, "unexpected end of input"),
+            DecodeError::UnknownOpCode(tag) => write!(f, "unknown opcode tag {}", tag),
+            DecodeError::UnknownValueTag(tag) => write!(f, "unknown value tag {}", tag),
+            DecodeError::Bincode(err) => write!(f, "invalid value payload: {}", err),
+            DecodeError::Expand(err) => write!(f, "cannot expand ciphertext list: {}", err),
+            DecodeError::InvalidBundle(message) => write!(f, "invalid input bundle: {}", message),
+            DecodeError::InvalidImmediate => write!(f, "immediate must be a plaintext value"),
+            DecodeError::InvalidWidth(width) => write!(f, "invalid integer width {}", width),
+            DecodeError::NotUnary(tag) => write!(f, "VMap over non-unary opcode tag {}", tag),
+            DecodeError::UnknownTable(id) => write!(f, "unknown lookup table {}", id),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+impl From<bincode::Error> for DecodeError {
+    fn from(err: bincode::Error) -> Self {
+        DecodeError::Bincode(err)
+    }
+}
+
+impl From<tfhe::Error> for DecodeError {
+    fn from(err: tfhe::Error) -> Self {
+        DecodeError::Expand(err)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CompileError {
+    UnexpectedChar(char, usize), // Character the lexer can't start a token with, and its offset
+    UnexpectedToken(String),     // Token the parser didn't expect here
+    UnexpectedEnd,               // Expression ended early
+    UnknownVariable(String),     // Name that was never declared as an input
+    UnknownFunction(String),     // Call to anything other than min or max
+    LiteralOutOfRange(u128, ValueType), // Literal doesn't fit the width it is used at
+    TypeMismatch(String),        // Operands of the wrong kind or of different widths
+    MissingInput(String),        // Input left unbound when building the program
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompileError::UnexpectedChar(c, offset) => {
+                write!(f, "unexpected character {:?} at offset {}", c, offset)
+            }
+            CompileError::UnexpectedToken(token) => write!(f, "unexpected token {}", token),
+            CompileError::UnexpectedEnd => write!(f, "unexpected end of expression"),
+            CompileError::UnknownVariable(name) => write!(f, "unknown variable {}", name),
+            CompileError::UnknownFunction(name) => write!(f, "unknown function {}", name),
+            CompileError::LiteralOutOfRange(val, ty) => {
+                write!(f, "literal {} does not fit in {:?}", val, ty)
+            }
+            CompileError::TypeMismatch(message) => write!(f, "type mismatch: {}", message),
+            CompileError::MissingInput(name) => write!(f, "no value bound to input {}", name),
+        }
+    }
+}
+
+impl std::error::Error for CompileError {}
+
+#[derive(Debug, PartialEq)]
+pub enum DecryptError {
+    NotEnoughShares(usize, usize), // Shares needed, shares given
+    DuplicateShare(u32),           // Two shares from the same party
+    MismatchedShares,              // Shares of values with different types
+    InvalidShare(u32),             // Share payload could not be decoded
+}
+
+impl fmt::Display for DecryptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecryptError::NotEnoughShares(needed, given) => {
+                write!(f, "need {} decryption shares, got {}", needed, given)
+            }
+            DecryptError::DuplicateShare(party) => {
+                write!(f, "more than one share from party {}", party)
+            }
+            DecryptError::MismatchedShares => write!(f, "shares are for different values"),
+            DecryptError::InvalidShare(party) => write!(f, "invalid share from party {}", party),
+        }
+    }
+}
+
+impl std::error::Error for DecryptError {}
+
+#[derive(Debug)]
+pub enum SealError {
+    Decode(DecodeError),   // Container or module could not be decoded
+    BadSignature,          // Signature doesn't match the module bytes
+    Untrusted,             // Signed by a key the VM doesn't trust
+    StackUnderflow(usize), // Instruction that found too few items on the stack
+    NoServerKey,           // Snapshot of ciphertexts on a VM without a server key
+}
+
+impl fmt::Display for SealError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SealError::Decode(err) => write!(f, "invalid sealed module: {}", err),
+            SealError::BadSignature => write!(f, "module signature does not verify"),
+            SealError::Untrusted => write!(f, "module is not signed by a trusted key"),
+            SealError::StackUnderflow(ip) => write!(f, "stack underflow at instruction {}", ip),
+            SealError::NoServerKey => write!(f, "compressing ciphertexts needs VM::set_server_key"),
+        }
+    }
+}
+
+impl std::error::Error for SealError {}
+
+impl From<DecodeError> for SealError {
+    fn from(err: DecodeError) -> Self {
+        SealError::Decode(err)
+    }
+}
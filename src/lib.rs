@@ -1,3 +1,10 @@
+pub mod builder;
+pub mod client;
+pub mod compiler;
+pub mod decrypt;
+pub mod error;
+pub mod seal;
 mod test;
+mod transcript;
 pub mod value;
 pub mod vm;
@@ -25,7 +25,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         OpCode::Add,
     ];
     println!("bytecode: {:?}", bytecode);
-    vm.execute(&bytecode);
+    vm.execute(&bytecode)?;
 
     let encrypted_res = vm.pop();
     let clear_res: u16 = encrypted_res.as_eint16().decrypt(&client_key);
@@ -0,0 +1,60 @@
+use crate::error::{DecodeError, SealError};
+use crate::vm::Module;
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+
+// Modules signed by their author, so a server can't alter the code or tables
+// it was given without being noticed. The signature is ed25519 over the bytes
+// of Module::to_bytes.
+//
+// Encoded as the signer's public key (32 bytes), the signature (64 bytes), then
+// the module bytes.
+#[derive(Clone)]
+pub struct SealedModule {
+    signer: VerifyingKey,
+    signature: Signature,
+    module: Vec<u8>,
+}
+
+impl SealedModule {
+    pub fn seal(module: &Module, key: &SigningKey) -> Self {
+        let module = module.to_bytes();
+        SealedModule {
+            signer: key.verifying_key(),
+            signature: key.sign(&module),
+            module,
+        }
+    }
+
+    pub fn signer(&self) -> &VerifyingKey {
+        &self.signer
+    }
+
+    // Checks the signature, then decodes the module
+    pub fn open(&self) -> Result<Module, SealError> {
+        self.signer
+            .verify_strict(&self.module, &self.signature)
+            .map_err(|_| SealError::BadSignature)?;
+        Ok(Module::from_bytes(&self.module)?)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.signer.to_bytes().to_vec();
+        bytes.extend(self.signature.to_bytes());
+        bytes.extend(&self.module);
+        bytes
+    }
+
+    // Only splits the container; the signature is checked by open
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SealError> {
+        if bytes.len() < 96 {
+            return Err(DecodeError::UnexpectedEof.into());
+        }
+        let signer = VerifyingKey::from_bytes(bytes[..32].try_into().unwrap())
+            .map_err(|_| SealError::BadSignature)?;
+        Ok(SealedModule {
+            signer,
+            signature: Signature::from_bytes(bytes[32..96].try_into().unwrap()),
+            module: bytes[96..].to_vec(),
+        })
+    }
+}
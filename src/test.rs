@@ -1,16 +1,878 @@
 #[cfg(test)]
 mod tests {
-    use crate::value::Value;
+    use crate::builder::{Bool, Euint16, Euint8, ProgramBuilder};
+    use crate::client::{self, InputBundle, Plain};
+    use crate::compiler::Compiler;
+    use crate::decrypt::{self, Combiner, KeyShare};
+    use crate::error::{CompileError, DecodeError, DecryptError, SealError};
+    use crate::seal::SealedModule;
+    use crate::value::{CompressedValue, Neg, Table, Value, ValueType};
     use crate::vm::deserialize;
     use crate::vm::serialize;
+    use crate::vm::Module;
     use crate::vm::OpCode;
+    use crate::vm::Status;
+    use crate::vm::UnaryOp;
     use crate::vm::VM;
+    use ed25519_dalek::SigningKey;
     use tfhe::prelude::*;
     use tfhe::{generate_keys, set_server_key, ConfigBuilder};
-    use tfhe::{FheBool, FheUint128, FheUint16, FheUint32, FheUint64, FheUint8};
+    use tfhe::{
+        CompressedFheUint128, FheBool, FheUint128, FheUint16, FheUint32, FheUint64, FheUint8,
+    };
+
+    #[test]
+    fn test_add_i8_i16_promotion() -> Result<(), Box<dyn std::error::Error>> {
+        // Basic configuration to use homomorphic integers
+        let config = ConfigBuilder::default().build();
+
+        // Key generation
+        let (client_key, server_keys) = generate_keys(config);
+        // On the server side:
+        set_server_key(server_keys);
+
+        let a = 20u8;
+        let b = 5u16;
+
+        let enc_a = FheUint8::try_encrypt(a, &client_key)?;
+        let enc_b = FheUint16::try_encrypt(b, &client_key)?;
+
+        let mut vm = VM::new();
+        let bytecode = [
+            OpCode::Push(Value::Euint8(enc_a)),
+            OpCode::Push(Value::Euint16(enc_b)),
+            OpCode::Add,
+        ];
+        vm.execute(&bytecode).unwrap();
+
+        let encrypted_res = vm.pop();
+        let clear_res: u16 = encrypted_res.as_eint16().decrypt(&client_key);
+        assert_eq!(clear_res, 25);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_add() -> Result<(), Box<dyn std::error::Error>> {
+        // Basic configuration to use homomorphic integers
+        let config = ConfigBuilder::default().build();
+
+        // Key generation
+        let (client_key, server_keys) = generate_keys(config);
+        // On the server side:
+        set_server_key(server_keys);
+
+        let a = 1u8;
+        let b = 2u8;
+
+        let enc_a = FheUint8::try_encrypt(a, &client_key)?;
+        let enc_b = FheUint8::try_encrypt(b, &client_key)?;
+        let mut vm = VM::new();
+        let bytecode = [
+            OpCode::Push(Value::Euint8(enc_a)),
+            OpCode::Push(Value::Euint8(enc_b)),
+            OpCode::Add,
+        ];
+        vm.execute(&bytecode).unwrap();
+        let encrypted_res = vm.pop();
+        let clear_res: u8 = encrypted_res.as_eint8().decrypt(&client_key);
+        assert_eq!(clear_res, 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_subtract_ciphertexts() -> Result<(), Box<dyn std::error::Error>> {
+        // Basic configuration to use homomorphic integers
+        let config = ConfigBuilder::default().build();
+
+        // Key generation
+        let (client_key, server_keys) = generate_keys(config);
+        // On the server side:
+        set_server_key(server_keys);
+
+        let a = 20u8;
+        let b = 5u8;
+
+        let enc_a = FheUint8::try_encrypt(a, &client_key)?;
+        let enc_b = FheUint8::try_encrypt(b, &client_key)?;
+
+        let mut vm = VM::new();
+        let bytecode = [
+            OpCode::Push(Value::Euint8(enc_a)),
+            OpCode::Push(Value::Euint8(enc_b)),
+            OpCode::Sub,
+        ];
+        vm.execute(&bytecode).unwrap();
+
+        let encrypted_res = vm.pop();
+        let clear_res: u8 = encrypted_res.as_eint8().decrypt(&client_key);
+        assert_eq!(clear_res, 15);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_multiply() -> Result<(), Box<dyn std::error::Error>> {
+        // Basic configuration to use homomorphic integers
+        let config = ConfigBuilder::default().build();
+
+        // Key generation
+        let (client_key, server_keys) = generate_keys(config);
+        // On the server side:
+        set_server_key(server_keys);
+
+        let a = 2u8;
+        let b = 3u8;
+
+        let enc_a = FheUint8::try_encrypt(a, &client_key)?;
+        let enc_b = FheUint8::try_encrypt(b, &client_key)?;
+        let mut vm = VM::new();
+        let bytecode = [
+            OpCode::Push(Value::Euint8(enc_a)),
+            OpCode::Push(Value::Euint8(enc_b)),
+            OpCode::Mul,
+        ];
+        vm.execute(&bytecode).unwrap();
+        let encrypted_res = vm.pop();
+        let clear_res: u8 = encrypted_res.as_eint8().decrypt(&client_key);
+        assert_eq!(clear_res, 6);
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_checked_overflow() -> Result<(), Box<dyn std::error::Error>> {
+        // Basic configuration to use homomorphic integers
+        let config = ConfigBuilder::default().build();
+
+        // Key generation
+        let (client_key, server_keys) = generate_keys(config);
+        // On the server side:
+        set_server_key(server_keys);
+
+        let enc_a = FheUint8::try_encrypt(250u8, &client_key)?;
+        let enc_b = FheUint8::try_encrypt(10u8, &client_key)?;
+        let mut vm = VM::new();
+        let bytecode = [
+            OpCode::Push(Value::Euint8(enc_a)),
+            OpCode::Push(Value::Euint8(enc_b)),
+            OpCode::AddChecked,
+        ];
+        vm.execute(&bytecode).unwrap();
+        let overflow: bool = vm.pop().as_ebool().decrypt(&client_key);
+        let clear_res: u8 = vm.pop().as_eint8().decrypt(&client_key);
+        assert!(overflow);
+        assert_eq!(clear_res, 4);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sub_mul_checked_with_plaintext() -> Result<(), Box<dyn std::error::Error>> {
+        // Basic configuration to use homomorphic integers
+        let config = ConfigBuilder::default().build();
+
+        // Key generation
+        let (client_key, server_keys) = generate_keys(config);
+        // On the server side:
+        set_server_key(server_keys);
+
+        let enc_a = FheUint16::try_encrypt(100u16, &client_key)?;
+        let enc_b = FheUint16::try_encrypt(20u16, &client_key)?;
+        let enc_c = FheUint16::try_encrypt(300u16, &client_key)?;
+        let enc_d = FheUint16::try_encrypt(65500u16, &client_key)?;
+        let mut vm = VM::new();
+        let bytecode = [
+            OpCode::Push(Value::Euint16(enc_a)),
+            OpCode::Push(Value::Uint16(101)),
+            OpCode::SubChecked,
+            OpCode::Push(Value::Uint16(1000)),
+            OpCode::Push(Value::Euint16(enc_b)),
+            OpCode::MulChecked,
+            // The narrower operand is promoted, so the high bits of the
+            // Euint16 are kept and only 16-bit overflow counts
+            OpCode::Push(Value::Euint16(enc_c)),
+            OpCode::Push(Value::Uint8(250)),
+            OpCode::AddChecked,
+            OpCode::Push(Value::Uint8(200)),
+            OpCode::Push(Value::Euint16(enc_d)),
+            OpCode::AddChecked,
+        ];
+        vm.execute(&bytecode).unwrap();
+        let overflow: bool = vm.pop().as_ebool().decrypt(&client_key);
+        let clear_res: u16 = vm.pop().as_eint16().decrypt(&client_key);
+        assert!(overflow);
+        assert_eq!(clear_res, 164);
+        let overflow: bool = vm.pop().as_ebool().decrypt(&client_key);
+        let clear_res: u16 = vm.pop().as_eint16().decrypt(&client_key);
+        assert!(!overflow);
+        assert_eq!(clear_res, 550);
+        let overflow: bool = vm.pop().as_ebool().decrypt(&client_key);
+        let clear_res: u16 = vm.pop().as_eint16().decrypt(&client_key);
+        assert!(!overflow);
+        assert_eq!(clear_res, 20000);
+        let overflow: bool = vm.pop().as_ebool().decrypt(&client_key);
+        let clear_res: u16 = vm.pop().as_eint16().decrypt(&client_key);
+        assert!(overflow);
+        assert_eq!(clear_res, u16::MAX);
+        Ok(())
+    }
+
+    #[test]
+    fn test_checked_arithmetic_plaintext() {
+        let mut vm = VM::new();
+        let bytecode = [
+            OpCode::Push(Value::Uint16(1000)),
+            OpCode::Push(Value::Uint8(1)),
+            OpCode::SubChecked,
+            // Booleans count as 0 or 1
+            OpCode::Push(Value::Bool(true)),
+            OpCode::Push(Value::Bool(true)),
+            OpCode::AddChecked,
+        ];
+        vm.execute(&bytecode).unwrap();
+        assert!(matches!(vm.pop(), Value::Bool(false)));
+        assert!(matches!(vm.pop(), Value::Uint8(2)));
+        assert!(matches!(vm.pop(), Value::Bool(false)));
+        assert!(matches!(vm.pop(), Value::Uint16(999)));
+    }
+
+    #[test]
+    fn test_saturating_arithmetic() -> Result<(), Box<dyn std::error::Error>> {
+        // Basic configuration to use homomorphic integers
+        let config = ConfigBuilder::default().build();
+
+        // Key generation
+        let (client_key, server_keys) = generate_keys(config);
+        // On the server side:
+        set_server_key(server_keys);
+
+        let enc_a = FheUint8::try_encrypt(250u8, &client_key)?;
+        let enc_b = FheUint16::try_encrypt(30u16, &client_key)?;
+        let enc_c = FheUint8::try_encrypt(20u8, &client_key)?;
+        let enc_d = FheUint16::try_encrypt(65530u16, &client_key)?;
+        let mut vm = VM::new();
+        let bytecode = [
+            OpCode::Push(Value::Euint8(enc_a)),
+            OpCode::Push(Value::Uint8(10)),
+            OpCode::AddSat,
+            OpCode::Push(Value::Euint16(enc_b)),
+            OpCode::Push(Value::Uint16(45)),
+            OpCode::SubSat,
+            OpCode::Push(Value::Euint8(enc_c)),
+            OpCode::Push(Value::Uint8(5)),
+            OpCode::MulSat,
+            // A wide ciphertext and a narrow plaintext saturate at the wider
+            // bound
+            OpCode::Push(Value::Euint16(enc_d.clone())),
+            OpCode::Push(Value::Uint8(10)),
+            OpCode::AddSat,
+            OpCode::Push(Value::Euint16(enc_d)),
+            OpCode::Push(Value::Uint8(1)),
+            OpCode::SubSat,
+        ];
+        vm.execute(&bytecode).unwrap();
+        let difference: u16 = vm.pop().as_eint16().decrypt(&client_key);
+        assert_eq!(difference, 65529);
+        let sum: u16 = vm.pop().as_eint16().decrypt(&client_key);
+        assert_eq!(sum, u16::MAX);
+        let product: u8 = vm.pop().as_eint8().decrypt(&client_key);
+        let difference: u16 = vm.pop().as_eint16().decrypt(&client_key);
+        let sum: u8 = vm.pop().as_eint8().decrypt(&client_key);
+        assert_eq!(product, 100);
+        assert_eq!(difference, 0);
+        assert_eq!(sum, u8::MAX);
+        Ok(())
+    }
+
+    #[test]
+    fn test_saturating_arithmetic_plaintext() {
+        let mut vm = VM::new();
+        let bytecode = [
+            OpCode::Push(Value::Uint32(3)),
+            OpCode::Push(Value::Uint8(4)),
+            OpCode::SubSat,
+            OpCode::Push(Value::Uint64(u64::MAX / 2)),
+            OpCode::Push(Value::Uint64(3)),
+            OpCode::MulSat,
+        ];
+        vm.execute(&bytecode).unwrap();
+        assert!(matches!(vm.pop(), Value::Uint64(u64::MAX)));
+        assert!(matches!(vm.pop(), Value::Uint32(0)));
+    }
+
+    #[test]
+    fn test_immediate_arithmetic() -> Result<(), Box<dyn std::error::Error>> {
+        // Basic configuration to use homomorphic integers
+        let config = ConfigBuilder::default().build();
+
+        // Key generation
+        let (client_key, server_keys) = generate_keys(config);
+        // On the server side:
+        set_server_key(server_keys);
+
+        let enc_a = FheUint16::try_encrypt(1000u16, &client_key)?;
+        let enc_b = FheBool::try_encrypt(true, &client_key)?;
+        let mut vm = VM::new();
+        let bytecode = [
+            OpCode::Push(Value::Euint16(enc_a)),
+            OpCode::AddImm(Value::Uint8(24)),
+            OpCode::MulImm(Value::Uint16(3)),
+            OpCode::SubImm(Value::Uint16(72)),
+            OpCode::Push(Value::Ebool(enc_b)),
+            OpCode::Inc,
+        ];
+        vm.execute(&bytecode).unwrap();
+        let flipped: bool = vm.pop().as_ebool().decrypt(&client_key);
+        let result: u16 = vm.pop().as_eint16().decrypt(&client_key);
+        assert!(!flipped);
+        assert_eq!(result, 3000);
+        Ok(())
+    }
+
+    #[test]
+    fn test_immediate_arithmetic_plaintext() {
+        let mut vm = VM::new();
+        let bytecode = [
+            OpCode::Push(Value::Uint8(200)),
+            OpCode::AddImm(Value::Uint8(100)),
+            OpCode::Push(Value::Bool(true)),
+            OpCode::Dec,
+            OpCode::MulImm(Value::Bool(true)),
+        ];
+        vm.execute(&bytecode).unwrap();
+        assert!(matches!(vm.pop(), Value::Bool(false)));
+        assert!(matches!(vm.pop(), Value::Uint8(44)));
+
+        // Only odd amounts flip a boolean
+        assert!(matches!(Value::Bool(true) + 2u8, Value::Bool(true)));
+        assert!(matches!(Value::Bool(true) - 3u8, Value::Bool(false)));
+    }
+
+    #[test]
+    fn test_immediate_wider_than_operand() {
+        let config = ConfigBuilder::default().build();
+        let (client_key, server_keys) = generate_keys(config);
+        set_server_key(server_keys);
+
+        let enc_a = FheUint8::try_encrypt(200u8, &client_key).unwrap();
+        let enc_b = FheBool::try_encrypt(true, &client_key).unwrap();
+        let mut vm = VM::new();
+        vm.execute(&[
+            OpCode::Push(Value::Uint8(200)),
+            OpCode::AddImm(Value::Uint16(300)),
+            OpCode::Push(Value::Euint8(enc_a)),
+            OpCode::AddImm(Value::Uint16(1)),
+            OpCode::Push(Value::Ebool(enc_b)),
+            OpCode::AddImm(Value::Uint8(3)),
+            OpCode::Push(Value::Bool(true)),
+            OpCode::MulImm(Value::Uint8(5)),
+        ])
+        .unwrap();
+        // The operand is widened to the immediate's width instead of panicking
+        assert!(matches!(vm.pop(), Value::Uint8(5)));
+        let flag: u8 = vm.pop().as_eint8().decrypt(&client_key);
+        assert_eq!(flag, 4);
+        let sum: u16 = vm.pop().as_eint16().decrypt(&client_key);
+        assert_eq!(sum, 201);
+        assert!(matches!(vm.pop(), Value::Uint16(500)));
+
+        // A hand-built encrypted immediate goes through the binary op
+        let imm = Value::Euint16(FheUint16::encrypt_trivial(2u16));
+        let product: u16 = Value::Uint8(100)
+            .mul_imm_op(&imm)
+            .as_eint16()
+            .decrypt(&client_key);
+        assert_eq!(product, 200);
+    }
+
+    #[test]
+    fn test_encrypted_immediate_rejected() {
+        let config = ConfigBuilder::default().build();
+        let (client_key, server_keys) = generate_keys(config);
+        set_server_key(server_keys);
+
+        let immediate = Value::Euint8(FheUint8::encrypt_trivial(3u8));
+        let compressed = CompressedFheUint128::try_encrypt(3u128, &client_key).unwrap();
+        for op in [
+            OpCode::AddImm(immediate.clone()),
+            OpCode::SubImm(Value::Compressed(Box::new(CompressedValue::Euint128(
+                compressed,
+            )))),
+            OpCode::VMap(0, 0, 1, UnaryOp::new(OpCode::MulImm(immediate)).unwrap()),
+        ] {
+            assert!(matches!(
+                deserialize(&serialize(&[op])),
+                Err(DecodeError::InvalidImmediate)
+            ));
+        }
+    }
+
+    #[test]
+    fn test_vmap_needs_unary_opcode() {
+        let vmap = |op| OpCode::VMap(0, 0, 1, UnaryOp::new(op).unwrap());
+        assert!(deserialize(&serialize(&[vmap(OpCode::Lut(0))])).is_ok());
+
+        // Splice the inner opcode in by hand, since no VMap can hold it
+        let mut bytes = serialize(&[vmap(OpCode::Inc)]);
+        bytes.pop();
+        for op in [
+            vmap(OpCode::Inc),
+            OpCode::Load(0),
+            OpCode::Push(Value::Uint8(1)),
+        ] {
+            let mut program = bytes.clone();
+            program.extend(serialize(&[op]));
+            assert!(matches!(
+                deserialize(&program),
+                Err(DecodeError::NotUnary(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_unary_op_rejects_binary_opcode() {
+        assert!(UnaryOp::new(OpCode::Dup).is_none());
+        assert!(matches!(
+            UnaryOp::try_from(OpCode::Add),
+            Err(DecodeError::NotUnary(0))
+        ));
+        // serde goes through the same check
+        let bytes = bincode::serialize(&OpCode::Add).unwrap();
+        assert!(bincode::deserialize::<UnaryOp>(&bytes).is_err());
+        let bytes = bincode::serialize(&OpCode::Inc).unwrap();
+        assert!(matches!(
+            bincode::deserialize::<UnaryOp>(&bytes).unwrap().op(),
+            OpCode::Inc
+        ));
+    }
+
+    #[test]
+    fn test_bool_to_int_width_rejected() {
+        let mut bytes = serialize(&[OpCode::BoolToInt(16)]);
+        assert!(deserialize(&bytes).is_ok());
+        bytes[1..5].copy_from_slice(&12u32.to_le_bytes());
+        assert!(matches!(
+            deserialize(&bytes),
+            Err(DecodeError::InvalidWidth(12))
+        ));
+    }
+
+    #[test]
+    fn test_stack_manipulation() {
+        let mut vm = VM::new();
+        let bytecode = [
+            OpCode::Push(Value::Uint8(1)),
+            OpCode::Push(Value::Uint8(2)),
+            OpCode::Push(Value::Uint8(3)),
+            OpCode::Push(Value::Uint8(4)),
+            OpCode::Rot,     // 1 3 4 2
+            OpCode::Over,    // 1 3 4 2 4
+            OpCode::Roll(4), // 3 4 2 4 1
+            OpCode::Pick(3), // 3 4 2 4 1 4
+            OpCode::Drop,    // 3 4 2 4 1
+            OpCode::Depth,   // 3 4 2 4 1 5
+        ];
+        vm.execute(&bytecode).unwrap();
+        let stack: Vec<u8> = vm
+            .stack
+            .iter()
+            .map(|value| match **value {
+                Value::Uint8(val) => val,
+                Value::Uint32(val) => val as u8,
+                _ => panic!("unexpected value"),
+            })
+            .collect();
+        assert_eq!(stack, [3, 4, 2, 4, 1, 5]);
+        assert!(matches!(*vm.stack[5], Value::Uint32(5)));
+    }
+
+    #[test]
+    fn test_execute_owned_copy_on_write() {
+        let mut vm = VM::new();
+        let program = vec![
+            OpCode::Push(Value::Uint8(5)),
+            OpCode::Dup,
+            OpCode::Inc, // Only the top copy changes
+            OpCode::Over,
+        ];
+        vm.execute_owned(program).unwrap();
+        assert!(matches!(vm.pop(), Value::Uint8(5)));
+        assert!(matches!(vm.pop(), Value::Uint8(6)));
+        assert!(matches!(vm.pop(), Value::Uint8(5)));
+    }
+
+    #[test]
+    fn test_pick_underflow() {
+        let mut vm = VM::new();
+        let result = vm.execute(&[OpCode::Push(Value::Uint8(1)), OpCode::Pick(1)]);
+        assert!(matches!(result, Err(SealError::StackUnderflow(1))));
+        // The failing instruction didn't run
+        assert_eq!(vm.ip(), 1);
+        assert!(matches!(vm.pop(), Value::Uint8(1)));
+
+        for op in [
+            OpCode::Drop,
+            OpCode::Over,
+            OpCode::Rot,
+            OpCode::Roll(2),
+            OpCode::Add,
+            OpCode::Mux,
+        ] {
+            let mut vm = VM::new();
+            assert!(matches!(
+                vm.execute_owned(vec![op]),
+                Err(SealError::StackUnderflow(0))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_compile_expression() -> Result<(), Box<dyn std::error::Error>> {
+        // Basic configuration to use homomorphic integers
+        let config = ConfigBuilder::default().build();
+
+        // Key generation
+        let (client_key, server_keys) = generate_keys(config);
+        // On the server side:
+        set_server_key(server_keys);
+
+        let program = Compiler::new()
+            .input("a", ValueType::Euint16)
+            .input("b", ValueType::Euint16)
+            .input("c", ValueType::Uint16)
+            .input("d", ValueType::Euint16)
+            .input("x", ValueType::Euint16)
+            .compile("max(a*b + c, 100) >= d ? x : 0xffff")?;
+        assert_eq!(program.slot("d"), Some(3));
+        let code = program.bind([
+            (
+                "a",
+                Value::Euint16(FheUint16::try_encrypt(3u16, &client_key)?),
+            ),
+            (
+                "b",
+                Value::Euint16(FheUint16::try_encrypt(40u16, &client_key)?),
+            ),
+            ("c", Value::Uint16(5)),
+            (
+                "d",
+                Value::Euint16(FheUint16::try_encrypt(126u16, &client_key)?),
+            ),
+            (
+                "x",
+                Value::Euint16(FheUint16::try_encrypt(7u16, &client_key)?),
+            ),
+        ])?;
+        let mut vm = VM::new();
+        vm.execute_owned(code).unwrap();
+        let clear_res: u16 = vm.pop().as_eint16().decrypt(&client_key);
+        assert_eq!(clear_res, 0xffff);
+        Ok(())
+    }
+
+    #[test]
+    fn test_compile_literal_widths() -> Result<(), CompileError> {
+        let program = Compiler::new()
+            .input("a", ValueType::Euint8)
+            .input("flag", ValueType::Ebool)
+            .compile("(a + 3) * 2 < 250 & !flag")?;
+        assert_eq!(
+            format!("{:?}", program.code),
+            "[Load(0), Push(Uint8: 3), Add, Push(Uint8: 2), Mul, Push(Uint8: 250), Lt, \
+             Load(1), Not, And]"
+        );
+        // Branches made only of literals take the width of the context too
+        let program = Compiler::new()
+            .input("a", ValueType::Euint8)
+            .input("flag", ValueType::Ebool)
+            .compile("a + (flag ? 1 : 0)")?;
+        assert_eq!(
+            format!("{:?}", program.code),
+            "[Load(0), Load(1), Push(Uint8: 1), Push(Uint8: 0), Mux, Add]"
+        );
+        let program = Compiler::new().compile("2 * 3")?;
+        assert_eq!(
+            format!("{:?}", program.code),
+            "[Push(Uint32: 2), Push(Uint32: 3), Mul]"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_compile_errors() {
+        let compiler = Compiler::new()
+            .input("a", ValueType::Euint8)
+            .input("b", ValueType::Euint16);
+        assert!(matches!(
+            compiler.compile("a + b"),
+            Err(CompileError::TypeMismatch(_))
+        ));
+        assert!(matches!(
+            compiler.compile("a + 256"),
+            Err(CompileError::LiteralOutOfRange(256, ValueType::Euint8))
+        ));
+        assert!(matches!(
+            compiler.compile("a ? b : b"),
+            Err(CompileError::TypeMismatch(_))
+        ));
+        // && and || are logical, not bitwise
+        assert!(matches!(
+            compiler.compile("2 && 1"),
+            Err(CompileError::TypeMismatch(_))
+        ));
+        assert!(matches!(
+            compiler.compile("a < 1 || b"),
+            Err(CompileError::TypeMismatch(_))
+        ));
+        assert!(matches!(
+            compiler.compile("(a < 1 ? 1 : 0) == true"),
+            Err(CompileError::TypeMismatch(_))
+        ));
+        assert!(matches!(
+            compiler.compile("a + c"),
+            Err(CompileError::UnknownVariable(name)) if name == "c"
+        ));
+        assert!(matches!(
+            compiler.compile("pow(a, 2)"),
+            Err(CompileError::UnknownFunction(name)) if name == "pow"
+        ));
+        assert!(matches!(
+            compiler.compile("a = 1"),
+            Err(CompileError::UnexpectedChar('=', 2))
+        ));
+        assert!(matches!(
+            compiler.compile("(a + 1"),
+            Err(CompileError::UnexpectedEnd)
+        ));
+        let program = compiler.compile("b - 1").unwrap();
+        assert!(matches!(
+            program.bind([("b", Value::Uint16(1))]),
+            Err(CompileError::MissingInput(name)) if name == "a"
+        ));
+    }
+
+    #[test]
+    fn test_builder_fibonacci() -> Result<(), Box<dyn std::error::Error>> {
+        // Basic configuration to use homomorphic integers
+        let config = ConfigBuilder::default().build();
+
+        // Key generation
+        let (client_key, server_keys) = generate_keys(config);
+        // On the server side:
+        set_server_key(server_keys);
+
+        // Fibonacci program. Jumps can't depend on encrypted values, so the
+        // loop is unrolled up to a public bound and iterations past max leave
+        // a and b unchanged.
+        let builder = ProgramBuilder::new();
+        let mut n = builder.input::<Euint8>("n");
+        let mut a = builder.input::<Euint8>("a");
+        let mut b = builder.input::<Euint8>("b");
+        let max = builder.input::<Euint8>("max");
+        for _ in 0..10 {
+            let running = n.lt(&max);
+            let next = &a + &b;
+            a = running.select(&b, &a);
+            b = running.select(&next, &b);
+            n = n + 1;
+        }
+        builder.output(&a);
+
+        let program = builder.build();
+        let code = program.bind([
+            ("n", Value::Euint8(FheUint8::try_encrypt(1u8, &client_key)?)),
+            ("a", Value::Euint8(FheUint8::try_encrypt(1u8, &client_key)?)),
+            ("b", Value::Euint8(FheUint8::try_encrypt(1u8, &client_key)?)),
+            (
+                "max",
+                Value::Euint8(FheUint8::try_encrypt(10u8, &client_key)?),
+            ),
+        ])?;
+        let mut vm = VM::new();
+        vm.execute_owned(code).unwrap();
+        let clear_res: u8 = vm.pop().as_eint8().decrypt(&client_key);
+        assert_eq!(clear_res, 55);
+        Ok(())
+    }
+
+    #[test]
+    fn test_builder_optimizes_slots() {
+        let builder = ProgramBuilder::new();
+        let a = builder.input::<Euint16>("a");
+        let b = builder.input::<Euint16>("b");
+        let _unused = &a - &b;
+        let sum = &a + &b;
+        builder.output(&(&sum * &sum + 1));
+        builder.output(&builder.constant::<Bool>(true));
+
+        let program = builder.build();
+        assert_eq!(program.slot("b"), Some(1));
+        assert_eq!(
+            format!("{:?}", program.code),
+            "[Load(0), Load(1), Sub, Drop, Load(0), Load(1), Add, Store(2), Load(2), Load(2), \
+             Mul, AddImm(Uint16: 1), Push(Bool: true)]"
+        );
+    }
+
+    #[test]
+    fn test_snapshot_and_resume() -> Result<(), Box<dyn std::error::Error>> {
+        // Basic configuration to use homomorphic integers
+        let config = ConfigBuilder::default().build();
+
+        // Key generation
+        let (client_key, server_keys) = generate_keys(config);
+        // On the server side:
+        set_server_key(server_keys.clone());
+
+        let enc_a = FheUint8::try_encrypt(5u8, &client_key)?;
+        let enc_b = FheUint8::try_encrypt(3u8, &client_key)?;
+        let bytecode = [
+            OpCode::Push(Value::Euint8(enc_a)),
+            OpCode::Store(0),
+            OpCode::Push(Value::Euint8(enc_b)),
+            OpCode::Load(0),
+            OpCode::Add,
+            OpCode::Load(0),
+            OpCode::Mul,
+        ];
+        let mut vm = VM::new();
+        assert_eq!(vm.resume(&bytecode, 4).unwrap(), Status::Paused);
+        assert_eq!(vm.ip(), 4);
+
+        // Compressing the ciphertexts needs the VM's own server key
+        assert!(matches!(vm.snapshot(), Err(SealError::NoServerKey)));
+        vm.set_server_key(server_keys);
+
+        // Continue on a fresh VM, as another worker would
+        let snapshot = vm.snapshot()?;
+        let mut vm = VM::new();
+        vm.restore(&snapshot)?;
+        assert_eq!(vm.ip(), 4);
+        assert_eq!(vm.resume(&bytecode, 2).unwrap(), Status::Paused);
+        assert_eq!(vm.resume(&bytecode, 2).unwrap(), Status::Finished);
+        let clear_res: u8 = vm.pop().as_eint8().decrypt(&client_key);
+        assert_eq!(clear_res, 40);
+        Ok(())
+    }
+
+    #[test]
+    fn test_snapshot_is_compressed() -> Result<(), Box<dyn std::error::Error>> {
+        // Basic configuration to use homomorphic integers
+        let config = ConfigBuilder::default().build();
+
+        // Key generation
+        let (client_key, server_keys) = generate_keys(config);
+        // On the server side:
+        set_server_key(server_keys.clone());
+
+        let value = Value::Euint64(FheUint64::try_encrypt(1234u64, &client_key)?);
+        let full_size = bincode::serialize(&value)?.len();
+        let mut vm = VM::new();
+        vm.set_server_key(server_keys);
+        vm.execute(&[OpCode::Push(value), OpCode::Store(0), OpCode::Load(0)])
+            .unwrap();
+        let snapshot = vm.snapshot()?;
+        // One copy in memory and one on the stack, together smaller than one
+        // full-size ciphertext
+        assert!(snapshot.len() < full_size);
+
+        let mut vm = VM::new();
+        vm.restore(&snapshot)?;
+        vm.execute(&[OpCode::Load(0), OpCode::Add, OpCode::Load(0), OpCode::Add])
+            .unwrap();
+        let clear_res: u64 = vm.pop().as_eint64().decrypt(&client_key);
+        assert_eq!(clear_res, 3702);
+        Ok(())
+    }
+
+    #[test]
+    fn test_restore_truncated_snapshot() {
+        let mut vm = VM::new();
+        vm.execute(&[OpCode::Push(Value::Uint64(7)), OpCode::Dup])
+            .unwrap();
+        // Plaintexts need no server key
+        let snapshot = vm.snapshot().unwrap();
+        let mut vm = VM::new();
+        assert!(vm.restore(&snapshot[..snapshot.len() - 1]).is_err());
+        vm.restore(&snapshot).unwrap();
+        assert_eq!(vm.resume(&[], 1).unwrap(), Status::Finished);
+        assert!(matches!(vm.pop(), Value::Uint64(7)));
+        assert!(matches!(vm.pop(), Value::Uint64(7)));
+    }
+
+    #[test]
+    fn test_compressed_program() -> Result<(), Box<dyn std::error::Error>> {
+        // Basic configuration to use homomorphic integers
+        let config = ConfigBuilder::default().build();
+
+        // Key generation
+        let (client_key, server_keys) = generate_keys(config);
+        // On the server side:
+        set_server_key(server_keys);
+
+        let compressed = |val| client::encrypt_compressed(val, ValueType::Euint128, &client_key);
+        let program = [
+            OpCode::Push(compressed(1 << 100)),
+            OpCode::Push(compressed(12345)),
+            OpCode::Add,
+        ];
+        let full = [
+            OpCode::Push(Value::Euint128(FheUint128::try_encrypt(
+                1u128 << 100,
+                &client_key,
+            )?)),
+            OpCode::Push(Value::Euint128(FheUint128::try_encrypt(
+                12345u128,
+                &client_key,
+            )?)),
+            OpCode::Add,
+        ];
+        let serialized = serialize(&program);
+        let full_size = serialize(&full).len();
+        assert!(serialized.len() * 50 < full_size);
+
+        let program = deserialize(&serialized)?;
+        assert!(matches!(program[0], OpCode::Push(Value::Compressed(_))));
+        let mut vm = VM::new();
+        vm.execute(&program).unwrap();
+        let clear_res: u128 = vm.pop().as_eint128().decrypt(&client_key);
+        assert_eq!(clear_res, (1 << 100) + 12345);
+
+        // Value operations outside the VM decompress their operands too
+        let small = client::encrypt_compressed(200, ValueType::Euint8, &client_key);
+        let flag = client::encrypt_compressed(1, ValueType::Ebool, &client_key);
+        let results = [
+            small.clone().add_imm_op(&Value::Uint16(100)),
+            small.clone().div_op(Value::Uint8(7)),
+            small.add_sat_op(&Value::Uint8(100)),
+            small.lt_op(&compressed(300)),
+            small.clone().lut_op(&std::array::from_fn(|i| i as u8 / 2)),
+            flag.and_op(&Value::Bool(true)),
+            flag.clone().bool_to_int_op(32),
+            small.neg() + 1u8,
+        ];
+        let plain = results.map(|value| client::decrypt(&value, &client_key));
+        assert_eq!(
+            plain,
+            [
+                Plain::Uint16(300),
+                Plain::Uint8(28),
+                Plain::Uint8(255),
+                Plain::Bool(true),
+                Plain::Uint8(100),
+                Plain::Bool(true),
+                Plain::Uint32(1),
+                Plain::Uint8(57),
+            ]
+        );
+        assert_eq!(
+            client::encrypt_compressed(5, ValueType::Uint8, &client_key).value_type(),
+            ValueType::Uint8
+        );
+        Ok(())
+    }
 
     #[test]
-    fn test_add_i8_i16_promotion() -> Result<(), Box<dyn std::error::Error>> {
+    fn test_client_encrypt_decrypt() -> Result<(), Box<dyn std::error::Error>> {
         // Basic configuration to use homomorphic integers
         let config = ConfigBuilder::default().build();
 
@@ -19,29 +881,70 @@ mod tests {
         // On the server side:
         set_server_key(server_keys);
 
-        let a = 20u8;
-        let b = 5u16;
-
-        let enc_a = FheUint8::try_encrypt(a, &client_key)?;
-        let enc_b = FheUint16::try_encrypt(b, &client_key)?;
+        let types = [
+            (ValueType::Ebool, 1),
+            (ValueType::Euint8, u8::MAX as u128),
+            (ValueType::Euint16, 1000),
+            (ValueType::Euint32, 1 << 31),
+            (ValueType::Euint64, 1 << 63),
+            (ValueType::Euint128, u128::MAX),
+            (ValueType::Uint16, 7),
+        ];
+        for (ty, val) in types {
+            let value = client::encrypt(val, ty, &client_key);
+            assert_eq!(value.value_type(), ty);
+            assert_eq!(client::decrypt(&value, &client_key).to_u128(), val);
+        }
 
+        // Results are decrypted without knowing their type up front
         let mut vm = VM::new();
         let bytecode = [
-            OpCode::Push(Value::Euint8(enc_a)),
-            OpCode::Push(Value::Euint16(enc_b)),
+            OpCode::Push(client::encrypt(200, ValueType::Euint8, &client_key)),
+            OpCode::Push(client::encrypt(100, ValueType::Euint16, &client_key)),
             OpCode::Add,
+            OpCode::Dup,
+            OpCode::Push(Value::Uint16(250)),
+            OpCode::Gt,
         ];
-        vm.execute(&bytecode);
+        vm.execute(&bytecode).unwrap();
+        assert_eq!(client::decrypt(&vm.pop(), &client_key), Plain::Bool(true));
+        assert_eq!(client::decrypt(&vm.pop(), &client_key), Plain::Uint16(300));
+        Ok(())
+    }
 
-        let encrypted_res = vm.pop();
-        let clear_res: u16 = encrypted_res.as_eint16().decrypt(&client_key);
-        assert_eq!(clear_res, 25);
+    #[test]
+    fn test_bind_public_key_bundles() -> Result<(), Box<dyn std::error::Error>> {
+        // Basic configuration to use homomorphic integers
+        let config = ConfigBuilder::default().build();
 
+        // Key generation
+        let (client_key, server_keys) = generate_keys(config);
+        let public_key = tfhe::CompactPublicKey::new(&client_key);
+        // On the server side:
+        set_server_key(server_keys);
+
+        // Two data providers, each holding only the public key
+        let bundle_a = InputBundle::encrypt(
+            &[("a", 3, ValueType::Euint16), ("flag", 1, ValueType::Ebool)],
+            &public_key,
+        );
+        let bundle_b = InputBundle::encrypt(&[("b", 40, ValueType::Euint16)], &public_key);
+        let bundle_a: InputBundle = bincode::deserialize(&bincode::serialize(&bundle_a)?)?;
+
+        let program = Compiler::new()
+            .input("a", ValueType::Euint16)
+            .input("b", ValueType::Euint16)
+            .input("flag", ValueType::Ebool)
+            .compile("flag ? a * b : 0")?;
+        let inputs = bundle_a.expand()?.into_iter().chain(bundle_b.expand()?);
+        let mut vm = VM::new();
+        vm.execute_owned(program.bind(inputs)?).unwrap();
+        assert_eq!(client::decrypt(&vm.pop(), &client_key), Plain::Uint16(120));
         Ok(())
     }
 
     #[test]
-    fn test_add() -> Result<(), Box<dyn std::error::Error>> {
+    fn test_threshold_decryption() -> Result<(), Box<dyn std::error::Error>> {
         // Basic configuration to use homomorphic integers
         let config = ConfigBuilder::default().build();
 
@@ -50,26 +953,58 @@ mod tests {
         // On the server side:
         set_server_key(server_keys);
 
-        let a = 1u8;
-        let b = 2u8;
-
-        let enc_a = FheUint8::try_encrypt(a, &client_key)?;
-        let enc_b = FheUint8::try_encrypt(b, &client_key)?;
         let mut vm = VM::new();
         let bytecode = [
-            OpCode::Push(Value::Euint8(enc_a)),
-            OpCode::Push(Value::Euint8(enc_b)),
+            OpCode::Push(client::encrypt(
+                u64::MAX as u128,
+                ValueType::Euint128,
+                &client_key,
+            )),
+            OpCode::Push(Value::Uint128(3 << 64)),
+            OpCode::Add,
+            OpCode::Push(client::encrypt(40, ValueType::Euint8, &client_key)),
+            OpCode::Push(client::encrypt(2, ValueType::Euint8, &client_key)),
             OpCode::Add,
+            OpCode::Dup,
+            OpCode::Push(Value::Uint8(42)),
+            OpCode::Eq,
         ];
-        vm.execute(&bytecode);
-        let encrypted_res = vm.pop();
-        let clear_res: u8 = encrypted_res.as_eint8().decrypt(&client_key);
-        assert_eq!(clear_res, 3);
+        vm.execute(&bytecode).unwrap();
+        let (holders, combiner) = decrypt::mock(client_key, 3, 2);
+
+        // Any two of the three parties can release a result
+        let flag = vm.pop();
+        let shares: Vec<_> = holders.iter().map(|h| h.partial_decrypt(&flag)).collect();
+        assert_eq!(combiner.combine(&shares[1..])?, Plain::Bool(true));
+        let sum = vm.pop();
+        let shares = [
+            holders[2].partial_decrypt(&sum),
+            holders[0].partial_decrypt(&sum),
+        ];
+        assert_eq!(combiner.combine(&shares)?, Plain::Uint8(42));
+        let wide = vm.pop();
+        let shares: Vec<_> = holders.iter().map(|h| h.partial_decrypt(&wide)).collect();
+        assert_eq!(combiner.combine(&shares)?, Plain::Uint128((4 << 64) - 1));
+
+        // A single party can't
+        assert_eq!(
+            combiner.combine(&shares[..1]),
+            Err(DecryptError::NotEnoughShares(2, 1))
+        );
+        assert_eq!(
+            combiner.combine(&[shares[0].clone(), shares[0].clone()]),
+            Err(DecryptError::DuplicateShare(1))
+        );
+        let other = holders[1].partial_decrypt(&flag);
+        assert_eq!(
+            combiner.combine(&[shares[0].clone(), other]),
+            Err(DecryptError::MismatchedShares)
+        );
         Ok(())
     }
 
     #[test]
-    fn test_subtract_ciphertexts() -> Result<(), Box<dyn std::error::Error>> {
+    fn test_bool_int_conversions() -> Result<(), Box<dyn std::error::Error>> {
         // Basic configuration to use homomorphic integers
         let config = ConfigBuilder::default().build();
 
@@ -78,29 +1013,421 @@ mod tests {
         // On the server side:
         set_server_key(server_keys);
 
-        let a = 20u8;
-        let b = 5u8;
+        // Count the elements equal to 7
+        let mut bytecode = vec![OpCode::Push(Value::Uint16(0))];
+        for val in [7u8, 3, 7, 0] {
+            bytecode.extend([
+                OpCode::Push(client::encrypt(val as u128, ValueType::Euint8, &client_key)),
+                OpCode::Push(Value::Uint8(7)),
+                OpCode::Eq,
+                OpCode::BoolToInt(16),
+                OpCode::Add,
+            ]);
+        }
+        // A nonzero count selects the first branch
+        bytecode.extend([
+            OpCode::Dup,
+            OpCode::IntToBool,
+            OpCode::Push(Value::Uint32(10)),
+            OpCode::Push(Value::Uint32(20)),
+            OpCode::Mux,
+        ]);
+        let mut vm = VM::new();
+        vm.execute(&bytecode).unwrap();
+        assert_eq!(client::decrypt(&vm.pop(), &client_key), Plain::Uint32(10));
+        assert_eq!(client::decrypt(&vm.pop(), &client_key), Plain::Uint16(2));
+
+        for width in [8, 16, 32, 64, 128] {
+            let ebool = client::encrypt(1, ValueType::Ebool, &client_key);
+            let int = ebool.bool_to_int_op(width);
+            assert_eq!(int.value_type().bits(), width);
+            assert_eq!(client::decrypt(&int, &client_key).to_u128(), 1);
+            let zero = client::encrypt(0, int.value_type(), &client_key);
+            assert_eq!(
+                client::decrypt(&zero.int_to_bool_op(), &client_key),
+                Plain::Bool(false)
+            );
+        }
+        let int = Value::Bool(true).bool_to_int_op(64);
+        assert_eq!(client::decrypt(&int, &client_key), Plain::Uint64(1));
+        let flag = Value::Uint128(1 << 100).int_to_bool_op();
+        assert_eq!(client::decrypt(&flag, &client_key), Plain::Bool(true));
 
-        let enc_a = FheUint8::try_encrypt(a, &client_key)?;
-        let enc_b = FheUint8::try_encrypt(b, &client_key)?;
+        // IntToBool leaves booleans alone and BoolToInt normalizes integers
+        let mut vm = VM::new();
+        vm.execute(&[
+            OpCode::Push(client::encrypt(1, ValueType::Ebool, &client_key)),
+            OpCode::IntToBool,
+            OpCode::Push(Value::Bool(false)),
+            OpCode::IntToBool,
+            OpCode::Push(client::encrypt(200, ValueType::Euint8, &client_key)),
+            OpCode::BoolToInt(32),
+            OpCode::Push(Value::Uint64(0)),
+            OpCode::BoolToInt(8),
+        ])
+        .unwrap();
+        assert_eq!(client::decrypt(&vm.pop(), &client_key), Plain::Uint8(0));
+        assert_eq!(client::decrypt(&vm.pop(), &client_key), Plain::Uint32(1));
+        assert_eq!(client::decrypt(&vm.pop(), &client_key), Plain::Bool(false));
+        assert_eq!(client::decrypt(&vm.pop(), &client_key), Plain::Bool(true));
+        Ok(())
+    }
+
+    #[test]
+    fn test_vector_opcodes() -> Result<(), Box<dyn std::error::Error>> {
+        // Basic configuration to use homomorphic integers
+        let config = ConfigBuilder::default().build();
+
+        // Key generation
+        let (client_key, server_keys) = generate_keys(config);
+        // On the server side:
+        set_server_key(server_keys.clone());
+
+        // a in 0..5, b in 5..10
+        let mut bytecode = Vec::new();
+        for (slot, val) in [1u8, 2, 3, 4, 5, 6, 7, 8, 9, 10].into_iter().enumerate() {
+            bytecode.push(OpCode::Push(client::encrypt(
+                val as u128,
+                ValueType::Euint8,
+                &client_key,
+            )));
+            bytecode.push(OpCode::Store(slot as i32));
+        }
+        bytecode.extend([
+            OpCode::VAdd(10, 0, 5, 5),
+            OpCode::VMul(15, 0, 5, 5),
+            OpCode::VMap(
+                20,
+                0,
+                5,
+                UnaryOp::new(OpCode::MulImm(Value::Uint8(3))).unwrap(),
+            ),
+            OpCode::VSum(10, 5),
+            OpCode::VDot(0, 5, 5),
+            OpCode::Load(12),
+            OpCode::Load(19),
+            OpCode::Load(24),
+        ]);
+
+        // The parallel and sequential paths agree
+        let mut parallel = VM::new();
+        parallel.set_server_key(server_keys);
+        for vm in [&mut parallel, &mut VM::new()] {
+            vm.execute(&bytecode).unwrap();
+            let mut results = Vec::new();
+            while let Some(value) = vm.stack.pop() {
+                results.push(client::decrypt(&value, &client_key));
+            }
+            assert_eq!(
+                results,
+                [
+                    Plain::Uint8(15),  // 5 * 3
+                    Plain::Uint8(50),  // 5 * 10
+                    Plain::Uint8(11),  // 3 + 8
+                    Plain::Uint8(130), // 6 + 14 + 24 + 36 + 50
+                    Plain::Uint8(55),  // (1 + ... + 5) + (6 + ... + 10)
+                ]
+            );
+        }
+
+        let bytes = serialize(&bytecode);
+        assert_eq!(
+            format!("{:?}", deserialize(&bytes)?),
+            format!("{:?}", bytecode)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_sorting_opcodes() -> Result<(), Box<dyn std::error::Error>> {
+        // Basic configuration to use homomorphic integers
+        let config = ConfigBuilder::default().build();
+
+        // Key generation
+        let (client_key, server_keys) = generate_keys(config);
+        // On the server side:
+        set_server_key(server_keys.clone());
+
+        fn store(values: &[Value]) -> Vec<OpCode> {
+            let mut bytecode = Vec::new();
+            for (slot, value) in values.iter().enumerate() {
+                bytecode.push(OpCode::Push(value.clone()));
+                bytecode.push(OpCode::Store(slot as i32));
+            }
+            bytecode
+        }
+
+        // Check the network on plaintexts of every length, duplicates included
+        let mut seed = 12345u32;
+        for len in 0..20u32 {
+            let keys: Vec<u32> = (0..len)
+                .map(|_| {
+                    seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+                    (seed >> 16) % 10
+                })
+                .collect();
+            let mut expected = keys.clone();
+            expected.sort();
+            let mut bytecode = store(&keys.iter().map(|&k| Value::Uint32(k)).collect::<Vec<_>>());
+            bytecode.push(OpCode::Sort(0, len));
+            bytecode.extend((0..len as i32).map(OpCode::Load));
+            let mut vm = VM::new();
+            vm.execute(&bytecode).unwrap();
+            let sorted: Vec<_> = vm
+                .stack
+                .iter()
+                .map(|v| client::decrypt(v, &client_key))
+                .collect();
+            assert_eq!(
+                sorted,
+                expected
+                    .iter()
+                    .map(|&k| Plain::Uint32(k))
+                    .collect::<Vec<_>>()
+            );
+
+            for k in 0..=len {
+                // Payloads record where each key started
+                let mut values: Vec<_> = keys.iter().map(|&k| Value::Uint32(k)).collect();
+                values.extend((0..len).map(Value::Uint32));
+                let mut bytecode = store(&values);
+                bytecode.push(OpCode::TopK(0, len as i32, len, k));
+                bytecode.extend((0..k as i32).map(OpCode::Load));
+                bytecode.extend((0..k as i32).map(|i| OpCode::Load(len as i32 + i)));
+                let mut vm = VM::new();
+                vm.execute(&bytecode).unwrap();
+                let results: Vec<_> = vm
+                    .stack
+                    .iter()
+                    .map(|v| client::decrypt(v, &client_key).to_u128() as u32)
+                    .collect();
+                let (top, payloads) = results.split_at(k as usize);
+                let largest: Vec<_> = expected.iter().rev().take(k as usize).copied().collect();
+                assert_eq!(top, largest);
+                for (key, payload) in top.iter().zip(payloads) {
+                    assert_eq!(keys[*payload as usize], *key);
+                }
+            }
+        }
 
+        // Encrypted bids, sorted in parallel
+        let bids = [30u8, 10, 50, 20, 50];
+        let values: Vec<_> = bids
+            .iter()
+            .map(|&bid| client::encrypt(bid as u128, ValueType::Euint8, &client_key))
+            .collect();
+        // Bids in 0..5, bidder ids in 5..10 and a copy of the bids in 10..15
+        let mut values = [values.clone(), values].concat();
+        let ids = (0..5).map(|id| client::encrypt(100 + id, ValueType::Euint8, &client_key));
+        values.splice(5..5, ids);
+        let mut bytecode = store(&values);
+        bytecode.extend([
+            OpCode::ArgMax(0, 5),
+            OpCode::Sort(10, 5),
+            OpCode::TopK(0, 5, 5, 2),
+        ]);
+        bytecode.extend([0, 1, 5, 6].map(OpCode::Load));
+        bytecode.extend((10..15).map(OpCode::Load));
         let mut vm = VM::new();
-        let bytecode = [
-            OpCode::Push(Value::Euint8(enc_a)),
-            OpCode::Push(Value::Euint8(enc_b)),
-            OpCode::Sub,
-        ];
-        vm.execute(&bytecode);
+        vm.set_server_key(server_keys);
+        vm.execute(&bytecode).unwrap();
+        let results: Vec<_> = vm
+            .stack
+            .iter()
+            .map(|v| client::decrypt(v, &client_key))
+            .collect();
+        assert_eq!(
+            results,
+            [
+                Plain::Uint8(50), // The maximum
+                Plain::Uint32(2), // and the index of its first occurrence
+                Plain::Uint8(50), // The two highest bids
+                Plain::Uint8(50),
+                Plain::Uint8(102), // and their bidders
+                Plain::Uint8(104),
+                Plain::Uint8(10),
+                Plain::Uint8(20),
+                Plain::Uint8(30),
+                Plain::Uint8(50),
+                Plain::Uint8(50),
+            ]
+        );
+        Ok(())
+    }
 
-        let encrypted_res = vm.pop();
-        let clear_res: u8 = encrypted_res.as_eint8().decrypt(&client_key);
-        assert_eq!(clear_res, 15);
+    #[test]
+    fn test_lookup_tables() -> Result<(), Box<dyn std::error::Error>> {
+        // Basic configuration to use homomorphic integers
+        let config = ConfigBuilder::default().build();
+
+        // Key generation
+        let (client_key, server_keys) = generate_keys(config);
+        // On the server side:
+        set_server_key(server_keys);
 
+        // A step function and a byte permutation
+        let step: Table = std::array::from_fn(|i| if i >= 128 { 255 } else { 0 });
+        let sbox: Table = std::array::from_fn(|i| (i as u8).wrapping_mul(167) ^ 0x63);
+        let module = Module {
+            tables: vec![step, sbox],
+            code: vec![
+                OpCode::Push(client::encrypt(200, ValueType::Euint8, &client_key)),
+                OpCode::Lut(0),
+                OpCode::Push(client::encrypt(0x1280, ValueType::Euint16, &client_key)),
+                OpCode::Lut(1),
+                OpCode::Push(Value::Uint32(0x0102_0304)),
+                OpCode::Lut(1),
+            ],
+        };
+        let module = Module::from_bytes(&module.to_bytes())?;
+        assert_eq!(module.tables, [step, sbox]);
+
+        let mut vm = VM::new();
+        vm.execute_module(&module).unwrap();
+        let expected = u32::from_le_bytes([4, 3, 2, 1].map(|b| sbox[b]));
+        assert_eq!(
+            client::decrypt(&vm.pop(), &client_key),
+            Plain::Uint32(expected)
+        );
+        let expected = u16::from_le_bytes([sbox[0x80], sbox[0x12]]);
+        assert_eq!(
+            client::decrypt(&vm.pop(), &client_key),
+            Plain::Uint16(expected)
+        );
+        assert_eq!(client::decrypt(&vm.pop(), &client_key), Plain::Uint8(255));
+
+        // VMap runs each element with the program's tables
+        let mut code = Vec::new();
+        for (address, val) in [(0, 3), (1, 200)] {
+            code.push(OpCode::Push(client::encrypt(
+                val,
+                ValueType::Euint8,
+                &client_key,
+            )));
+            code.push(OpCode::Store(address));
+        }
+        code.push(OpCode::VMap(2, 0, 2, UnaryOp::new(OpCode::Lut(1)).unwrap()));
+        code.push(OpCode::Load(2));
+        code.push(OpCode::Load(3));
+        let mapped = Module {
+            tables: vec![step, sbox],
+            code,
+        };
+        let mut vm = VM::new();
+        vm.execute_module(&mapped).unwrap();
+        assert_eq!(
+            client::decrypt(&vm.pop(), &client_key),
+            Plain::Uint8(sbox[200])
+        );
+        assert_eq!(
+            client::decrypt(&vm.pop(), &client_key),
+            Plain::Uint8(sbox[3])
+        );
+
+        // The table section must be complete
+        let bytes = module.to_bytes();
+        assert!(Module::from_bytes(&bytes[..100]).is_err());
+
+        // Every Lut, including one inside a VMap, must name a known table
+        let unknown = Module {
+            tables: vec![step],
+            code: vec![OpCode::VMap(0, 0, 1, UnaryOp::new(OpCode::Lut(1)).unwrap())],
+        };
+        assert!(matches!(
+            Module::from_bytes(&unknown.to_bytes()),
+            Err(DecodeError::UnknownTable(1))
+        ));
+        let mut vm = VM::new();
+        assert!(matches!(
+            vm.execute_module(&unknown),
+            Err(SealError::Decode(DecodeError::UnknownTable(1)))
+        ));
+        let mut vm = VM::new();
+        let result = vm.execute(&[OpCode::Push(Value::Uint8(1)), OpCode::Lut(0)]);
+        assert!(matches!(
+            result,
+            Err(SealError::Decode(DecodeError::UnknownTable(0)))
+        ));
         Ok(())
     }
 
     #[test]
-    fn test_multiply() -> Result<(), Box<dyn std::error::Error>> {
+    fn test_sealed_module() -> Result<(), Box<dyn std::error::Error>> {
+        let author = SigningKey::from_bytes(&[7; 32]);
+        let module = Module {
+            tables: vec![std::array::from_fn(|i| i as u8 ^ 1)],
+            code: vec![
+                OpCode::Push(Value::Uint8(40)),
+                OpCode::Push(Value::Uint8(2)),
+                OpCode::Add,
+                OpCode::Lut(0),
+            ],
+        };
+        let sealed = SealedModule::from_bytes(&SealedModule::seal(&module, &author).to_bytes())?;
+
+        let mut vm = VM::new();
+        vm.trust(author.verifying_key());
+        vm.execute_sealed(&sealed)?;
+        assert!(matches!(vm.pop(), Value::Uint8(43)));
+
+        // Any change to the code or tables breaks the signature
+        let mut bytes = sealed.to_bytes();
+        *bytes.last_mut().unwrap() ^= 1;
+        let tampered = SealedModule::from_bytes(&bytes)?;
+        assert!(matches!(
+            vm.execute_sealed(&tampered),
+            Err(SealError::BadSignature)
+        ));
+        bytes[100] ^= 1;
+        assert!(matches!(
+            SealedModule::from_bytes(&bytes)?.open(),
+            Err(SealError::BadSignature)
+        ));
+
+        // A valid signature from someone else isn't enough
+        let other = SealedModule::seal(&module, &SigningKey::from_bytes(&[8; 32]));
+        assert!(other.open().is_ok());
+        assert!(matches!(
+            vm.execute_sealed(&other),
+            Err(SealError::Untrusted)
+        ));
+        assert!(VM::new().execute_sealed(&other).is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn test_trusted_vm_refuses_unsealed_code() {
+        let code = [OpCode::Push(Value::Uint8(1))];
+        let module = Module {
+            tables: Vec::new(),
+            code: code.to_vec(),
+        };
+        let mut plain = VM::new();
+        plain.execute(&code).unwrap();
+        let snapshot = plain.snapshot().unwrap();
+
+        let mut vm = VM::new();
+        vm.trust(SigningKey::from_bytes(&[7; 32]).verifying_key());
+        for _ in 0..2 {
+            assert!(matches!(vm.execute(&code), Err(SealError::Untrusted)));
+            assert!(matches!(vm.resume(&code, 1), Err(SealError::Untrusted)));
+            assert!(matches!(
+                vm.execute_owned(code.to_vec()),
+                Err(SealError::Untrusted)
+            ));
+            assert!(matches!(
+                vm.execute_module(&module),
+                Err(SealError::Untrusted)
+            ));
+
+            // A restored VM keeps its trusted keys
+            vm.restore(&snapshot).unwrap();
+        }
+        assert!(matches!(vm.pop(), Value::Uint8(1)));
+    }
+
+    #[test]
+    fn test_execution_transcript() -> Result<(), Box<dyn std::error::Error>> {
         // Basic configuration to use homomorphic integers
         let config = ConfigBuilder::default().build();
 
@@ -109,22 +1436,75 @@ mod tests {
         // On the server side:
         set_server_key(server_keys);
 
-        let a = 2u8;
-        let b = 3u8;
+        let input = client::encrypt(6, ValueType::Euint8, &client_key);
+        let program = |input: &Value, factor: u8| {
+            vec![
+                OpCode::Push(input.clone()),
+                OpCode::Store(0),
+                OpCode::Load(0),
+                OpCode::Dup,
+                OpCode::Push(Value::Uint8(factor)),
+                OpCode::Mul,
+                OpCode::Add,
+            ]
+        };
+        let code = program(&input, 3);
 
-        let enc_a = FheUint8::try_encrypt(a, &client_key)?;
-        let enc_b = FheUint8::try_encrypt(b, &client_key)?;
+        let mut vm = VM::new();
+        vm.execute(&code)?;
+        assert_eq!(vm.transcript(), None);
+
+        vm = VM::new();
+        vm.enable_transcript();
+        vm.execute(&code)?;
+        let digest = vm.transcript().unwrap();
+        assert_eq!(client::decrypt(&vm.pop(), &client_key), Plain::Uint8(24));
+
+        // Re-running the same program on the same inputs reproduces the digest,
+        // however the run is split up
+        let mut replay = VM::new();
+        replay.enable_transcript();
+        replay.execute_owned(code.clone())?;
+        assert_eq!(replay.transcript(), Some(digest));
+        let mut replay = VM::new();
+        replay.enable_transcript();
+        while replay.resume(&code, 2)? == Status::Paused {}
+        assert_eq!(replay.transcript(), Some(digest));
+
+        // A different program or a different input doesn't
+        let mut other = VM::new();
+        other.enable_transcript();
+        other.execute(&program(&input, 4))?;
+        assert_ne!(other.transcript(), Some(digest));
+        let input = client::encrypt(6, ValueType::Euint8, &client_key);
+        other.enable_transcript();
+        other.execute(&program(&input, 3))?;
+        assert_ne!(other.transcript(), Some(digest));
+        Ok(())
+    }
+
+    #[test]
+    fn test_plaintext_arithmetic_wraps() {
         let mut vm = VM::new();
         let bytecode = [
-            OpCode::Push(Value::Euint8(enc_a)),
-            OpCode::Push(Value::Euint8(enc_b)),
-            OpCode::Mul,
+            OpCode::Push(Value::Uint8(200)),
+            OpCode::Push(Value::Uint8(100)),
+            OpCode::Add,
+            OpCode::Push(Value::Uint8(0)),
+            OpCode::Dec,
+            OpCode::Push(Value::Uint16(1)),
+            OpCode::Push(Value::Uint16(17)),
+            OpCode::ShiftLeft,
+            OpCode::Push(Value::Uint8(200)),
+            OpCode::Push(Value::Uint8(2)),
+            OpCode::MulChecked,
         ];
-        vm.execute(&bytecode);
-        let encrypted_res = vm.pop();
-        let clear_res: u8 = encrypted_res.as_eint8().decrypt(&client_key);
-        assert_eq!(clear_res, 6);
-        Ok(())
+        vm.execute(&bytecode).unwrap();
+        assert!(matches!(vm.pop(), Value::Bool(true)));
+        assert!(matches!(vm.pop(), Value::Uint8(144)));
+        assert!(matches!(vm.pop(), Value::Uint16(2)));
+        assert!(matches!(vm.pop(), Value::Uint8(255)));
+        assert!(matches!(vm.pop(), Value::Uint8(44)));
     }
 
     #[test]
@@ -148,7 +1528,7 @@ mod tests {
             OpCode::Push(Value::Euint8(enc_b)),
             OpCode::Div,
         ];
-        vm.execute(&bytecode);
+        vm.execute(&bytecode).unwrap();
         let encrypted_res = vm.pop();
         let clear_res: u8 = encrypted_res.as_eint8().decrypt(&client_key);
         assert_eq!(clear_res, 3);
@@ -176,7 +1556,7 @@ mod tests {
             OpCode::Push(Value::Euint8(enc_b)),
             OpCode::And,
         ];
-        vm.execute(&bytecode);
+        vm.execute(&bytecode).unwrap();
         let encrypted_res = vm.pop();
         let clear_res: u8 = encrypted_res.as_eint8().decrypt(&client_key);
         assert_eq!(clear_res, 1);
@@ -204,7 +1584,7 @@ mod tests {
             OpCode::Push(Value::Euint8(enc_b)),
             OpCode::Or,
         ];
-        vm.execute(&bytecode);
+        vm.execute(&bytecode).unwrap();
         let encrypted_res = vm.pop();
         let clear_res: u8 = encrypted_res.as_eint8().decrypt(&client_key);
         assert_eq!(clear_res, 7);
@@ -232,7 +1612,7 @@ mod tests {
             OpCode::Push(Value::Euint8(enc_b)),
             OpCode::Xor,
         ];
-        vm.execute(&bytecode);
+        vm.execute(&bytecode).unwrap();
         let encrypted_res = vm.pop();
         let clear_res: u8 = encrypted_res.as_eint8().decrypt(&client_key);
         assert_eq!(clear_res, 6);
@@ -260,7 +1640,7 @@ mod tests {
             OpCode::Push(Value::Euint8(enc_b)),
             OpCode::ShiftLeft,
         ];
-        vm.execute(&bytecode);
+        vm.execute(&bytecode).unwrap();
         let encrypted_res = vm.pop();
         let clear_res: u8 = encrypted_res.as_eint8().decrypt(&client_key);
         assert_eq!(clear_res, 2);
@@ -288,13 +1668,95 @@ mod tests {
             OpCode::Push(Value::Euint8(enc_b)),
             OpCode::ShiftRight,
         ];
-        vm.execute(&bytecode);
+        vm.execute(&bytecode).unwrap();
         let encrypted_res = vm.pop();
         let clear_res: u8 = encrypted_res.as_eint8().decrypt(&client_key);
         assert_eq!(clear_res, 1);
         Ok(())
     }
 
+    #[test]
+    fn test_rotate() -> Result<(), Box<dyn std::error::Error>> {
+        // Basic configuration to use homomorphic integers
+        let config = ConfigBuilder::default().build();
+
+        // Key generation
+        let (client_key, server_keys) = generate_keys(config);
+        // On the server side:
+        set_server_key(server_keys);
+
+        let enc_a = FheUint8::try_encrypt(0b1001_0110u8, &client_key)?;
+        let enc_b = FheUint16::try_encrypt(3u16, &client_key)?;
+        let mut vm = VM::new();
+        let bytecode = [
+            OpCode::Push(Value::Euint8(enc_a.clone())),
+            OpCode::Push(Value::Euint16(enc_b)),
+            OpCode::RotateLeft,
+            OpCode::Push(Value::Euint8(enc_a)),
+            OpCode::Push(Value::Uint32(11)),
+            OpCode::RotateRight,
+        ];
+        vm.execute(&bytecode).unwrap();
+        let clear_res: u8 = vm.pop().as_eint8().decrypt(&client_key);
+        assert_eq!(clear_res, 0b1001_0110u8.rotate_right(11));
+        let clear_res: u8 = vm.pop().as_eint8().decrypt(&client_key);
+        assert_eq!(clear_res, 0b1001_0110u8.rotate_left(3));
+        Ok(())
+    }
+
+    #[test]
+    fn test_bit_counts() -> Result<(), Box<dyn std::error::Error>> {
+        // Basic configuration to use homomorphic integers
+        let config = ConfigBuilder::default().build();
+
+        // Key generation
+        let (client_key, server_keys) = generate_keys(config);
+        // On the server side:
+        set_server_key(server_keys);
+
+        let a = 0b0010_1100u16;
+        let enc_a = FheUint16::try_encrypt(a, &client_key)?;
+        let mut vm = VM::new();
+        let bytecode = [
+            OpCode::Push(Value::Euint16(enc_a.clone())),
+            OpCode::CountOnes,
+            OpCode::Push(Value::Euint16(enc_a.clone())),
+            OpCode::LeadingZeros,
+            OpCode::Push(Value::Euint16(enc_a.clone())),
+            OpCode::TrailingZeros,
+            OpCode::Push(Value::Euint16(enc_a)),
+            OpCode::ILog2,
+        ];
+        vm.execute(&bytecode).unwrap();
+        let ilog2: u32 = vm.pop().as_eint32().decrypt(&client_key);
+        let trailing_zeros: u32 = vm.pop().as_eint32().decrypt(&client_key);
+        let leading_zeros: u32 = vm.pop().as_eint32().decrypt(&client_key);
+        let count_ones: u32 = vm.pop().as_eint32().decrypt(&client_key);
+        assert_eq!(count_ones, a.count_ones());
+        assert_eq!(leading_zeros, a.leading_zeros());
+        assert_eq!(trailing_zeros, a.trailing_zeros());
+        assert_eq!(ilog2, a.ilog2());
+        Ok(())
+    }
+
+    #[test]
+    fn test_bit_ops_plaintext() {
+        let mut vm = VM::new();
+        let bytecode = [
+            OpCode::Push(Value::Uint8(0b1000_0001)),
+            OpCode::Push(Value::Uint64(1)),
+            OpCode::RotateLeft,
+            OpCode::Push(Value::Uint128(1 << 100)),
+            OpCode::LeadingZeros,
+            OpCode::Push(Value::Uint32(0)),
+            OpCode::ILog2,
+        ];
+        vm.execute(&bytecode).unwrap();
+        assert!(matches!(vm.pop(), Value::Uint32(0)));
+        assert!(matches!(vm.pop(), Value::Uint32(27)));
+        assert!(matches!(vm.pop(), Value::Uint8(0b0000_0011)));
+    }
+
     #[test]
     fn test_eq() -> Result<(), Box<dyn std::error::Error>> {
         // Basic configuration to use homomorphic integers
@@ -316,7 +1778,7 @@ mod tests {
             OpCode::Push(Value::Euint8(enc_b)),
             OpCode::Eq,
         ];
-        vm.execute(&bytecode);
+        vm.execute(&bytecode).unwrap();
         let encrypted_res = vm.pop();
         let clear_res = encrypted_res.as_ebool().decrypt(&client_key);
         assert!(clear_res);
@@ -344,7 +1806,7 @@ mod tests {
             OpCode::Push(Value::Euint8(enc_b)),
             OpCode::Neq,
         ];
-        vm.execute(&bytecode);
+        vm.execute(&bytecode).unwrap();
         let encrypted_res = vm.pop();
         let clear_res = encrypted_res.as_ebool().decrypt(&client_key);
         assert!(clear_res);
@@ -371,7 +1833,7 @@ mod tests {
             OpCode::Push(Value::Euint8(enc_b)),
             OpCode::Lt,
         ];
-        vm.execute(&bytecode);
+        vm.execute(&bytecode).unwrap();
         let encrypted_res = vm.pop();
         let clear_res = encrypted_res.as_ebool().decrypt(&client_key);
         assert!(clear_res);
@@ -399,7 +1861,7 @@ mod tests {
             OpCode::Push(Value::Euint8(enc_b)),
             OpCode::Gt,
         ];
-        vm.execute(&bytecode);
+        vm.execute(&bytecode).unwrap();
         let encrypted_res = vm.pop();
         let clear_res = encrypted_res.as_ebool().decrypt(&client_key);
         assert!(clear_res);
@@ -427,7 +1889,7 @@ mod tests {
             OpCode::Push(Value::Euint8(enc_b)),
             OpCode::Gte,
         ];
-        vm.execute(&bytecode);
+        vm.execute(&bytecode).unwrap();
         let encrypted_res = vm.pop();
         let clear_res = encrypted_res.as_ebool().decrypt(&client_key);
         assert!(clear_res);
@@ -455,7 +1917,7 @@ mod tests {
             OpCode::Push(Value::Euint8(enc_b)),
             OpCode::Lte,
         ];
-        vm.execute(&bytecode);
+        vm.execute(&bytecode).unwrap();
         let encrypted_res = vm.pop();
         let clear_res = encrypted_res.as_ebool().decrypt(&client_key);
         assert!(clear_res);
@@ -483,7 +1945,7 @@ mod tests {
             OpCode::Push(Value::Euint8(enc_b)),
             OpCode::Min,
         ];
-        vm.execute(&bytecode);
+        vm.execute(&bytecode).unwrap();
         let encrypted_res = vm.pop();
         let clear_res: u8 = encrypted_res.as_eint8().decrypt(&client_key);
         assert_eq!(clear_res, 4);
@@ -511,7 +1973,7 @@ mod tests {
             OpCode::Push(Value::Euint8(enc_b)),
             OpCode::Max,
         ];
-        vm.execute(&bytecode);
+        vm.execute(&bytecode).unwrap();
         let encrypted_res = vm.pop();
         let clear_res: u8 = encrypted_res.as_eint8().decrypt(&client_key);
         assert_eq!(clear_res, 5);
@@ -533,7 +1995,7 @@ mod tests {
         let enc_a = FheUint8::try_encrypt(a, &client_key)?;
         let mut vm = VM::new();
         let bytecode = [OpCode::Push(Value::Euint8(enc_a)), OpCode::Inc];
-        vm.execute(&bytecode);
+        vm.execute(&bytecode).unwrap();
         let encrypted_res = vm.pop();
         let clear_res: u8 = encrypted_res.as_eint8().decrypt(&client_key);
         assert_eq!(clear_res, 5);
@@ -555,7 +2017,7 @@ mod tests {
         let enc_a = FheUint8::try_encrypt(a, &client_key)?;
         let mut vm = VM::new();
         let bytecode = [OpCode::Push(Value::Euint8(enc_a)), OpCode::Dec];
-        vm.execute(&bytecode);
+        vm.execute(&bytecode).unwrap();
         let encrypted_res = vm.pop();
         let clear_res: u8 = encrypted_res.as_eint8().decrypt(&client_key);
         assert_eq!(clear_res, 3);
@@ -581,7 +2043,66 @@ mod tests {
             OpCode::Store(0),
             OpCode::Load(0),
         ];
-        vm.execute(&bytecode);
+        vm.execute(&bytecode).unwrap();
+        let encrypted_res = vm.pop();
+        let clear_res: u8 = encrypted_res.as_eint8().decrypt(&client_key);
+        assert_eq!(clear_res, 5);
+        Ok(())
+    }
+
+    #[test]
+    fn test_store() -> Result<(), Box<dyn std::error::Error>> {
+        // Basic configuration to use homomorphic integers
+        let config = ConfigBuilder::default().build();
+
+        // Key generation
+        let (client_key, server_keys) = generate_keys(config);
+        // On the server side:
+        set_server_key(server_keys);
+
+        let a = 5u8;
+        let b = 6u8;
+
+        let enc_a = FheUint8::try_encrypt(a, &client_key)?;
+        let enc_b = FheUint8::try_encrypt(b, &client_key)?;
+        let mut vm = VM::new();
+        let bytecode = [
+            OpCode::Push(Value::Euint8(enc_a)),
+            OpCode::Store(0),
+            OpCode::Push(Value::Euint8(enc_b)),
+            OpCode::Store(1),
+            OpCode::Load(0),
+            OpCode::Load(1),
+        ];
+        vm.execute(&bytecode).unwrap();
+        let encrypted_res = vm.pop();
+        let clear_res: u8 = encrypted_res.as_eint8().decrypt(&client_key);
+        assert_eq!(clear_res, 6);
+        Ok(())
+    }
+
+    #[test]
+    fn test_swap() -> Result<(), Box<dyn std::error::Error>> {
+        // Basic configuration to use homomorphic integers
+        let config = ConfigBuilder::default().build();
+
+        // Key generation
+        let (client_key, server_keys) = generate_keys(config);
+        // On the server side:
+        set_server_key(server_keys);
+
+        let a = 5u8;
+        let b = 6u8;
+
+        let enc_a = FheUint8::try_encrypt(a, &client_key)?;
+        let enc_b = FheUint8::try_encrypt(b, &client_key)?;
+        let mut vm = VM::new();
+        let bytecode = [
+            OpCode::Push(Value::Euint8(enc_a)),
+            OpCode::Push(Value::Euint8(enc_b)),
+            OpCode::Swap,
+        ];
+        vm.execute(&bytecode).unwrap();
         let encrypted_res = vm.pop();
         let clear_res: u8 = encrypted_res.as_eint8().decrypt(&client_key);
         assert_eq!(clear_res, 5);
@@ -589,7 +2110,29 @@ mod tests {
     }
 
     #[test]
-    fn test_store() -> Result<(), Box<dyn std::error::Error>> {
+    fn test_neg() -> Result<(), Box<dyn std::error::Error>> {
+        // Basic configuration to use homomorphic integers
+        let config = ConfigBuilder::default().build();
+
+        // Key generation
+        let (client_key, server_keys) = generate_keys(config);
+        // On the server side:
+        set_server_key(server_keys);
+
+        let a = 5u8;
+
+        let enc_a = FheUint8::try_encrypt(a, &client_key)?;
+        let mut vm = VM::new();
+        let bytecode = [OpCode::Push(Value::Euint8(enc_a)), OpCode::Neg];
+        vm.execute(&bytecode).unwrap();
+        let encrypted_res = vm.pop();
+        let clear_res: u8 = encrypted_res.as_eint8().decrypt(&client_key);
+        assert_eq!(clear_res, -5i8 as u8);
+        Ok(())
+    }
+
+    #[test]
+    fn test_mux() -> Result<(), Box<dyn std::error::Error>> {
         // Basic configuration to use homomorphic integers
         let config = ConfigBuilder::default().build();
 
@@ -598,21 +2141,21 @@ mod tests {
         // On the server side:
         set_server_key(server_keys);
 
-        let a = 5u8;
+        let a = true;
         let b = 6u8;
+        let c = 1u8;
 
-        let enc_a = FheUint8::try_encrypt(a, &client_key)?;
+        let enc_a = FheBool::try_encrypt(a, &client_key)?;
         let enc_b = FheUint8::try_encrypt(b, &client_key)?;
+        let enc_c = FheUint8::try_encrypt(c, &client_key)?;
         let mut vm = VM::new();
         let bytecode = [
-            OpCode::Push(Value::Euint8(enc_a)),
-            OpCode::Store(0),
+            OpCode::Push(Value::Ebool(enc_a)),
             OpCode::Push(Value::Euint8(enc_b)),
-            OpCode::Store(1),
-            OpCode::Load(0),
-            OpCode::Load(1),
+            OpCode::Push(Value::Euint8(enc_c)),
+            OpCode::Mux,
         ];
-        vm.execute(&bytecode);
+        vm.execute(&bytecode).unwrap();
         let encrypted_res = vm.pop();
         let clear_res: u8 = encrypted_res.as_eint8().decrypt(&client_key);
         assert_eq!(clear_res, 6);
@@ -620,7 +2163,7 @@ mod tests {
     }
 
     #[test]
-    fn test_swap() -> Result<(), Box<dyn std::error::Error>> {
+    fn test_mux_wide_with_plaintext() -> Result<(), Box<dyn std::error::Error>> {
         // Basic configuration to use homomorphic integers
         let config = ConfigBuilder::default().build();
 
@@ -629,26 +2172,62 @@ mod tests {
         // On the server side:
         set_server_key(server_keys);
 
-        let a = 5u8;
-        let b = 6u8;
+        let enc_a = FheBool::try_encrypt(false, &client_key)?;
+        let enc_b = FheUint64::try_encrypt(600u64, &client_key)?;
+        let mut vm = VM::new();
+        let bytecode = [
+            OpCode::Push(Value::Ebool(enc_a)),
+            OpCode::Push(Value::Euint64(enc_b)),
+            OpCode::Push(Value::Uint64(1 << 40)),
+            OpCode::Mux,
+            // Branches of different widths are promoted to the wider one
+            OpCode::Push(Value::Bool(true)),
+            OpCode::Push(Value::Uint8(7)),
+            OpCode::Push(Value::Euint64(FheUint64::try_encrypt(600u64, &client_key)?)),
+            OpCode::Mux,
+        ];
+        vm.execute(&bytecode).unwrap();
+        let clear_res: u64 = vm.pop().as_eint64().decrypt(&client_key);
+        assert_eq!(clear_res, 7);
+        let clear_res: u64 = vm.pop().as_eint64().decrypt(&client_key);
+        assert_eq!(clear_res, 1 << 40);
+        Ok(())
+    }
 
-        let enc_a = FheUint8::try_encrypt(a, &client_key)?;
-        let enc_b = FheUint8::try_encrypt(b, &client_key)?;
+    #[test]
+    fn test_and_comparison_results() -> Result<(), Box<dyn std::error::Error>> {
+        // Basic configuration to use homomorphic integers
+        let config = ConfigBuilder::default().build();
+
+        // Key generation
+        let (client_key, server_keys) = generate_keys(config);
+        // On the server side:
+        set_server_key(server_keys);
+
+        let enc_a = FheUint8::try_encrypt(2u8, &client_key)?;
+        let enc_b = FheUint8::try_encrypt(3u8, &client_key)?;
+        let enc_c = FheUint8::try_encrypt(4u8, &client_key)?;
+        let enc_d = FheUint8::try_encrypt(5u8, &client_key)?;
         let mut vm = VM::new();
+        // a < b && c != d
         let bytecode = [
             OpCode::Push(Value::Euint8(enc_a)),
             OpCode::Push(Value::Euint8(enc_b)),
-            OpCode::Swap,
+            OpCode::Lt,
+            OpCode::Push(Value::Euint8(enc_c)),
+            OpCode::Push(Value::Euint8(enc_d)),
+            OpCode::Neq,
+            OpCode::And,
         ];
-        vm.execute(&bytecode);
+        vm.execute(&bytecode).unwrap();
         let encrypted_res = vm.pop();
-        let clear_res: u8 = encrypted_res.as_eint8().decrypt(&client_key);
-        assert_eq!(clear_res, 5);
+        let clear_res: bool = encrypted_res.as_ebool().decrypt(&client_key);
+        assert!(clear_res);
         Ok(())
     }
 
     #[test]
-    fn test_neg() -> Result<(), Box<dyn std::error::Error>> {
+    fn test_bool_logic_ciphertext_with_plaintext() -> Result<(), Box<dyn std::error::Error>> {
         // Basic configuration to use homomorphic integers
         let config = ConfigBuilder::default().build();
 
@@ -657,20 +2236,28 @@ mod tests {
         // On the server side:
         set_server_key(server_keys);
 
-        let a = 5u8;
-
-        let enc_a = FheUint8::try_encrypt(a, &client_key)?;
+        let enc_a = FheBool::try_encrypt(false, &client_key)?;
+        let enc_b = FheBool::try_encrypt(true, &client_key)?;
         let mut vm = VM::new();
-        let bytecode = [OpCode::Push(Value::Euint8(enc_a)), OpCode::Neg];
-        vm.execute(&bytecode);
+        let bytecode = [
+            OpCode::Push(Value::Ebool(enc_a)),
+            OpCode::Push(Value::Bool(true)),
+            OpCode::Or,
+            OpCode::Push(Value::Ebool(enc_b)),
+            OpCode::Xor,
+            OpCode::Push(Value::Bool(true)),
+            OpCode::Swap,
+            OpCode::And,
+        ];
+        vm.execute(&bytecode).unwrap();
         let encrypted_res = vm.pop();
-        let clear_res: u8 = encrypted_res.as_eint8().decrypt(&client_key);
-        assert_eq!(clear_res, -5i8 as u8);
+        let clear_res: bool = encrypted_res.as_ebool().decrypt(&client_key);
+        assert!(!clear_res);
         Ok(())
     }
 
     #[test]
-    fn test_mux() -> Result<(), Box<dyn std::error::Error>> {
+    fn test_not() -> Result<(), Box<dyn std::error::Error>> {
         // Basic configuration to use homomorphic integers
         let config = ConfigBuilder::default().build();
 
@@ -679,24 +2266,22 @@ mod tests {
         // On the server side:
         set_server_key(server_keys);
 
-        let a = true;
-        let b = 6u8;
-        let c = 1u8;
-
-        let enc_a = FheBool::try_encrypt(a, &client_key)?;
-        let enc_b = FheUint8::try_encrypt(b, &client_key)?;
-        let enc_c = FheUint8::try_encrypt(c, &client_key)?;
+        let enc_a = FheBool::try_encrypt(true, &client_key)?;
+        let enc_b = FheUint8::try_encrypt(5u8, &client_key)?;
         let mut vm = VM::new();
         let bytecode = [
             OpCode::Push(Value::Ebool(enc_a)),
+            OpCode::Not,
             OpCode::Push(Value::Euint8(enc_b)),
-            OpCode::Push(Value::Euint8(enc_c)),
-            OpCode::Mux,
+            OpCode::Not,
         ];
-        vm.execute(&bytecode);
+        vm.execute(&bytecode).unwrap();
         let encrypted_res = vm.pop();
         let clear_res: u8 = encrypted_res.as_eint8().decrypt(&client_key);
-        assert_eq!(clear_res, 6);
+        assert_eq!(clear_res, !5u8);
+        let encrypted_res = vm.pop();
+        let clear_res: bool = encrypted_res.as_ebool().decrypt(&client_key);
+        assert!(!clear_res);
         Ok(())
     }
 
@@ -718,7 +2303,7 @@ mod tests {
             OpCode::Push(Value::Uint8(b)),
             OpCode::Add,
         ];
-        vm.execute(&bytecode);
+        vm.execute(&bytecode).unwrap();
         let encrypted_res = vm.pop();
         let clear_res: u8 = encrypted_res.as_eint8().decrypt(&client_key);
         assert_eq!(clear_res, 11);
@@ -743,7 +2328,7 @@ mod tests {
             OpCode::Push(Value::Uint8(b)),
             OpCode::Sub,
         ];
-        vm.execute(&bytecode);
+        vm.execute(&bytecode).unwrap();
         let encrypted_res = vm.pop();
         let clear_res: u8 = encrypted_res.as_eint8().decrypt(&client_key);
         assert_eq!(clear_res, 2);
@@ -768,7 +2353,7 @@ mod tests {
             OpCode::Push(Value::Uint8(b)),
             OpCode::Mul,
         ];
-        vm.execute(&bytecode);
+        vm.execute(&bytecode).unwrap();
         let encrypted_res = vm.pop();
         let clear_res: u8 = encrypted_res.as_eint8().decrypt(&client_key);
         assert_eq!(clear_res, 24);
@@ -793,7 +2378,7 @@ mod tests {
             OpCode::Push(Value::Uint8(b)),
             OpCode::Div,
         ];
-        vm.execute(&bytecode);
+        vm.execute(&bytecode).unwrap();
         let encrypted_res = vm.pop();
         let clear_res: u8 = encrypted_res.as_eint8().decrypt(&client_key);
         assert_eq!(clear_res, 2);
@@ -818,7 +2403,7 @@ mod tests {
             OpCode::Push(Value::Uint8(b)),
             OpCode::And,
         ];
-        vm.execute(&bytecode);
+        vm.execute(&bytecode).unwrap();
         let encrypted_res = vm.pop();
         let clear_res: u8 = encrypted_res.as_eint8().decrypt(&client_key);
         assert_eq!(clear_res, 1);
@@ -843,7 +2428,7 @@ mod tests {
             OpCode::Push(Value::Euint8(enc_b)),
             OpCode::And,
         ];
-        vm.execute(&bytecode);
+        vm.execute(&bytecode).unwrap();
         let encrypted_res = vm.pop();
         let clear_res: u8 = encrypted_res.as_eint8().decrypt(&client_key);
         assert_eq!(clear_res, 1);
@@ -868,7 +2453,7 @@ mod tests {
             OpCode::Push(Value::Uint8(b)),
             OpCode::Or,
         ];
-        vm.execute(&bytecode);
+        vm.execute(&bytecode).unwrap();
         let encrypted_res = vm.pop();
         let clear_res: u8 = encrypted_res.as_eint8().decrypt(&client_key);
         assert_eq!(clear_res, 7);
@@ -893,7 +2478,7 @@ mod tests {
             OpCode::Push(Value::Euint8(enc_b)),
             OpCode::Or,
         ];
-        vm.execute(&bytecode);
+        vm.execute(&bytecode).unwrap();
         let encrypted_res = vm.pop();
         let clear_res: u8 = encrypted_res.as_eint8().decrypt(&client_key);
         assert_eq!(clear_res, 7);
@@ -918,7 +2503,7 @@ mod tests {
             OpCode::Push(Value::Uint8(b)),
             OpCode::Xor,
         ];
-        vm.execute(&bytecode);
+        vm.execute(&bytecode).unwrap();
         let encrypted_res = vm.pop();
         let clear_res: u8 = encrypted_res.as_eint8().decrypt(&client_key);
         assert_eq!(clear_res, 6);
@@ -943,7 +2528,7 @@ mod tests {
             OpCode::Push(Value::Euint8(enc_b)),
             OpCode::Xor,
         ];
-        vm.execute(&bytecode);
+        vm.execute(&bytecode).unwrap();
         let encrypted_res = vm.pop();
         let clear_res: u8 = encrypted_res.as_eint8().decrypt(&client_key);
         assert_eq!(clear_res, 6);
@@ -968,7 +2553,7 @@ mod tests {
             OpCode::Push(Value::Euint8(enc_b)),
             OpCode::Add,
         ];
-        vm.execute(&bytecode);
+        vm.execute(&bytecode).unwrap();
         let encrypted_res = vm.pop();
         let clear_res: u8 = encrypted_res.as_eint8().decrypt(&client_key);
         assert_eq!(clear_res, 11);
@@ -993,7 +2578,7 @@ mod tests {
             OpCode::Push(Value::Euint8(enc_b)),
             OpCode::Sub,
         ];
-        vm.execute(&bytecode);
+        vm.execute(&bytecode).unwrap();
         let encrypted_res = vm.pop();
         let clear_res: u8 = encrypted_res.as_eint8().decrypt(&client_key);
         assert_eq!(clear_res, 2);
@@ -1018,7 +2603,7 @@ mod tests {
             OpCode::Push(Value::Euint8(enc_b)),
             OpCode::Mul,
         ];
-        vm.execute(&bytecode);
+        vm.execute(&bytecode).unwrap();
         let encrypted_res = vm.pop();
         let clear_res: u8 = encrypted_res.as_eint8().decrypt(&client_key);
         assert_eq!(clear_res, 24);
@@ -1026,8 +2611,7 @@ mod tests {
     }
 
     #[test]
-    #[should_panic]
-    fn test_divide_plaintext_by_ciphertext() {
+    fn test_divide_plaintext_by_ciphertext() -> Result<(), Box<dyn std::error::Error>> {
         // Basic configuration to use homomorphic integers
         let config = ConfigBuilder::default().build();
 
@@ -1037,22 +2621,112 @@ mod tests {
         set_server_key(server_keys);
         let a = 6u8;
         let b = 3u8;
-        let enc_b = FheUint8::try_encrypt(b, &client_key).unwrap();
+        let enc_b = FheUint8::try_encrypt(b, &client_key)?;
         let mut vm = VM::new();
         let bytecode = [
             OpCode::Push(Value::Uint8(a)),
             OpCode::Push(Value::Euint8(enc_b)),
             OpCode::Div,
         ];
-        vm.execute(&bytecode);
+        vm.execute(&bytecode).unwrap();
         let encrypted_res = vm.pop();
         let clear_res: u8 = encrypted_res.as_eint8().decrypt(&client_key);
         assert_eq!(clear_res, 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_divide_wide_plaintext_by_ciphertext() -> Result<(), Box<dyn std::error::Error>> {
+        // Basic configuration to use homomorphic integers
+        let config = ConfigBuilder::default().build();
+
+        // Key generation
+        let (client_key, server_keys) = generate_keys(config);
+        // On the server side:
+        set_server_key(server_keys);
+        let enc_b = FheUint8::try_encrypt(7u8, &client_key)?;
+        let mut vm = VM::new();
+        let bytecode = [
+            OpCode::Push(Value::Uint16(1000)),
+            OpCode::Push(Value::Euint8(enc_b)),
+            OpCode::Div,
+        ];
+        vm.execute(&bytecode).unwrap();
+        let encrypted_res = vm.pop();
+        let clear_res: u16 = encrypted_res.as_eint16().decrypt(&client_key);
+        assert_eq!(clear_res, 142);
+        Ok(())
+    }
+
+    #[test]
+    fn test_div_checked_by_encrypted_zero() -> Result<(), Box<dyn std::error::Error>> {
+        // Basic configuration to use homomorphic integers
+        let config = ConfigBuilder::default().build();
+
+        // Key generation
+        let (client_key, server_keys) = generate_keys(config);
+        // On the server side:
+        set_server_key(server_keys);
+        let enc_a = FheUint8::try_encrypt(100u8, &client_key)?;
+        let enc_b = FheUint8::try_encrypt(0u8, &client_key)?;
+        let enc_c = FheUint8::try_encrypt(4u8, &client_key)?;
+        let mut vm = VM::new();
+        let bytecode = [
+            OpCode::Push(Value::Uint8(100)),
+            OpCode::Push(Value::Euint8(enc_c)),
+            OpCode::DivChecked,
+            OpCode::Push(Value::Euint8(enc_a)),
+            OpCode::Push(Value::Euint8(enc_b)),
+            OpCode::DivChecked,
+        ];
+        vm.execute(&bytecode).unwrap();
+        let divide_by_zero: bool = vm.pop().as_ebool().decrypt(&client_key);
+        assert!(divide_by_zero);
+        let quotient: u8 = vm.pop().as_eint8().decrypt(&client_key);
+        assert_eq!(quotient, u8::MAX);
+        let divide_by_zero: bool = vm.pop().as_ebool().decrypt(&client_key);
+        assert!(!divide_by_zero);
+        let quotient: u8 = vm.pop().as_eint8().decrypt(&client_key);
+        assert_eq!(quotient, 25);
+        Ok(())
+    }
+
+    #[test]
+    fn test_div_checked_by_plaintext_zero() -> Result<(), Box<dyn std::error::Error>> {
+        // Basic configuration to use homomorphic integers
+        let config = ConfigBuilder::default().build();
+
+        // Key generation
+        let (client_key, server_keys) = generate_keys(config);
+        // On the server side:
+        set_server_key(server_keys);
+        let enc_a = FheUint16::try_encrypt(100u16, &client_key)?;
+        let mut vm = VM::new();
+        let bytecode = [
+            OpCode::Push(Value::Euint16(enc_a)),
+            OpCode::Push(Value::Uint8(0)),
+            OpCode::DivChecked,
+        ];
+        vm.execute(&bytecode).unwrap();
+        // Same width as dividing by any other Uint8
+        assert!(matches!(vm.pop(), Value::Bool(true)));
+        let quotient: u8 = vm.pop().as_eint8().decrypt(&client_key);
+        assert_eq!(quotient, u8::MAX);
+
+        // Two plaintexts give a plaintext quotient
+        let bytecode = [
+            OpCode::Push(Value::Uint16(100)),
+            OpCode::Push(Value::Uint8(0)),
+            OpCode::DivChecked,
+        ];
+        vm.execute(&bytecode).unwrap();
+        assert!(matches!(vm.pop(), Value::Bool(true)));
+        assert!(matches!(vm.pop(), Value::Uint16(u16::MAX)));
+        Ok(())
     }
 
     #[test]
-    #[should_panic]
-    fn test_min_ciphertext_with_plaintext() {
+    fn test_min_ciphertext_with_plaintext() -> Result<(), Box<dyn std::error::Error>> {
         // Basic configuration to use homomorphic integers
         let config = ConfigBuilder::default().build();
 
@@ -1062,17 +2736,110 @@ mod tests {
         set_server_key(server_keys);
         let a = 4u8;
         let b = 5u8;
-        let enc_b = FheUint8::try_encrypt(b, &client_key).unwrap();
+        let enc_b = FheUint8::try_encrypt(b, &client_key)?;
         let mut vm = VM::new();
         let bytecode = [
             OpCode::Push(Value::Uint8(a)),
             OpCode::Push(Value::Euint8(enc_b)),
             OpCode::Min,
         ];
-        vm.execute(&bytecode);
+        vm.execute(&bytecode).unwrap();
         let encrypted_res = vm.pop();
         let clear_res: u8 = encrypted_res.as_eint8().decrypt(&client_key);
         assert_eq!(clear_res, 4);
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_mixed_widths_with_plaintext() -> Result<(), Box<dyn std::error::Error>> {
+        // Basic configuration to use homomorphic integers
+        let config = ConfigBuilder::default().build();
+
+        // Key generation
+        let (client_key, server_keys) = generate_keys(config);
+        // On the server side:
+        set_server_key(server_keys);
+        let enc_a = FheUint8::try_encrypt(200u8, &client_key)?;
+        let mut vm = VM::new();
+        let bytecode = [
+            OpCode::Push(Value::Euint8(enc_a)),
+            OpCode::Push(Value::Uint16(300)),
+            OpCode::Max,
+        ];
+        vm.execute(&bytecode).unwrap();
+        let encrypted_res = vm.pop();
+        let clear_res: u16 = encrypted_res.as_eint16().decrypt(&client_key);
+        assert_eq!(clear_res, 300);
+        Ok(())
+    }
+
+    #[test]
+    fn test_lt_ciphertext_with_plaintext() -> Result<(), Box<dyn std::error::Error>> {
+        // Basic configuration to use homomorphic integers
+        let config = ConfigBuilder::default().build();
+
+        // Key generation
+        let (client_key, server_keys) = generate_keys(config);
+        // On the server side:
+        set_server_key(server_keys);
+        let enc_a = FheUint16::try_encrypt(1000u16, &client_key)?;
+        let enc_b = FheUint8::try_encrypt(7u8, &client_key)?;
+        let mut vm = VM::new();
+        let bytecode = [
+            // threshold on the left, ciphertext on the right
+            OpCode::Push(Value::Uint8(200)),
+            OpCode::Push(Value::Euint16(enc_a)),
+            OpCode::Lt,
+            // wider plaintext than ciphertext
+            OpCode::Push(Value::Euint8(enc_b)),
+            OpCode::Push(Value::Uint32(70000)),
+            OpCode::Gte,
+        ];
+        vm.execute(&bytecode).unwrap();
+        let clear_res: bool = vm.pop().as_ebool().decrypt(&client_key);
+        assert!(!clear_res);
+        let clear_res: bool = vm.pop().as_ebool().decrypt(&client_key);
+        assert!(clear_res);
+        Ok(())
+    }
+
+    #[test]
+    fn test_eq_mixed_widths() -> Result<(), Box<dyn std::error::Error>> {
+        // Basic configuration to use homomorphic integers
+        let config = ConfigBuilder::default().build();
+
+        // Key generation
+        let (client_key, server_keys) = generate_keys(config);
+        // On the server side:
+        set_server_key(server_keys);
+        let enc_a = FheUint16::try_encrypt(42u16, &client_key)?;
+        let enc_b = FheUint8::try_encrypt(42u8, &client_key)?;
+        let mut vm = VM::new();
+        let bytecode = [
+            OpCode::Push(Value::Euint16(enc_a)),
+            OpCode::Push(Value::Euint8(enc_b)),
+            OpCode::Eq,
+        ];
+        vm.execute(&bytecode).unwrap();
+        let clear_res: bool = vm.pop().as_ebool().decrypt(&client_key);
+        assert!(clear_res);
+        Ok(())
+    }
+
+    #[test]
+    fn test_compare_plaintexts() {
+        let mut vm = VM::new();
+        let bytecode = [
+            OpCode::Push(Value::Uint8(3)),
+            OpCode::Push(Value::Uint64(3)),
+            OpCode::Lte,
+            OpCode::Push(Value::Uint16(9)),
+            OpCode::Push(Value::Uint8(4)),
+            OpCode::Min,
+        ];
+        vm.execute(&bytecode).unwrap();
+        assert!(matches!(vm.pop(), Value::Uint16(4)));
+        assert!(matches!(vm.pop(), Value::Bool(true)));
     }
 
     #[test]
@@ -1092,7 +2859,7 @@ mod tests {
             OpCode::Push(Value::Uint8(b)),
             OpCode::Rem,
         ];
-        vm.execute(&bytecode);
+        vm.execute(&bytecode).unwrap();
         let encrypted_res = vm.pop();
         let clear_res: u8 = encrypted_res.as_eint8().decrypt(&client_key);
         assert_eq!(clear_res, 2);
@@ -1116,13 +2883,66 @@ mod tests {
             OpCode::Push(Value::Euint8(enc_b)),
             OpCode::Rem,
         ];
-        vm.execute(&bytecode);
+        vm.execute(&bytecode).unwrap();
         let encrypted_res = vm.pop();
         let clear_res: u8 = encrypted_res.as_eint8().decrypt(&client_key);
         assert_eq!(clear_res, 2);
         Ok(())
     }
 
+    #[test]
+    fn test_div_rem() -> Result<(), Box<dyn std::error::Error>> {
+        // Basic configuration to use homomorphic integers
+        let config = ConfigBuilder::default().build();
+        // Key generation
+        let (client_key, server_keys) = generate_keys(config);
+        // On the server side:
+        set_server_key(server_keys);
+        let enc_a = FheUint16::try_encrypt(1234u16, &client_key)?;
+        let enc_b = FheUint8::try_encrypt(10u8, &client_key)?;
+        let mut vm = VM::new();
+        let bytecode = [
+            OpCode::Push(Value::Euint16(enc_a)),
+            OpCode::Push(Value::Euint8(enc_b)),
+            OpCode::DivRem,
+        ];
+        vm.execute(&bytecode).unwrap();
+        let remainder: u16 = vm.pop().as_eint16().decrypt(&client_key);
+        let quotient: u16 = vm.pop().as_eint16().decrypt(&client_key);
+        assert_eq!(quotient, 123);
+        assert_eq!(remainder, 4);
+        Ok(())
+    }
+
+    #[test]
+    fn test_div_rem_with_plaintext() -> Result<(), Box<dyn std::error::Error>> {
+        // Basic configuration to use homomorphic integers
+        let config = ConfigBuilder::default().build();
+        // Key generation
+        let (client_key, server_keys) = generate_keys(config);
+        // On the server side:
+        set_server_key(server_keys);
+        let enc_a = FheUint8::try_encrypt(47u8, &client_key)?;
+        let enc_b = FheUint8::try_encrypt(6u8, &client_key)?;
+        let mut vm = VM::new();
+        let bytecode = [
+            OpCode::Push(Value::Euint8(enc_a)),
+            OpCode::Push(Value::Uint8(5)),
+            OpCode::DivRem,
+            OpCode::Push(Value::Uint8(100)),
+            OpCode::Push(Value::Euint8(enc_b)),
+            OpCode::DivRem,
+        ];
+        vm.execute(&bytecode).unwrap();
+        let remainder: u8 = vm.pop().as_eint8().decrypt(&client_key);
+        let quotient: u8 = vm.pop().as_eint8().decrypt(&client_key);
+        assert_eq!((quotient, remainder), (16, 4));
+        let remainder: u8 = vm.pop().as_eint8().decrypt(&client_key);
+        let quotient: u8 = vm.pop().as_eint8().decrypt(&client_key);
+        assert_eq!((quotient, remainder), (9, 2));
+        Ok(())
+    }
+
     #[test]
     fn test_program_serialization_deserialization() -> Result<(), Box<dyn std::error::Error>> {
         // Basic configuration to use homomorphic integers
@@ -1184,7 +3004,8 @@ mod tests {
         //println!("Serialized program: {:?}", hex::encode(serialized.clone()));
 
         // Deserialize the bytes back into opcodes
-        let deserialized_program = deserialize(&serialized);
+        let deserialized_program = deserialize(&serialized)?;
+        assert_eq!(deserialized_program.len(), original_program.len());
 
         // loop through deserialized program and assert each opcode matches original
         for (i, opcode) in deserialized_program.iter().enumerate() {
@@ -1223,7 +3044,8 @@ mod tests {
         //println!("Serialized program: {:?}", hex::encode(serialized.clone()));
 
         // Deserialize the bytes back into opcodes
-        let deserialized_program = deserialize(&serialized);
+        let deserialized_program = deserialize(&serialized)?;
+        assert_eq!(deserialized_program.len(), original_program.len());
 
         // loop through deserialized program and assert each opcode matches original
         for (i, opcode) in deserialized_program.iter().enumerate() {
@@ -1276,7 +3098,7 @@ mod tests {
         ];
 
         let mut vm = VM::new();
-        vm.execute(&bytecode);
+        vm.execute(&bytecode).unwrap();
         let encrypted_res = vm.pop();
         let clear_res: u8 = encrypted_res.as_int8().decrypt(&client_key);
         assert_eq!(clear_res, 55);
@@ -1284,3 +3106,176 @@ mod tests {
     }
     */
 }
+
+#[cfg(test)]
+mod proptests {
+    use crate::value::Value;
+    use crate::vm::deserialize;
+    use crate::vm::serialize;
+    use crate::vm::OpCode;
+    use crate::vm::UnaryOp;
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+    use proptest::test_runner::TestRunner;
+    use tfhe::prelude::*;
+    use tfhe::{generate_keys, set_server_key, ConfigBuilder};
+    use tfhe::{FheBool, FheUint128, FheUint16, FheUint32, FheUint64, FheUint8};
+
+    fn plaintext_value() -> impl Strategy<Value = Value> {
+        prop_oneof![
+            any::<bool>().prop_map(Value::Bool),
+            any::<u8>().prop_map(Value::Uint8),
+            any::<u16>().prop_map(Value::Uint16),
+            any::<u32>().prop_map(Value::Uint32),
+            any::<u64>().prop_map(Value::Uint64),
+            any::<u128>().prop_map(Value::Uint128),
+        ]
+    }
+
+    // Trivial encryptions keep the strategy cheap; they serialize exactly like
+    // real ciphertexts. Requires the server key to be set on the current thread.
+    fn encrypted_value() -> impl Strategy<Value = Value> {
+        prop_oneof![
+            any::<bool>().prop_map(|v| Value::Ebool(FheBool::encrypt_trivial(v))),
+            any::<u8>().prop_map(|v| Value::Euint8(FheUint8::encrypt_trivial(v))),
+            any::<u16>().prop_map(|v| Value::Euint16(FheUint16::encrypt_trivial(v))),
+            any::<u32>().prop_map(|v| Value::Euint32(FheUint32::encrypt_trivial(v))),
+            any::<u64>().prop_map(|v| Value::Euint64(FheUint64::encrypt_trivial(v))),
+            any::<u128>().prop_map(|v| Value::Euint128(FheUint128::encrypt_trivial(v))),
+        ]
+    }
+
+    fn value() -> impl Strategy<Value = Value> {
+        prop_oneof![plaintext_value(), encrypted_value()]
+    }
+
+    fn opcode(value: impl Strategy<Value = Value> + 'static) -> impl Strategy<Value = OpCode> {
+        prop_oneof![
+            Just(OpCode::Add),
+            Just(OpCode::Sub),
+            Just(OpCode::Mul),
+            Just(OpCode::AddChecked),
+            Just(OpCode::SubChecked),
+            Just(OpCode::MulChecked),
+            Just(OpCode::AddSat),
+            Just(OpCode::SubSat),
+            Just(OpCode::MulSat),
+            plaintext_value().prop_map(OpCode::AddImm),
+            plaintext_value().prop_map(OpCode::SubImm),
+            plaintext_value().prop_map(OpCode::MulImm),
+            Just(OpCode::Div),
+            Just(OpCode::DivChecked),
+            Just(OpCode::Neg),
+            Just(OpCode::Rem),
+            Just(OpCode::DivRem),
+            Just(OpCode::And),
+            Just(OpCode::Or),
+            Just(OpCode::Xor),
+            Just(OpCode::Not),
+            Just(OpCode::ShiftRight),
+            Just(OpCode::ShiftLeft),
+            Just(OpCode::RotateLeft),
+            Just(OpCode::RotateRight),
+            Just(OpCode::CountOnes),
+            Just(OpCode::LeadingZeros),
+            Just(OpCode::TrailingZeros),
+            Just(OpCode::ILog2),
+            Just(OpCode::Eq),
+            Just(OpCode::Neq),
+            Just(OpCode::Lt),
+            Just(OpCode::Lte),
+            Just(OpCode::Gt),
+            Just(OpCode::Gte),
+            Just(OpCode::Min),
+            Just(OpCode::Max),
+            prop::sample::select(vec![8, 16, 32, 64, 128]).prop_map(OpCode::BoolToInt),
+            Just(OpCode::IntToBool),
+            Just(OpCode::Mux),
+            value.prop_map(OpCode::Push),
+            Just(OpCode::Dup),
+            Just(OpCode::NoOp),
+            Just(OpCode::Inc),
+            Just(OpCode::Dec),
+            any::<i32>().prop_map(OpCode::Load),
+            any::<i32>().prop_map(OpCode::Store),
+            Just(OpCode::Swap),
+            Just(OpCode::Drop),
+            Just(OpCode::Over),
+            Just(OpCode::Rot),
+            any::<u32>().prop_map(OpCode::Pick),
+            any::<u32>().prop_map(OpCode::Roll),
+            Just(OpCode::Depth),
+            (any::<i32>(), any::<i32>(), any::<i32>(), any::<u32>())
+                .prop_map(|(dst, lhs, rhs, len)| OpCode::VAdd(dst, lhs, rhs, len)),
+            (any::<i32>(), any::<i32>(), any::<i32>(), any::<u32>())
+                .prop_map(|(dst, lhs, rhs, len)| OpCode::VMul(dst, lhs, rhs, len)),
+            (any::<i32>(), any::<u32>()).prop_map(|(start, len)| OpCode::VSum(start, len)),
+            (any::<i32>(), any::<i32>(), any::<u32>())
+                .prop_map(|(lhs, rhs, len)| OpCode::VDot(lhs, rhs, len)),
+            (any::<i32>(), any::<i32>(), any::<u32>(), plaintext_value()).prop_map(
+                |(dst, src, len, value)| OpCode::VMap(
+                    dst,
+                    src,
+                    len,
+                    UnaryOp::new(OpCode::MulImm(value)).unwrap()
+                )
+            ),
+            (any::<i32>(), any::<u32>()).prop_map(|(start, len)| OpCode::Sort(start, len)),
+            (any::<i32>(), any::<i32>(), any::<u32>(), any::<u32>())
+                .prop_map(|(keys, payloads, len, k)| OpCode::TopK(keys, payloads, len, k)),
+            (any::<i32>(), any::<u32>()).prop_map(|(start, len)| OpCode::ArgMax(start, len)),
+            any::<u32>().prop_map(OpCode::Lut),
+        ]
+    }
+
+    fn encode(program: &[OpCode]) -> Vec<Vec<u8>> {
+        program.iter().map(|op| op.to_bytes()).collect()
+    }
+
+    proptest! {
+        #[test]
+        fn test_deserialize_arbitrary_bytes(bytes in vec(any::<u8>(), 0..256)) {
+            // Must return an error rather than panic on malformed input
+            if let Ok(program) = deserialize(&bytes) {
+                prop_assert_eq!(serialize(&program), bytes);
+            }
+        }
+
+        #[test]
+        fn test_plaintext_program_roundtrip(program in vec(opcode(plaintext_value()), 0..32)) {
+            let bytes = serialize(&program);
+            let decoded = deserialize(&bytes).unwrap();
+            prop_assert_eq!(encode(&decoded), encode(&program));
+        }
+
+        #[test]
+        fn test_truncated_plaintext_program(program in vec(opcode(plaintext_value()), 1..32), cut in any::<prop::sample::Index>()) {
+            let bytes = serialize(&program);
+            let _ = deserialize(&bytes[..cut.index(bytes.len())]);
+        }
+    }
+
+    #[test]
+    fn test_program_roundtrip() {
+        let config = ConfigBuilder::default().build();
+        let (_client_key, server_keys) = generate_keys(config);
+        set_server_key(server_keys);
+
+        let mut runner = TestRunner::new(ProptestConfig::with_cases(32));
+        runner
+            .run(&vec(opcode(value()), 0..8), |program| {
+                let bytes = serialize(&program);
+                let decoded = deserialize(&bytes).unwrap();
+                prop_assert_eq!(encode(&decoded), encode(&program));
+
+                // Every strict prefix either decodes to fewer opcodes or is rejected
+                for cut in 0..bytes.len() {
+                    if let Ok(prefix) = deserialize(&bytes[..cut]) {
+                        prop_assert!(prefix.len() < program.len());
+                    }
+                }
+                Ok(())
+            })
+            .unwrap();
+    }
+}
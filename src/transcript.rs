@@ -0,0 +1,102 @@
+use crate::value::Value;
+use crate::vm::OpCode;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+
+// A rolling SHA-256 over everything a run did, for auditing. Each instruction
+// adds its index and encoding, the hashes of the stack items it consumed, and
+// the hashes of the stack items and memory cells it produced. tfhe operations
+// are deterministic for a given server key, so a verifier who re-runs the same
+// program on the same inputs gets the same digest.
+pub(crate) struct Transcript {
+    hasher: Sha256,
+}
+
+// What an instruction touches, worked out from the opcode before it runs so
+// that tracing doesn't need a copy of the stack or memory
+pub(crate) struct Footprint {
+    encoding: Vec<u8>,
+    consumes: usize,         // Stack items it pops or moves
+    writes: Vec<(i32, u32)>, // Memory ranges it stores to, as start and length
+}
+
+impl Footprint {
+    pub(crate) fn of(op: &OpCode) -> Self {
+        // Copies read the stack without consuming it
+        let consumes = match op {
+            OpCode::Dup | OpCode::Over | OpCode::Pick(_) => 0,
+            op => op.depth(),
+        };
+        let writes = match op {
+            OpCode::Store(address) => vec![(*address, 1)],
+            OpCode::VAdd(dst, _, _, len)
+            | OpCode::VMul(dst, _, _, len)
+            | OpCode::VMap(dst, _, len, _)
+            | OpCode::Sort(dst, len) => vec![(*dst, *len)],
+            OpCode::TopK(keys, payloads, len, k) => {
+                vec![(*keys, (*k).min(*len)), (*payloads, (*k).min(*len))]
+            }
+            _ => Vec::new(),
+        };
+        Footprint {
+            encoding: op.to_bytes(),
+            consumes,
+            writes,
+        }
+    }
+
+    pub(crate) fn consumes(&self) -> usize {
+        self.consumes
+    }
+}
+
+fn hash(value: &Value) -> [u8; 32] {
+    Sha256::digest(bincode::serialize(value).unwrap()).into()
+}
+
+impl Transcript {
+    pub(crate) fn new() -> Self {
+        Transcript {
+            hasher: Sha256::new(),
+        }
+    }
+
+    // `consumed` are the stack items the instruction replaced and `produced`
+    // what it left in their place. Only the cells it wrote are read from
+    // memory.
+    pub(crate) fn record(
+        &mut self,
+        ip: usize,
+        footprint: &Footprint,
+        consumed: &[Arc<Value>],
+        produced: &[Arc<Value>],
+        memory: &[Arc<Value>],
+    ) {
+        self.hasher.update((ip as u64).to_le_bytes());
+        self.hasher
+            .update((footprint.encoding.len() as u64).to_le_bytes());
+        self.hasher.update(&footprint.encoding);
+
+        for items in [consumed, produced] {
+            self.hasher.update((items.len() as u64).to_le_bytes());
+            for item in items {
+                self.hasher.update(hash(item));
+            }
+        }
+
+        let len: u32 = footprint.writes.iter().map(|(_, len)| len).sum();
+        self.hasher.update((len as u64).to_le_bytes());
+        for (start, len) in &footprint.writes {
+            let start: usize = (*start).try_into().unwrap();
+            let cells = &memory[start..start + *len as usize];
+            for (address, cell) in (start..).zip(cells) {
+                self.hasher.update((address as u64).to_le_bytes());
+                self.hasher.update(hash(cell));
+            }
+        }
+    }
+
+    pub(crate) fn digest(&self) -> [u8; 32] {
+        self.hasher.clone().finalize().into()
+    }
+}
@@ -1,56 +1,119 @@
+use crate::error::DecodeError;
 use serde::{Deserialize, Serialize};
 use std::ops::{Add, Rem, Sub};
 use tfhe::prelude::*;
-use tfhe::{FheBool, FheUint128, FheUint16, FheUint32, FheUint64, FheUint8};
+use tfhe::{
+    CompressedFheBool, CompressedFheUint128, CompressedFheUint16, CompressedFheUint32,
+    CompressedFheUint64, CompressedFheUint8,
+};
+use tfhe::{FheBool, FheUint128, FheUint16, FheUint32, FheUint64, FheUint8, MatchValues};
+
+// A lookup table from bytes to bytes, applied by Lut
+pub type Table = [u8; 256];
+
+// Plaintext counterparts of the operators used by binary_op!. Arithmetic wraps
+// and shift amounts are taken modulo the bit width, as they are for the tfhe
+// types, so plaintext results do not depend on the build profile.
+trait PlainOps {
+    fn add_method(self, other: Self) -> Self;
+    fn sub_method(self, other: Self) -> Self;
+    fn mul_method(self, other: Self) -> Self;
+    fn and_method(self, other: Self) -> Self;
+    fn or_method(self, other: Self) -> Self;
+    fn xor_method(self, other: Self) -> Self;
+    fn rem_method(self, other: Self) -> Self;
+    fn shr_method(self, other: Self) -> Self;
+    fn shl_method(self, other: Self) -> Self;
+}
+
+macro_rules! plain_ops {
+    ($($t:ty),*) => {
+        $(
+            impl PlainOps for $t {
+                fn add_method(self, other: Self) -> Self {
+                    self.wrapping_add(other)
+                }
+                fn sub_method(self, other: Self) -> Self {
+                    self.wrapping_sub(other)
+                }
+                fn mul_method(self, other: Self) -> Self {
+                    self.wrapping_mul(other)
+                }
+                fn and_method(self, other: Self) -> Self {
+                    self & other
+                }
+                fn or_method(self, other: Self) -> Self {
+                    self | other
+                }
+                fn xor_method(self, other: Self) -> Self {
+                    self ^ other
+                }
+                fn rem_method(self, other: Self) -> Self {
+                    self % other
+                }
+                fn shr_method(self, other: Self) -> Self {
+                    self.wrapping_shr(other as u32)
+                }
+                fn shl_method(self, other: Self) -> Self {
+                    self.wrapping_shl(other as u32)
+                }
+            }
+        )*
+    };
+}
+
+plain_ops!(u8, u16, u32, u64, u128);
 
 macro_rules! binary_op {
-    ($($op:ident, $op_method:ident, $op_token:tt);*) => {
+    ($($vis:vis $op:ident, $op_method:ident, $op_token:tt);*) => {
         impl Value {
             $(
-                pub fn $op(self, other: Self) -> Self {
+                $vis fn $op(&self, other: &Self) -> Self {
                     match (self, other) {
+                        (Value::Compressed(a), b) => a.decompress().$op(b),
+                        (a, Value::Compressed(b)) => a.$op(&b.decompress()),
                         // Plaintext, Plaintext
                         (Value::Bool(_a), Value::Bool(_b)) => unimplemented!(),
-                        (Value::Bool(a), Value::Uint8(b)) => Value::Uint8((a as u8) $op_token b),
-                        (Value::Bool(a), Value::Uint16(b)) => Value::Uint16((a as u16) $op_token b),
-                        (Value::Bool(a), Value::Uint32(b)) => Value::Uint32((a as u32) $op_token b),
-                        (Value::Bool(a), Value::Uint64(b)) => Value::Uint64((a as u64) $op_token b),
-                        (Value::Bool(a), Value::Uint128(b)) => Value::Uint128((a as u128) $op_token b),
-
-                        (Value::Uint8(a), Value::Bool(b)) => Value::Uint8(a $op_token (b as u8)),
-                        (Value::Uint8(a), Value::Uint8(b)) => Value::Uint8(a $op_token b),
-                        (Value::Uint8(a), Value::Uint16(b)) => Value::Uint16((a as u16) $op_token b),
-                        (Value::Uint8(a), Value::Uint32(b)) => Value::Uint32((a as u32) $op_token b),
-                        (Value::Uint8(a), Value::Uint64(b)) => Value::Uint64((a as u64) $op_token b),
-                        (Value::Uint8(a), Value::Uint128(b)) => Value::Uint128((a as u128) $op_token b),
+                        (Value::Bool(a), Value::Uint8(b)) => Value::Uint8((*a as u8).$op_method(*b)),
+                        (Value::Bool(a), Value::Uint16(b)) => Value::Uint16((*a as u16).$op_method(*b)),
+                        (Value::Bool(a), Value::Uint32(b)) => Value::Uint32((*a as u32).$op_method(*b)),
+                        (Value::Bool(a), Value::Uint64(b)) => Value::Uint64((*a as u64).$op_method(*b)),
+                        (Value::Bool(a), Value::Uint128(b)) => Value::Uint128((*a as u128).$op_method(*b)),
+
+                        (Value::Uint8(a), Value::Bool(b)) => Value::Uint8(a.$op_method(*b as u8)),
+                        (Value::Uint8(a), Value::Uint8(b)) => Value::Uint8(a.$op_method(*b)),
+                        (Value::Uint8(a), Value::Uint16(b)) => Value::Uint16((*a as u16).$op_method(*b)),
+                        (Value::Uint8(a), Value::Uint32(b)) => Value::Uint32((*a as u32).$op_method(*b)),
+                        (Value::Uint8(a), Value::Uint64(b)) => Value::Uint64((*a as u64).$op_method(*b)),
+                        (Value::Uint8(a), Value::Uint128(b)) => Value::Uint128((*a as u128).$op_method(*b)),
 
                         (Value::Uint16(_a), Value::Bool(_b)) => unimplemented!(),
-                        (Value::Uint16(a), Value::Uint8(b)) => Value::Uint16(a $op_token (b as u16)),
-                        (Value::Uint16(a), Value::Uint16(b)) => Value::Uint16(a $op_token b),
-                        (Value::Uint16(a), Value::Uint32(b)) => Value::Uint32((a as u32) $op_token b),
-                        (Value::Uint16(a), Value::Uint64(b)) => Value::Uint64((a as u64) $op_token b),
-                        (Value::Uint16(a), Value::Uint128(b)) => Value::Uint128((a as u128) $op_token b),
+                        (Value::Uint16(a), Value::Uint8(b)) => Value::Uint16(a.$op_method(*b as u16)),
+                        (Value::Uint16(a), Value::Uint16(b)) => Value::Uint16(a.$op_method(*b)),
+                        (Value::Uint16(a), Value::Uint32(b)) => Value::Uint32((*a as u32).$op_method(*b)),
+                        (Value::Uint16(a), Value::Uint64(b)) => Value::Uint64((*a as u64).$op_method(*b)),
+                        (Value::Uint16(a), Value::Uint128(b)) => Value::Uint128((*a as u128).$op_method(*b)),
 
                         (Value::Uint32(_a), Value::Bool(_b)) => unimplemented!(),
-                        (Value::Uint32(a), Value::Uint8(b)) => Value::Uint32(a $op_token (b as u32)),
-                        (Value::Uint32(a), Value::Uint16(b)) => Value::Uint32(a $op_token (b as u32)),
-                        (Value::Uint32(a), Value::Uint32(b)) => Value::Uint32(a $op_token b),
-                        (Value::Uint32(a), Value::Uint64(b)) => Value::Uint64((a as u64) $op_token b),
-                        (Value::Uint32(a), Value::Uint128(b)) => Value::Uint128((a as u128) $op_token b),
+                        (Value::Uint32(a), Value::Uint8(b)) => Value::Uint32(a.$op_method(*b as u32)),
+                        (Value::Uint32(a), Value::Uint16(b)) => Value::Uint32(a.$op_method(*b as u32)),
+                        (Value::Uint32(a), Value::Uint32(b)) => Value::Uint32(a.$op_method(*b)),
+                        (Value::Uint32(a), Value::Uint64(b)) => Value::Uint64((*a as u64).$op_method(*b)),
+                        (Value::Uint32(a), Value::Uint128(b)) => Value::Uint128((*a as u128).$op_method(*b)),
 
                         (Value::Uint64(_a), Value::Bool(_b)) => unimplemented!(),
-                        (Value::Uint64(a), Value::Uint8(b)) => Value::Uint64(a $op_token (b as u64)),
-                        (Value::Uint64(a), Value::Uint16(b)) => Value::Uint64(a $op_token (b as u64)),
-                        (Value::Uint64(a), Value::Uint32(b)) => Value::Uint64(a $op_token (b as u64)),
-                        (Value::Uint64(a), Value::Uint64(b)) => Value::Uint64(a $op_token b),
-                        (Value::Uint64(a), Value::Uint128(b)) => Value::Uint128((a as u128) $op_token b),
+                        (Value::Uint64(a), Value::Uint8(b)) => Value::Uint64(a.$op_method(*b as u64)),
+                        (Value::Uint64(a), Value::Uint16(b)) => Value::Uint64(a.$op_method(*b as u64)),
+                        (Value::Uint64(a), Value::Uint32(b)) => Value::Uint64(a.$op_method(*b as u64)),
+                        (Value::Uint64(a), Value::Uint64(b)) => Value::Uint64(a.$op_method(*b)),
+                        (Value::Uint64(a), Value::Uint128(b)) => Value::Uint128((*a as u128).$op_method(*b)),
 
                         (Value::Uint128(_a), Value::Bool(_b)) => unimplemented!(),
-                        (Value::Uint128(a), Value::Uint8(b)) => Value::Uint128(a $op_token (b as u128)),
-                        (Value::Uint128(a), Value::Uint16(b)) => Value::Uint128(a $op_token (b as u128)),
-                        (Value::Uint128(a), Value::Uint32(b)) => Value::Uint128(a $op_token (b as u128)),
-                        (Value::Uint128(a), Value::Uint64(b)) => Value::Uint128(a $op_token (b as u128)),
-                        (Value::Uint128(a), Value::Uint128(b)) => Value::Uint128(a $op_token b),
+                        (Value::Uint128(a), Value::Uint8(b)) => Value::Uint128(a.$op_method(*b as u128)),
+                        (Value::Uint128(a), Value::Uint16(b)) => Value::Uint128(a.$op_method(*b as u128)),
+                        (Value::Uint128(a), Value::Uint32(b)) => Value::Uint128(a.$op_method(*b as u128)),
+                        (Value::Uint128(a), Value::Uint64(b)) => Value::Uint128(a.$op_method(*b as u128)),
+                        (Value::Uint128(a), Value::Uint128(b)) => Value::Uint128(a.$op_method(*b)),
 
                         // Plaintext, Encrypted
                         (Value::Bool(_a), Value::Ebool(_b)) => unimplemented!(),
@@ -61,39 +124,39 @@ macro_rules! binary_op {
                         (Value::Bool(_a), Value::Euint128(_b)) => unimplemented!(),
 
                         (Value::Uint8(_a), Value::Ebool(_b)) => unimplemented!(),
-                        (Value::Uint8(a), Value::Euint8(b)) => Value::Euint8(FheUint8::encrypt_trivial(a) $op_token b),
-                        (Value::Uint8(a), Value::Euint16(b)) => Value::Euint16(FheUint16::encrypt_trivial(a) $op_token b),
-                        (Value::Uint8(a), Value::Euint32(b)) => Value::Euint32(FheUint32::encrypt_trivial(a) $op_token b),
-                        (Value::Uint8(a), Value::Euint64(b)) => Value::Euint64(FheUint64::encrypt_trivial(a) $op_token b),
-                        (Value::Uint8(a), Value::Euint128(b)) => Value::Euint128(FheUint128::encrypt_trivial(a) $op_token b),
+                        (Value::Uint8(a), Value::Euint8(b)) => Value::Euint8(FheUint8::encrypt_trivial(*a) $op_token b),
+                        (Value::Uint8(a), Value::Euint16(b)) => Value::Euint16(FheUint16::encrypt_trivial(*a) $op_token b),
+                        (Value::Uint8(a), Value::Euint32(b)) => Value::Euint32(FheUint32::encrypt_trivial(*a) $op_token b),
+                        (Value::Uint8(a), Value::Euint64(b)) => Value::Euint64(FheUint64::encrypt_trivial(*a) $op_token b),
+                        (Value::Uint8(a), Value::Euint128(b)) => Value::Euint128(FheUint128::encrypt_trivial(*a) $op_token b),
 
                         (Value::Uint16(_a), Value::Ebool(_b)) => unimplemented!(),
-                        (Value::Uint16(a), Value::Euint8(b)) => Value::Euint8(FheUint8::encrypt_trivial(a) $op_token b),
-                        (Value::Uint16(a), Value::Euint16(b)) => Value::Euint16(FheUint16::encrypt_trivial(a) $op_token b),
-                        (Value::Uint16(a), Value::Euint32(b)) => Value::Euint32(FheUint32::encrypt_trivial(a) $op_token b),
-                        (Value::Uint16(a), Value::Euint64(b)) => Value::Euint64(FheUint64::encrypt_trivial(a) $op_token b),
-                        (Value::Uint16(a), Value::Euint128(b)) => Value::Euint128(FheUint128::encrypt_trivial(a) $op_token b),
+                        (Value::Uint16(a), Value::Euint8(b)) => Value::Euint8(FheUint8::encrypt_trivial(*a) $op_token b),
+                        (Value::Uint16(a), Value::Euint16(b)) => Value::Euint16(FheUint16::encrypt_trivial(*a) $op_token b),
+                        (Value::Uint16(a), Value::Euint32(b)) => Value::Euint32(FheUint32::encrypt_trivial(*a) $op_token b),
+                        (Value::Uint16(a), Value::Euint64(b)) => Value::Euint64(FheUint64::encrypt_trivial(*a) $op_token b),
+                        (Value::Uint16(a), Value::Euint128(b)) => Value::Euint128(FheUint128::encrypt_trivial(*a) $op_token b),
 
                         (Value::Uint32(_a), Value::Ebool(_b)) => unimplemented!(),
-                        (Value::Uint32(a), Value::Euint8(b)) => Value::Euint8(FheUint8::encrypt_trivial(a) $op_token b),
-                        (Value::Uint32(a), Value::Euint16(b)) => Value::Euint16(FheUint16::encrypt_trivial(a) $op_token b),
-                        (Value::Uint32(a), Value::Euint32(b)) => Value::Euint32(FheUint32::encrypt_trivial(a) $op_token b),
-                        (Value::Uint32(a), Value::Euint64(b)) => Value::Euint64(FheUint64::encrypt_trivial(a) $op_token b),
-                        (Value::Uint32(a), Value::Euint128(b)) => Value::Euint128(FheUint128::encrypt_trivial(a) $op_token b),
+                        (Value::Uint32(a), Value::Euint8(b)) => Value::Euint8(FheUint8::encrypt_trivial(*a) $op_token b),
+                        (Value::Uint32(a), Value::Euint16(b)) => Value::Euint16(FheUint16::encrypt_trivial(*a) $op_token b),
+                        (Value::Uint32(a), Value::Euint32(b)) => Value::Euint32(FheUint32::encrypt_trivial(*a) $op_token b),
+                        (Value::Uint32(a), Value::Euint64(b)) => Value::Euint64(FheUint64::encrypt_trivial(*a) $op_token b),
+                        (Value::Uint32(a), Value::Euint128(b)) => Value::Euint128(FheUint128::encrypt_trivial(*a) $op_token b),
 
                         (Value::Uint64(_a), Value::Ebool(_b)) => unimplemented!(),
-                        (Value::Uint64(a), Value::Euint8(b)) => Value::Euint8(FheUint8::encrypt_trivial(a) $op_token b),
-                        (Value::Uint64(a), Value::Euint16(b)) => Value::Euint16(FheUint16::encrypt_trivial(a) $op_token b),
-                        (Value::Uint64(a), Value::Euint32(b)) => Value::Euint32(FheUint32::encrypt_trivial(a) $op_token b),
-                        (Value::Uint64(a), Value::Euint64(b)) => Value::Euint64(FheUint64::encrypt_trivial(a) $op_token b),
-                        (Value::Uint64(a), Value::Euint128(b)) => Value::Euint128(FheUint128::encrypt_trivial(a) $op_token b),
+                        (Value::Uint64(a), Value::Euint8(b)) => Value::Euint8(FheUint8::encrypt_trivial(*a) $op_token b),
+                        (Value::Uint64(a), Value::Euint16(b)) => Value::Euint16(FheUint16::encrypt_trivial(*a) $op_token b),
+                        (Value::Uint64(a), Value::Euint32(b)) => Value::Euint32(FheUint32::encrypt_trivial(*a) $op_token b),
+                        (Value::Uint64(a), Value::Euint64(b)) => Value::Euint64(FheUint64::encrypt_trivial(*a) $op_token b),
+                        (Value::Uint64(a), Value::Euint128(b)) => Value::Euint128(FheUint128::encrypt_trivial(*a) $op_token b),
 
                         (Value::Uint128(_a), Value::Ebool(_b)) => unimplemented!(),
-                        (Value::Uint128(a), Value::Euint8(b)) => Value::Euint8(FheUint8::encrypt_trivial(a) $op_token b),
-                        (Value::Uint128(a), Value::Euint16(b)) => Value::Euint16(FheUint16::encrypt_trivial(a) $op_token b),
-                        (Value::Uint128(a), Value::Euint32(b)) => Value::Euint32(FheUint32::encrypt_trivial(a) $op_token b),
-                        (Value::Uint128(a), Value::Euint64(b)) => Value::Euint64(FheUint64::encrypt_trivial(a) $op_token b),
-                        (Value::Uint128(a), Value::Euint128(b)) => Value::Euint128(FheUint128::encrypt_trivial(a) $op_token b),
+                        (Value::Uint128(a), Value::Euint8(b)) => Value::Euint8(FheUint8::encrypt_trivial(*a) $op_token b),
+                        (Value::Uint128(a), Value::Euint16(b)) => Value::Euint16(FheUint16::encrypt_trivial(*a) $op_token b),
+                        (Value::Uint128(a), Value::Euint32(b)) => Value::Euint32(FheUint32::encrypt_trivial(*a) $op_token b),
+                        (Value::Uint128(a), Value::Euint64(b)) => Value::Euint64(FheUint64::encrypt_trivial(*a) $op_token b),
+                        (Value::Uint128(a), Value::Euint128(b)) => Value::Euint128(FheUint128::encrypt_trivial(*a) $op_token b),
 
                         // Encrypted, Plaintext
                         (Value::Ebool(_a), Value::Bool(_b)) => unimplemented!(),
@@ -104,39 +167,39 @@ macro_rules! binary_op {
                         (Value::Euint128(_a), Value::Bool(_b)) => unimplemented!(),
 
                         (Value::Ebool(_a), Value::Uint8(_b)) => unimplemented!(),
-                        (Value::Euint8(a), Value::Uint8(b)) => Value::Euint8(a $op_token b),
-                        (Value::Euint16(a), Value::Uint8(b)) => Value::Euint8(FheUint8::cast_from(a) $op_token b),
-                        (Value::Euint32(a), Value::Uint8(b)) => Value::Euint8(FheUint8::cast_from(a) $op_token b),
-                        (Value::Euint64(a), Value::Uint8(b)) => Value::Euint8(FheUint8::cast_from(a) $op_token b),
-                        (Value::Euint128(a), Value::Uint8(b)) => Value::Euint8(FheUint8::cast_from(a) $op_token b),
+                        (Value::Euint8(a), Value::Uint8(b)) => Value::Euint8(a $op_token *b),
+                        (Value::Euint16(a), Value::Uint8(b)) => Value::Euint8(FheUint8::cast_from(a.clone()) $op_token *b),
+                        (Value::Euint32(a), Value::Uint8(b)) => Value::Euint8(FheUint8::cast_from(a.clone()) $op_token *b),
+                        (Value::Euint64(a), Value::Uint8(b)) => Value::Euint8(FheUint8::cast_from(a.clone()) $op_token *b),
+                        (Value::Euint128(a), Value::Uint8(b)) => Value::Euint8(FheUint8::cast_from(a.clone()) $op_token *b),
 
                         (Value::Ebool(_a), Value::Uint16(_b)) => unimplemented!(),
-                        (Value::Euint8(a), Value::Uint16(b)) => Value::Euint16(FheUint16::cast_from(a) $op_token b),
-                        (Value::Euint16(a), Value::Uint16(b)) => Value::Euint16(a $op_token b),
-                        (Value::Euint32(a), Value::Uint16(b)) => Value::Euint16(FheUint16::cast_from(a) $op_token b),
-                        (Value::Euint64(a), Value::Uint16(b)) => Value::Euint16(FheUint16::cast_from(a) $op_token b),
-                        (Value::Euint128(a), Value::Uint16(b)) => Value::Euint16(FheUint16::cast_from(a) $op_token b),
+                        (Value::Euint8(a), Value::Uint16(b)) => Value::Euint16(FheUint16::cast_from(a.clone()) $op_token *b),
+                        (Value::Euint16(a), Value::Uint16(b)) => Value::Euint16(a $op_token *b),
+                        (Value::Euint32(a), Value::Uint16(b)) => Value::Euint16(FheUint16::cast_from(a.clone()) $op_token *b),
+                        (Value::Euint64(a), Value::Uint16(b)) => Value::Euint16(FheUint16::cast_from(a.clone()) $op_token *b),
+                        (Value::Euint128(a), Value::Uint16(b)) => Value::Euint16(FheUint16::cast_from(a.clone()) $op_token *b),
 
                         (Value::Ebool(_a), Value::Uint32(_b)) => unimplemented!(),
-                        (Value::Euint8(a), Value::Uint32(b)) => Value::Euint32(FheUint32::cast_from(a) $op_token b),
-                        (Value::Euint16(a), Value::Uint32(b)) => Value::Euint32(FheUint32::cast_from(a) $op_token b),
-                        (Value::Euint32(a), Value::Uint32(b)) => Value::Euint32(a $op_token b),
-                        (Value::Euint64(a), Value::Uint32(b)) => Value::Euint32(FheUint32::cast_from(a) $op_token b),
-                        (Value::Euint128(a), Value::Uint32(b)) => Value::Euint32(FheUint32::cast_from(a) $op_token b),
+                        (Value::Euint8(a), Value::Uint32(b)) => Value::Euint32(FheUint32::cast_from(a.clone()) $op_token *b),
+                        (Value::Euint16(a), Value::Uint32(b)) => Value::Euint32(FheUint32::cast_from(a.clone()) $op_token *b),
+                        (Value::Euint32(a), Value::Uint32(b)) => Value::Euint32(a $op_token *b),
+                        (Value::Euint64(a), Value::Uint32(b)) => Value::Euint32(FheUint32::cast_from(a.clone()) $op_token *b),
+                        (Value::Euint128(a), Value::Uint32(b)) => Value::Euint32(FheUint32::cast_from(a.clone()) $op_token *b),
 
                         (Value::Ebool(_a), Value::Uint64(_b)) => unimplemented!(),
-                        (Value::Euint8(a), Value::Uint64(b)) => Value::Euint64(FheUint64::cast_from(a) $op_token b),
-                        (Value::Euint16(a), Value::Uint64(b)) => Value::Euint64(FheUint64::cast_from(a) $op_token b),
-                        (Value::Euint32(a), Value::Uint64(b)) => Value::Euint64(FheUint64::cast_from(a) $op_token b),
-                        (Value::Euint64(a), Value::Uint64(b)) => Value::Euint64(a $op_token b),
-                        (Value::Euint128(a), Value::Uint64(b)) => Value::Euint64(FheUint64::cast_from(a) $op_token b),
+                        (Value::Euint8(a), Value::Uint64(b)) => Value::Euint64(FheUint64::cast_from(a.clone()) $op_token *b),
+                        (Value::Euint16(a), Value::Uint64(b)) => Value::Euint64(FheUint64::cast_from(a.clone()) $op_token *b),
+                        (Value::Euint32(a), Value::Uint64(b)) => Value::Euint64(FheUint64::cast_from(a.clone()) $op_token *b),
+                        (Value::Euint64(a), Value::Uint64(b)) => Value::Euint64(a $op_token *b),
+                        (Value::Euint128(a), Value::Uint64(b)) => Value::Euint64(FheUint64::cast_from(a.clone()) $op_token *b),
 
                         (Value::Ebool(_a), Value::Uint128(_b)) => unimplemented!(),
-                        (Value::Euint8(a), Value::Uint128(b)) => Value::Euint128(FheUint128::cast_from(a) $op_token b),
-                        (Value::Euint16(a), Value::Uint128(b)) => Value::Euint128(FheUint128::cast_from(a) $op_token b),
-                        (Value::Euint32(a), Value::Uint128(b)) => Value::Euint128(FheUint128::cast_from(a) $op_token b),
-                        (Value::Euint64(a), Value::Uint128(b)) => Value::Euint128(FheUint128::cast_from(a) $op_token b),
-                        (Value::Euint128(a), Value::Uint128(b)) => Value::Euint128(a $op_token b),
+                        (Value::Euint8(a), Value::Uint128(b)) => Value::Euint128(FheUint128::cast_from(a.clone()) $op_token *b),
+                        (Value::Euint16(a), Value::Uint128(b)) => Value::Euint128(FheUint128::cast_from(a.clone()) $op_token *b),
+                        (Value::Euint32(a), Value::Uint128(b)) => Value::Euint128(FheUint128::cast_from(a.clone()) $op_token *b),
+                        (Value::Euint64(a), Value::Uint128(b)) => Value::Euint128(FheUint128::cast_from(a.clone()) $op_token *b),
+                        (Value::Euint128(a), Value::Uint128(b)) => Value::Euint128(a $op_token *b),
 
                         // Encrypted, Encrypted
                         (Value::Ebool(_a), Value::Ebool(_b)) => unimplemented!(),
@@ -148,37 +211,37 @@ macro_rules! binary_op {
 
                         (Value::Euint8(_a), Value::Ebool(_b)) => unimplemented!(),
                         (Value::Euint8(a), Value::Euint8(b)) => Value::Euint8(a $op_token b),
-                        (Value::Euint8(a), Value::Euint16(b)) => Value::Euint16(FheUint16::cast_from(a) $op_token b),
-                        (Value::Euint8(a), Value::Euint32(b)) => Value::Euint32(FheUint32::cast_from(a) $op_token b),
-                        (Value::Euint8(a), Value::Euint64(b)) => Value::Euint64(FheUint64::cast_from(a) $op_token b),
-                        (Value::Euint8(a), Value::Euint128(b)) => Value::Euint128(FheUint128::cast_from(a) $op_token b),
+                        (Value::Euint8(a), Value::Euint16(b)) => Value::Euint16(FheUint16::cast_from(a.clone()) $op_token b),
+                        (Value::Euint8(a), Value::Euint32(b)) => Value::Euint32(FheUint32::cast_from(a.clone()) $op_token b),
+                        (Value::Euint8(a), Value::Euint64(b)) => Value::Euint64(FheUint64::cast_from(a.clone()) $op_token b),
+                        (Value::Euint8(a), Value::Euint128(b)) => Value::Euint128(FheUint128::cast_from(a.clone()) $op_token b),
 
                         (Value::Euint16(_a), Value::Ebool(_b)) => unimplemented!(),
-                        (Value::Euint16(a), Value::Euint8(b)) => Value::Euint16(a $op_token FheUint16::cast_from(b)),
+                        (Value::Euint16(a), Value::Euint8(b)) => Value::Euint16(a $op_token FheUint16::cast_from(b.clone())),
                         (Value::Euint16(a), Value::Euint16(b)) => Value::Euint16(a $op_token b),
-                        (Value::Euint16(a), Value::Euint32(b)) => Value::Euint32(FheUint32::cast_from(a) $op_token b),
-                        (Value::Euint16(a), Value::Euint64(b)) => Value::Euint64(FheUint64::cast_from(a) $op_token b),
-                        (Value::Euint16(a), Value::Euint128(b)) => Value::Euint128(FheUint128::cast_from(a) $op_token b),
+                        (Value::Euint16(a), Value::Euint32(b)) => Value::Euint32(FheUint32::cast_from(a.clone()) $op_token b),
+                        (Value::Euint16(a), Value::Euint64(b)) => Value::Euint64(FheUint64::cast_from(a.clone()) $op_token b),
+                        (Value::Euint16(a), Value::Euint128(b)) => Value::Euint128(FheUint128::cast_from(a.clone()) $op_token b),
 
                         (Value::Euint32(_a), Value::Ebool(_b)) => unimplemented!(),
-                        (Value::Euint32(a), Value::Euint8(b)) => Value::Euint32(a $op_token FheUint32::cast_from(b)),
-                        (Value::Euint32(a), Value::Euint16(b)) => Value::Euint32(a $op_token FheUint32::cast_from(b)),
+                        (Value::Euint32(a), Value::Euint8(b)) => Value::Euint32(a $op_token FheUint32::cast_from(b.clone())),
+                        (Value::Euint32(a), Value::Euint16(b)) => Value::Euint32(a $op_token FheUint32::cast_from(b.clone())),
                         (Value::Euint32(a), Value::Euint32(b)) => Value::Euint32(a $op_token b),
-                        (Value::Euint32(a), Value::Euint64(b)) => Value::Euint64(FheUint64::cast_from(a) $op_token b),
-                        (Value::Euint32(a), Value::Euint128(b)) => Value::Euint128(FheUint128::cast_from(a) $op_token b),
+                        (Value::Euint32(a), Value::Euint64(b)) => Value::Euint64(FheUint64::cast_from(a.clone()) $op_token b),
+                        (Value::Euint32(a), Value::Euint128(b)) => Value::Euint128(FheUint128::cast_from(a.clone()) $op_token b),
 
                         (Value::Euint64(_a), Value::Ebool(_b)) => unimplemented!(),
-                        (Value::Euint64(a), Value::Euint8(b)) => Value::Euint64(a $op_token FheUint64::cast_from(b)),
-                        (Value::Euint64(a), Value::Euint16(b)) => Value::Euint64(a $op_token FheUint64::cast_from(b)),
-                        (Value::Euint64(a), Value::Euint32(b)) => Value::Euint64(a $op_token FheUint64::cast_from(b)),
+                        (Value::Euint64(a), Value::Euint8(b)) => Value::Euint64(a $op_token FheUint64::cast_from(b.clone())),
+                        (Value::Euint64(a), Value::Euint16(b)) => Value::Euint64(a $op_token FheUint64::cast_from(b.clone())),
+                        (Value::Euint64(a), Value::Euint32(b)) => Value::Euint64(a $op_token FheUint64::cast_from(b.clone())),
                         (Value::Euint64(a), Value::Euint64(b)) => Value::Euint64(a $op_token b),
-                        (Value::Euint64(a), Value::Euint128(b)) => Value::Euint128(FheUint128::cast_from(a) $op_token b),
+                        (Value::Euint64(a), Value::Euint128(b)) => Value::Euint128(FheUint128::cast_from(a.clone()) $op_token b),
 
                         (Value::Euint128(_a), Value::Ebool(_b)) => unimplemented!(),
-                        (Value::Euint128(a), Value::Euint8(b)) => Value::Euint128(a $op_token FheUint128::cast_from(b)),
-                        (Value::Euint128(a), Value::Euint16(b)) => Value::Euint128(a $op_token FheUint128::cast_from(b)),
-                        (Value::Euint128(a), Value::Euint32(b)) => Value::Euint128(a $op_token FheUint128::cast_from(b)),
-                        (Value::Euint128(a), Value::Euint64(b)) => Value::Euint128(a $op_token FheUint128::cast_from(b)),
+                        (Value::Euint128(a), Value::Euint8(b)) => Value::Euint128(a $op_token FheUint128::cast_from(b.clone())),
+                        (Value::Euint128(a), Value::Euint16(b)) => Value::Euint128(a $op_token FheUint128::cast_from(b.clone())),
+                        (Value::Euint128(a), Value::Euint32(b)) => Value::Euint128(a $op_token FheUint128::cast_from(b.clone())),
+                        (Value::Euint128(a), Value::Euint64(b)) => Value::Euint128(a $op_token FheUint128::cast_from(b.clone())),
                         (Value::Euint128(a), Value::Euint128(b)) => Value::Euint128(a $op_token b),
 
                     }
@@ -189,20 +252,236 @@ macro_rules! binary_op {
 }
 
 binary_op! {
-    add_op, add_method, +;
-    sub_op, sub_method, -;
-    mul_op, mul_method, *;
-    and_op, and_method, &;
-    or_op, or_method, |;
-    xor_op, xor_method, ^;
-    rem_op, rem_method, %;
-    shr_op, shr_method, >>;
-    shl_op, shl_method, <<
+    pub add_op, add_method, +;
+    pub sub_op, sub_method, -;
+    pub mul_op, mul_method, *;
+    int_and_op, and_method, &;
+    int_or_op, or_method, |;
+    int_xor_op, xor_method, ^;
+    pub rem_op, rem_method, %;
+    pub shr_op, shr_method, >>;
+    pub shl_op, shl_method, <<
+}
+
+// Boolean operands are combined here; everything else falls through to the
+// integer implementation generated by binary_op!.
+macro_rules! bitwise_op {
+    ($($op:ident, $int_op:ident, $op_token:tt);*) => {
+        impl Value {
+            $(
+                pub fn $op(&self, other: &Self) -> Self {
+                    match (self, other) {
+                        (Value::Bool(a), Value::Bool(b)) => Value::Bool(a $op_token b),
+                        (Value::Bool(a), Value::Ebool(b)) => Value::Ebool(*a $op_token b),
+                        (Value::Ebool(a), Value::Bool(b)) => Value::Ebool(a $op_token *b),
+                        (Value::Ebool(a), Value::Ebool(b)) => Value::Ebool(a $op_token b),
+                        (Value::Compressed(a), b) => a.decompress().$op(b),
+                        (a, Value::Compressed(b)) => a.$op(&b.decompress()),
+                        (a, b) => a.$int_op(b),
+                    }
+                }
+            )*
+        }
+    };
+}
+
+bitwise_op! {
+    and_op, int_and_op, &;
+    or_op, int_or_op, |;
+    xor_op, int_xor_op, ^
+}
+
+impl Value {
+    pub fn not_op(&self) -> Self {
+        match self {
+            Value::Compressed(val) => val.decompress().not_op(),
+            Value::Ebool(val) => Value::Ebool(!val),
+            Value::Euint8(val) => Value::Euint8(!val),
+            Value::Euint16(val) => Value::Euint16(!val),
+            Value::Euint32(val) => Value::Euint32(!val),
+            Value::Euint64(val) => Value::Euint64(!val),
+            Value::Euint128(val) => Value::Euint128(!val),
+            Value::Bool(val) => Value::Bool(!val),
+            Value::Uint8(val) => Value::Uint8(!val),
+            Value::Uint16(val) => Value::Uint16(!val),
+            Value::Uint32(val) => Value::Uint32(!val),
+            Value::Uint64(val) => Value::Uint64(!val),
+            Value::Uint128(val) => Value::Uint128(!val),
+        }
+    }
+}
+
+// Checked arithmetic promotes both operands to the wider of the two widths,
+// as the comparisons do, so no high bits are lost before the overflow check.
+// Returns the wrapped result together with an overflow flag for that width.
+macro_rules! overflowing_op {
+    ($($op:ident, $method:ident);*) => {
+        impl Value {
+            $(
+                pub fn $op(&self, other: &Self) -> (Self, Self) {
+                    fn flagged<T, F>(
+                        wrap: fn(T) -> Value,
+                        flag: fn(F) -> Value,
+                        (result, overflow): (T, F),
+                    ) -> (Value, Value) {
+                        (wrap(result), flag(overflow))
+                    }
+
+                    match (self, other) {
+                        (Value::Uint8(a), Value::Uint8(b)) => flagged(Value::Uint8, Value::Bool, a.$method(*b)),
+                        (Value::Uint16(a), Value::Uint16(b)) => flagged(Value::Uint16, Value::Bool, a.$method(*b)),
+                        (Value::Uint32(a), Value::Uint32(b)) => flagged(Value::Uint32, Value::Bool, a.$method(*b)),
+                        (Value::Uint64(a), Value::Uint64(b)) => flagged(Value::Uint64, Value::Bool, a.$method(*b)),
+                        (Value::Uint128(a), Value::Uint128(b)) => flagged(Value::Uint128, Value::Bool, a.$method(*b)),
+
+                        (Value::Euint8(a), Value::Euint8(b)) => flagged(Value::Euint8, Value::Ebool, a.$method(b)),
+                        (Value::Euint16(a), Value::Euint16(b)) => flagged(Value::Euint16, Value::Ebool, a.$method(b)),
+                        (Value::Euint32(a), Value::Euint32(b)) => flagged(Value::Euint32, Value::Ebool, a.$method(b)),
+                        (Value::Euint64(a), Value::Euint64(b)) => flagged(Value::Euint64, Value::Ebool, a.$method(b)),
+                        (Value::Euint128(a), Value::Euint128(b)) => flagged(Value::Euint128, Value::Ebool, a.$method(b)),
+
+                        (Value::Compressed(a), b) => a.decompress().$op(b),
+                        (a, Value::Compressed(b)) => a.$op(&b.decompress()),
+                        (a, b) => {
+                            let (a, b) = a.clone().promote(b.clone());
+                            a.$op(&b)
+                        }
+                    }
+                }
+            )*
+        }
+    };
+}
+
+overflowing_op! {
+    add_checked_op, overflowing_add;
+    sub_checked_op, overflowing_sub;
+    mul_checked_op, overflowing_mul
+}
+
+impl Value {
+    // Saturating arithmetic: the checked result is replaced by the bound of the
+    // promoted type with a single if_then_else when the overflow flag is set.
+    pub fn add_sat_op(&self, other: &Self) -> Self {
+        let (result, overflow) = self.add_checked_op(other);
+        result.saturate(overflow, u128::MAX)
+    }
+
+    pub fn sub_sat_op(&self, other: &Self) -> Self {
+        let (result, overflow) = self.sub_checked_op(other);
+        result.saturate(overflow, 0)
+    }
+
+    pub fn mul_sat_op(&self, other: &Self) -> Self {
+        let (result, overflow) = self.mul_checked_op(other);
+        result.saturate(overflow, u128::MAX)
+    }
+
+    // Truncating the bound to the result width turns u128::MAX into that
+    // width's maximum.
+    fn saturate(self, overflow: Self, bound: u128) -> Self {
+        match (self, overflow) {
+            (Value::Euint8(val), Value::Ebool(overflow)) => {
+                Value::Euint8(overflow.if_then_else(&FheUint8::encrypt_trivial(bound as u8), &val))
+            }
+            (Value::Euint16(val), Value::Ebool(overflow)) => Value::Euint16(
+                overflow.if_then_else(&FheUint16::encrypt_trivial(bound as u16), &val),
+            ),
+            (Value::Euint32(val), Value::Ebool(overflow)) => Value::Euint32(
+                overflow.if_then_else(&FheUint32::encrypt_trivial(bound as u32), &val),
+            ),
+            (Value::Euint64(val), Value::Ebool(overflow)) => Value::Euint64(
+                overflow.if_then_else(&FheUint64::encrypt_trivial(bound as u64), &val),
+            ),
+            (Value::Euint128(val), Value::Ebool(overflow)) => {
+                Value::Euint128(overflow.if_then_else(&FheUint128::encrypt_trivial(bound), &val))
+            }
+            (Value::Uint8(val), Value::Bool(overflow)) => {
+                Value::Uint8(if overflow { bound as u8 } else { val })
+            }
+            (Value::Uint16(val), Value::Bool(overflow)) => {
+                Value::Uint16(if overflow { bound as u16 } else { val })
+            }
+            (Value::Uint32(val), Value::Bool(overflow)) => {
+                Value::Uint32(if overflow { bound as u32 } else { val })
+            }
+            (Value::Uint64(val), Value::Bool(overflow)) => {
+                Value::Uint64(if overflow { bound as u64 } else { val })
+            }
+            (Value::Uint128(val), Value::Bool(overflow)) => {
+                Value::Uint128(if overflow { bound } else { val })
+            }
+            _ => unimplemented!(),
+        }
+    }
+}
+
+// Immediate operands are plaintext scalars applied at the width of the stack
+// operand, so encrypted operands go through the cheaper tfhe scalar ops. An
+// immediate wider than the operand widens the operand first, as the binary
+// ops do, so a boolean operand only stays boolean with a Bool immediate. An
+// encrypted immediate can only come from a hand-built program and falls back
+// to the binary op.
+macro_rules! imm_op {
+    ($($op:ident, $binary_op:ident, $op_method:ident, $op_token:tt, $bool_token:tt);*) => {
+        impl Value {
+            $(
+                pub fn $op(self, imm: &Self) -> Self {
+                    let (ty, imm_ty) = (self.value_type(), imm.value_type());
+                    if imm_ty.is_encrypted() {
+                        let (a, b) = self.promote(imm.clone());
+                        return a.$binary_op(&b);
+                    }
+                    if imm_ty.bits() > ty.bits() {
+                        return self.widen(imm_ty.bits(), ty.is_encrypted()).$op(imm);
+                    }
+                    let scalar = imm.as_scalar();
+                    match self {
+                        Value::Compressed(val) => val.decompress().$op(imm),
+                        Value::Ebool(val) => Value::Ebool(val $bool_token (scalar & 1 == 1)),
+                        Value::Euint8(val) => Value::Euint8(val $op_token scalar as u8),
+                        Value::Euint16(val) => Value::Euint16(val $op_token scalar as u16),
+                        Value::Euint32(val) => Value::Euint32(val $op_token scalar as u32),
+                        Value::Euint64(val) => Value::Euint64(val $op_token scalar as u64),
+                        Value::Euint128(val) => Value::Euint128(val $op_token scalar),
+                        Value::Bool(val) => Value::Bool(val $bool_token (scalar & 1 == 1)),
+                        Value::Uint8(val) => Value::Uint8(val.$op_method(scalar as u8)),
+                        Value::Uint16(val) => Value::Uint16(val.$op_method(scalar as u16)),
+                        Value::Uint32(val) => Value::Uint32(val.$op_method(scalar as u32)),
+                        Value::Uint64(val) => Value::Uint64(val.$op_method(scalar as u64)),
+                        Value::Uint128(val) => Value::Uint128(val.$op_method(scalar)),
+                    }
+                }
+            )*
+        }
+    };
+}
+
+imm_op!(
+    add_imm_op, add_op, wrapping_add, +, ^;
+    sub_imm_op, sub_op, wrapping_sub, -, ^;
+    mul_imm_op, mul_op, wrapping_mul, *, &
+);
+
+impl Value {
+    fn as_scalar(&self) -> u128 {
+        match self {
+            Value::Bool(val) => *val as u128,
+            Value::Uint8(val) => *val as u128,
+            Value::Uint16(val) => *val as u128,
+            Value::Uint32(val) => *val as u128,
+            Value::Uint64(val) => *val as u128,
+            Value::Uint128(val) => *val,
+            _ => panic!("Immediate operand must be a plaintext value"),
+        }
+    }
 }
 
 impl Value {
     pub fn div_op(self, other: Self) -> Self {
         match (self, other) {
+            (Value::Compressed(a), b) => a.decompress().div_op(b),
+            (a, Value::Compressed(b)) => a.div_op(b.decompress()),
             // Plaintext, Plaintext
             (Value::Bool(_a), Value::Bool(_b)) => unimplemented!(),
             (Value::Bool(a), Value::Uint8(b)) => Value::Uint8((a as u8) / b),
@@ -255,39 +534,87 @@ impl Value {
             (Value::Bool(_a), Value::Euint128(_b)) => unimplemented!(),
 
             (Value::Uint8(_a), Value::Ebool(_b)) => unimplemented!(),
-            (Value::Uint8(_a), Value::Euint8(_b)) => unimplemented!(),
-            (Value::Uint8(_a), Value::Euint16(_b)) => unimplemented!(),
-            (Value::Uint8(_a), Value::Euint32(_b)) => unimplemented!(),
-            (Value::Uint8(_a), Value::Euint64(_b)) => unimplemented!(),
-            (Value::Uint8(_a), Value::Euint128(_b)) => unimplemented!(),
+            (Value::Uint8(a), Value::Euint8(b)) => Value::Euint8(FheUint8::encrypt_trivial(a) / b),
+            (Value::Uint8(a), Value::Euint16(b)) => {
+                Value::Euint16(FheUint16::encrypt_trivial(a) / b)
+            }
+            (Value::Uint8(a), Value::Euint32(b)) => {
+                Value::Euint32(FheUint32::encrypt_trivial(a) / b)
+            }
+            (Value::Uint8(a), Value::Euint64(b)) => {
+                Value::Euint64(FheUint64::encrypt_trivial(a) / b)
+            }
+            (Value::Uint8(a), Value::Euint128(b)) => {
+                Value::Euint128(FheUint128::encrypt_trivial(a) / b)
+            }
 
             (Value::Uint16(_a), Value::Ebool(_b)) => unimplemented!(),
-            (Value::Uint16(_a), Value::Euint8(_b)) => unimplemented!(),
-            (Value::Uint16(_a), Value::Euint16(_b)) => unimplemented!(),
-            (Value::Uint16(_a), Value::Euint32(_b)) => unimplemented!(),
-            (Value::Uint16(_a), Value::Euint64(_b)) => unimplemented!(),
-            (Value::Uint16(_a), Value::Euint128(_b)) => unimplemented!(),
+            (Value::Uint16(a), Value::Euint8(b)) => {
+                Value::Euint16(FheUint16::encrypt_trivial(a) / FheUint16::cast_from(b))
+            }
+            (Value::Uint16(a), Value::Euint16(b)) => {
+                Value::Euint16(FheUint16::encrypt_trivial(a) / b)
+            }
+            (Value::Uint16(a), Value::Euint32(b)) => {
+                Value::Euint32(FheUint32::encrypt_trivial(a) / b)
+            }
+            (Value::Uint16(a), Value::Euint64(b)) => {
+                Value::Euint64(FheUint64::encrypt_trivial(a) / b)
+            }
+            (Value::Uint16(a), Value::Euint128(b)) => {
+                Value::Euint128(FheUint128::encrypt_trivial(a) / b)
+            }
 
             (Value::Uint32(_a), Value::Ebool(_b)) => unimplemented!(),
-            (Value::Uint32(_a), Value::Euint8(_b)) => unimplemented!(),
-            (Value::Uint32(_a), Value::Euint16(_b)) => unimplemented!(),
-            (Value::Uint32(_a), Value::Euint32(_b)) => unimplemented!(),
-            (Value::Uint32(_a), Value::Euint64(_b)) => unimplemented!(),
-            (Value::Uint32(_a), Value::Euint128(_b)) => unimplemented!(),
+            (Value::Uint32(a), Value::Euint8(b)) => {
+                Value::Euint32(FheUint32::encrypt_trivial(a) / FheUint32::cast_from(b))
+            }
+            (Value::Uint32(a), Value::Euint16(b)) => {
+                Value::Euint32(FheUint32::encrypt_trivial(a) / FheUint32::cast_from(b))
+            }
+            (Value::Uint32(a), Value::Euint32(b)) => {
+                Value::Euint32(FheUint32::encrypt_trivial(a) / b)
+            }
+            (Value::Uint32(a), Value::Euint64(b)) => {
+                Value::Euint64(FheUint64::encrypt_trivial(a) / b)
+            }
+            (Value::Uint32(a), Value::Euint128(b)) => {
+                Value::Euint128(FheUint128::encrypt_trivial(a) / b)
+            }
 
             (Value::Uint64(_a), Value::Ebool(_b)) => unimplemented!(),
-            (Value::Uint64(_a), Value::Euint8(_b)) => unimplemented!(),
-            (Value::Uint64(_a), Value::Euint16(_b)) => unimplemented!(),
-            (Value::Uint64(_a), Value::Euint32(_b)) => unimplemented!(),
-            (Value::Uint64(_a), Value::Euint64(_b)) => unimplemented!(),
-            (Value::Uint64(_a), Value::Euint128(_b)) => unimplemented!(),
+            (Value::Uint64(a), Value::Euint8(b)) => {
+                Value::Euint64(FheUint64::encrypt_trivial(a) / FheUint64::cast_from(b))
+            }
+            (Value::Uint64(a), Value::Euint16(b)) => {
+                Value::Euint64(FheUint64::encrypt_trivial(a) / FheUint64::cast_from(b))
+            }
+            (Value::Uint64(a), Value::Euint32(b)) => {
+                Value::Euint64(FheUint64::encrypt_trivial(a) / FheUint64::cast_from(b))
+            }
+            (Value::Uint64(a), Value::Euint64(b)) => {
+                Value::Euint64(FheUint64::encrypt_trivial(a) / b)
+            }
+            (Value::Uint64(a), Value::Euint128(b)) => {
+                Value::Euint128(FheUint128::encrypt_trivial(a) / b)
+            }
 
             (Value::Uint128(_a), Value::Ebool(_b)) => unimplemented!(),
-            (Value::Uint128(_a), Value::Euint8(_b)) => unimplemented!(),
-            (Value::Uint128(_a), Value::Euint16(_b)) => unimplemented!(),
-            (Value::Uint128(_a), Value::Euint32(_b)) => unimplemented!(),
-            (Value::Uint128(_a), Value::Euint64(_b)) => unimplemented!(),
-            (Value::Uint128(_a), Value::Euint128(_b)) => unimplemented!(),
+            (Value::Uint128(a), Value::Euint8(b)) => {
+                Value::Euint128(FheUint128::encrypt_trivial(a) / FheUint128::cast_from(b))
+            }
+            (Value::Uint128(a), Value::Euint16(b)) => {
+                Value::Euint128(FheUint128::encrypt_trivial(a) / FheUint128::cast_from(b))
+            }
+            (Value::Uint128(a), Value::Euint32(b)) => {
+                Value::Euint128(FheUint128::encrypt_trivial(a) / FheUint128::cast_from(b))
+            }
+            (Value::Uint128(a), Value::Euint64(b)) => {
+                Value::Euint128(FheUint128::encrypt_trivial(a) / FheUint128::cast_from(b))
+            }
+            (Value::Uint128(a), Value::Euint128(b)) => {
+                Value::Euint128(FheUint128::encrypt_trivial(a) / b)
+            }
 
             // Encrypted, Plaintext
             (Value::Ebool(_a), Value::Bool(_b)) => unimplemented!(),
@@ -388,6 +715,711 @@ impl Value {
             (Value::Euint128(a), Value::Euint128(b)) => Value::Euint128(a / b),
         }
     }
+
+    // tfhe does not signal division by an encrypted zero; the quotient comes back
+    // with every bit set. Returns that quotient together with a flag that is set
+    // when the divisor was zero. A plaintext zero divisor gives the same quotient
+    // instead of panicking, with the type div_op gives for the operand types.
+    pub fn div_checked_op(self, other: Self) -> (Self, Self) {
+        let divide_by_zero = other.eq_op(&Value::Uint8(0));
+        let quotient = match divide_by_zero {
+            Value::Bool(true) => {
+                let (lhs, rhs) = (self.value_type(), other.value_type());
+                let ty = if lhs.is_encrypted() {
+                    rhs.encrypted()
+                } else if lhs.bits() > rhs.bits() {
+                    lhs
+                } else {
+                    rhs
+                };
+                let max = ty.plain_value(u128::MAX >> (128 - ty.bits())).unwrap();
+                if ty.is_encrypted() {
+                    max.into_trivial()
+                } else {
+                    max
+                }
+            }
+            _ => self.div_op(other),
+        };
+        (quotient, divide_by_zero)
+    }
+
+    // Computes quotient and remainder with a single division, promoting operands
+    // the same way as div_op.
+    pub fn div_rem_op(self, other: Self) -> (Self, Self) {
+        fn pair<T>(wrap: fn(T) -> Value, (q, r): (T, T)) -> (Value, Value) {
+            (wrap(q), wrap(r))
+        }
+
+        match (self, other) {
+            (Value::Compressed(a), b) => a.decompress().div_rem_op(b),
+            (a, Value::Compressed(b)) => a.div_rem_op(b.decompress()),
+            // Plaintext, Plaintext
+            (Value::Uint8(a), Value::Uint8(b)) => pair(Value::Uint8, (a / b, a % b)),
+            (Value::Uint8(a), Value::Uint16(b)) => {
+                pair(Value::Uint16, ((a as u16) / b, (a as u16) % b))
+            }
+            (Value::Uint8(a), Value::Uint32(b)) => {
+                pair(Value::Uint32, ((a as u32) / b, (a as u32) % b))
+            }
+            (Value::Uint8(a), Value::Uint64(b)) => {
+                pair(Value::Uint64, ((a as u64) / b, (a as u64) % b))
+            }
+            (Value::Uint8(a), Value::Uint128(b)) => {
+                pair(Value::Uint128, ((a as u128) / b, (a as u128) % b))
+            }
+
+            (Value::Uint16(a), Value::Uint8(b)) => {
+                pair(Value::Uint16, (a / (b as u16), a % (b as u16)))
+            }
+            (Value::Uint16(a), Value::Uint16(b)) => pair(Value::Uint16, (a / b, a % b)),
+            (Value::Uint16(a), Value::Uint32(b)) => {
+                pair(Value::Uint32, ((a as u32) / b, (a as u32) % b))
+            }
+            (Value::Uint16(a), Value::Uint64(b)) => {
+                pair(Value::Uint64, ((a as u64) / b, (a as u64) % b))
+            }
+            (Value::Uint16(a), Value::Uint128(b)) => {
+                pair(Value::Uint128, ((a as u128) / b, (a as u128) % b))
+            }
+
+            (Value::Uint32(a), Value::Uint8(b)) => {
+                pair(Value::Uint32, (a / (b as u32), a % (b as u32)))
+            }
+            (Value::Uint32(a), Value::Uint16(b)) => {
+                pair(Value::Uint32, (a / (b as u32), a % (b as u32)))
+            }
+            (Value::Uint32(a), Value::Uint32(b)) => pair(Value::Uint32, (a / b, a % b)),
+            (Value::Uint32(a), Value::Uint64(b)) => {
+                pair(Value::Uint64, ((a as u64) / b, (a as u64) % b))
+            }
+            (Value::Uint32(a), Value::Uint128(b)) => {
+                pair(Value::Uint128, ((a as u128) / b, (a as u128) % b))
+            }
+
+            (Value::Uint64(a), Value::Uint8(b)) => {
+                pair(Value::Uint64, (a / (b as u64), a % (b as u64)))
+            }
+            (Value::Uint64(a), Value::Uint16(b)) => {
+                pair(Value::Uint64, (a / (b as u64), a % (b as u64)))
+            }
+            (Value::Uint64(a), Value::Uint32(b)) => {
+                pair(Value::Uint64, (a / (b as u64), a % (b as u64)))
+            }
+            (Value::Uint64(a), Value::Uint64(b)) => pair(Value::Uint64, (a / b, a % b)),
+            (Value::Uint64(a), Value::Uint128(b)) => {
+                pair(Value::Uint128, ((a as u128) / b, (a as u128) % b))
+            }
+
+            (Value::Uint128(a), Value::Uint8(b)) => {
+                pair(Value::Uint128, (a / (b as u128), a % (b as u128)))
+            }
+            (Value::Uint128(a), Value::Uint16(b)) => {
+                pair(Value::Uint128, (a / (b as u128), a % (b as u128)))
+            }
+            (Value::Uint128(a), Value::Uint32(b)) => {
+                pair(Value::Uint128, (a / (b as u128), a % (b as u128)))
+            }
+            (Value::Uint128(a), Value::Uint64(b)) => {
+                pair(Value::Uint128, (a / (b as u128), a % (b as u128)))
+            }
+            (Value::Uint128(a), Value::Uint128(b)) => pair(Value::Uint128, (a / b, a % b)),
+
+            // Plaintext, Encrypted
+            (Value::Uint8(a), Value::Euint8(b)) => {
+                pair(Value::Euint8, FheUint8::encrypt_trivial(a).div_rem(b))
+            }
+            (Value::Uint8(a), Value::Euint16(b)) => {
+                pair(Value::Euint16, FheUint16::encrypt_trivial(a).div_rem(b))
+            }
+            (Value::Uint8(a), Value::Euint32(b)) => {
+                pair(Value::Euint32, FheUint32::encrypt_trivial(a).div_rem(b))
+            }
+            (Value::Uint8(a), Value::Euint64(b)) => {
+                pair(Value::Euint64, FheUint64::encrypt_trivial(a).div_rem(b))
+            }
+            (Value::Uint8(a), Value::Euint128(b)) => {
+                pair(Value::Euint128, FheUint128::encrypt_trivial(a).div_rem(b))
+            }
+
+            (Value::Uint16(a), Value::Euint8(b)) => pair(
+                Value::Euint16,
+                FheUint16::encrypt_trivial(a).div_rem(FheUint16::cast_from(b)),
+            ),
+            (Value::Uint16(a), Value::Euint16(b)) => {
+                pair(Value::Euint16, FheUint16::encrypt_trivial(a).div_rem(b))
+            }
+            (Value::Uint16(a), Value::Euint32(b)) => {
+                pair(Value::Euint32, FheUint32::encrypt_trivial(a).div_rem(b))
+            }
+            (Value::Uint16(a), Value::Euint64(b)) => {
+                pair(Value::Euint64, FheUint64::encrypt_trivial(a).div_rem(b))
+            }
+            (Value::Uint16(a), Value::Euint128(b)) => {
+                pair(Value::Euint128, FheUint128::encrypt_trivial(a).div_rem(b))
+            }
+
+            (Value::Uint32(a), Value::Euint8(b)) => pair(
+                Value::Euint32,
+                FheUint32::encrypt_trivial(a).div_rem(FheUint32::cast_from(b)),
+            ),
+            (Value::Uint32(a), Value::Euint16(b)) => pair(
+                Value::Euint32,
+                FheUint32::encrypt_trivial(a).div_rem(FheUint32::cast_from(b)),
+            ),
+            (Value::Uint32(a), Value::Euint32(b)) => {
+                pair(Value::Euint32, FheUint32::encrypt_trivial(a).div_rem(b))
+            }
+            (Value::Uint32(a), Value::Euint64(b)) => {
+                pair(Value::Euint64, FheUint64::encrypt_trivial(a).div_rem(b))
+            }
+            (Value::Uint32(a), Value::Euint128(b)) => {
+                pair(Value::Euint128, FheUint128::encrypt_trivial(a).div_rem(b))
+            }
+
+            (Value::Uint64(a), Value::Euint8(b)) => pair(
+                Value::Euint64,
+                FheUint64::encrypt_trivial(a).div_rem(FheUint64::cast_from(b)),
+            ),
+            (Value::Uint64(a), Value::Euint16(b)) => pair(
+                Value::Euint64,
+                FheUint64::encrypt_trivial(a).div_rem(FheUint64::cast_from(b)),
+            ),
+            (Value::Uint64(a), Value::Euint32(b)) => pair(
+                Value::Euint64,
+                FheUint64::encrypt_trivial(a).div_rem(FheUint64::cast_from(b)),
+            ),
+            (Value::Uint64(a), Value::Euint64(b)) => {
+                pair(Value::Euint64, FheUint64::encrypt_trivial(a).div_rem(b))
+            }
+            (Value::Uint64(a), Value::Euint128(b)) => {
+                pair(Value::Euint128, FheUint128::encrypt_trivial(a).div_rem(b))
+            }
+
+            (Value::Uint128(a), Value::Euint8(b)) => pair(
+                Value::Euint128,
+                FheUint128::encrypt_trivial(a).div_rem(FheUint128::cast_from(b)),
+            ),
+            (Value::Uint128(a), Value::Euint16(b)) => pair(
+                Value::Euint128,
+                FheUint128::encrypt_trivial(a).div_rem(FheUint128::cast_from(b)),
+            ),
+            (Value::Uint128(a), Value::Euint32(b)) => pair(
+                Value::Euint128,
+                FheUint128::encrypt_trivial(a).div_rem(FheUint128::cast_from(b)),
+            ),
+            (Value::Uint128(a), Value::Euint64(b)) => pair(
+                Value::Euint128,
+                FheUint128::encrypt_trivial(a).div_rem(FheUint128::cast_from(b)),
+            ),
+            (Value::Uint128(a), Value::Euint128(b)) => {
+                pair(Value::Euint128, FheUint128::encrypt_trivial(a).div_rem(b))
+            }
+
+            // Encrypted, Plaintext
+            (Value::Euint8(a), Value::Uint8(b)) => pair(Value::Euint8, a.div_rem(b)),
+            (Value::Euint16(a), Value::Uint8(b)) => {
+                pair(Value::Euint8, FheUint8::cast_from(a).div_rem(b))
+            }
+            (Value::Euint32(a), Value::Uint8(b)) => {
+                pair(Value::Euint8, FheUint8::cast_from(a).div_rem(b))
+            }
+            (Value::Euint64(a), Value::Uint8(b)) => {
+                pair(Value::Euint8, FheUint8::cast_from(a).div_rem(b))
+            }
+            (Value::Euint128(a), Value::Uint8(b)) => {
+                pair(Value::Euint8, FheUint8::cast_from(a).div_rem(b))
+            }
+
+            (Value::Euint8(a), Value::Uint16(b)) => {
+                pair(Value::Euint16, FheUint16::cast_from(a).div_rem(b))
+            }
+            (Value::Euint16(a), Value::Uint16(b)) => pair(Value::Euint16, a.div_rem(b)),
+            (Value::Euint32(a), Value::Uint16(b)) => {
+                pair(Value::Euint16, FheUint16::cast_from(a).div_rem(b))
+            }
+            (Value::Euint64(a), Value::Uint16(b)) => {
+                pair(Value::Euint16, FheUint16::cast_from(a).div_rem(b))
+            }
+            (Value::Euint128(a), Value::Uint16(b)) => {
+                pair(Value::Euint16, FheUint16::cast_from(a).div_rem(b))
+            }
+
+            (Value::Euint8(a), Value::Uint32(b)) => {
+                pair(Value::Euint32, FheUint32::cast_from(a).div_rem(b))
+            }
+            (Value::Euint16(a), Value::Uint32(b)) => {
+                pair(Value::Euint32, FheUint32::cast_from(a).div_rem(b))
+            }
+            (Value::Euint32(a), Value::Uint32(b)) => pair(Value::Euint32, a.div_rem(b)),
+            (Value::Euint64(a), Value::Uint32(b)) => {
+                pair(Value::Euint32, FheUint32::cast_from(a).div_rem(b))
+            }
+            (Value::Euint128(a), Value::Uint32(b)) => {
+                pair(Value::Euint32, FheUint32::cast_from(a).div_rem(b))
+            }
+
+            (Value::Euint8(a), Value::Uint64(b)) => {
+                pair(Value::Euint64, FheUint64::cast_from(a).div_rem(b))
+            }
+            (Value::Euint16(a), Value::Uint64(b)) => {
+                pair(Value::Euint64, FheUint64::cast_from(a).div_rem(b))
+            }
+            (Value::Euint32(a), Value::Uint64(b)) => {
+                pair(Value::Euint64, FheUint64::cast_from(a).div_rem(b))
+            }
+            (Value::Euint64(a), Value::Uint64(b)) => pair(Value::Euint64, a.div_rem(b)),
+            (Value::Euint128(a), Value::Uint64(b)) => {
+                pair(Value::Euint64, FheUint64::cast_from(a).div_rem(b))
+            }
+
+            (Value::Euint8(a), Value::Uint128(b)) => {
+                pair(Value::Euint128, FheUint128::cast_from(a).div_rem(b))
+            }
+            (Value::Euint16(a), Value::Uint128(b)) => {
+                pair(Value::Euint128, FheUint128::cast_from(a).div_rem(b))
+            }
+            (Value::Euint32(a), Value::Uint128(b)) => {
+                pair(Value::Euint128, FheUint128::cast_from(a).div_rem(b))
+            }
+            (Value::Euint64(a), Value::Uint128(b)) => {
+                pair(Value::Euint128, FheUint128::cast_from(a).div_rem(b))
+            }
+            (Value::Euint128(a), Value::Uint128(b)) => pair(Value::Euint128, a.div_rem(b)),
+
+            // Encrypted, Encrypted
+            (Value::Euint8(a), Value::Euint8(b)) => pair(Value::Euint8, a.div_rem(b)),
+            (Value::Euint8(a), Value::Euint16(b)) => {
+                pair(Value::Euint16, FheUint16::cast_from(a).div_rem(b))
+            }
+            (Value::Euint8(a), Value::Euint32(b)) => {
+                pair(Value::Euint32, FheUint32::cast_from(a).div_rem(b))
+            }
+            (Value::Euint8(a), Value::Euint64(b)) => {
+                pair(Value::Euint64, FheUint64::cast_from(a).div_rem(b))
+            }
+            (Value::Euint8(a), Value::Euint128(b)) => {
+                pair(Value::Euint128, FheUint128::cast_from(a).div_rem(b))
+            }
+
+            (Value::Euint16(a), Value::Euint8(b)) => {
+                pair(Value::Euint16, a.div_rem(FheUint16::cast_from(b)))
+            }
+            (Value::Euint16(a), Value::Euint16(b)) => pair(Value::Euint16, a.div_rem(b)),
+            (Value::Euint16(a), Value::Euint32(b)) => {
+                pair(Value::Euint32, FheUint32::cast_from(a).div_rem(b))
+            }
+            (Value::Euint16(a), Value::Euint64(b)) => {
+                pair(Value::Euint64, FheUint64::cast_from(a).div_rem(b))
+            }
+            (Value::Euint16(a), Value::Euint128(b)) => {
+                pair(Value::Euint128, FheUint128::cast_from(a).div_rem(b))
+            }
+
+            (Value::Euint32(a), Value::Euint8(b)) => {
+                pair(Value::Euint32, a.div_rem(FheUint32::cast_from(b)))
+            }
+            (Value::Euint32(a), Value::Euint16(b)) => {
+                pair(Value::Euint32, a.div_rem(FheUint32::cast_from(b)))
+            }
+            (Value::Euint32(a), Value::Euint32(b)) => pair(Value::Euint32, a.div_rem(b)),
+            (Value::Euint32(a), Value::Euint64(b)) => {
+                pair(Value::Euint64, FheUint64::cast_from(a).div_rem(b))
+            }
+            (Value::Euint32(a), Value::Euint128(b)) => {
+                pair(Value::Euint128, FheUint128::cast_from(a).div_rem(b))
+            }
+
+            (Value::Euint64(a), Value::Euint8(b)) => {
+                pair(Value::Euint64, a.div_rem(FheUint64::cast_from(b)))
+            }
+            (Value::Euint64(a), Value::Euint16(b)) => {
+                pair(Value::Euint64, a.div_rem(FheUint64::cast_from(b)))
+            }
+            (Value::Euint64(a), Value::Euint32(b)) => {
+                pair(Value::Euint64, a.div_rem(FheUint64::cast_from(b)))
+            }
+            (Value::Euint64(a), Value::Euint64(b)) => pair(Value::Euint64, a.div_rem(b)),
+            (Value::Euint64(a), Value::Euint128(b)) => {
+                pair(Value::Euint128, FheUint128::cast_from(a).div_rem(b))
+            }
+
+            (Value::Euint128(a), Value::Euint8(b)) => {
+                pair(Value::Euint128, a.div_rem(FheUint128::cast_from(b)))
+            }
+            (Value::Euint128(a), Value::Euint16(b)) => {
+                pair(Value::Euint128, a.div_rem(FheUint128::cast_from(b)))
+            }
+            (Value::Euint128(a), Value::Euint32(b)) => {
+                pair(Value::Euint128, a.div_rem(FheUint128::cast_from(b)))
+            }
+            (Value::Euint128(a), Value::Euint64(b)) => {
+                pair(Value::Euint128, a.div_rem(FheUint128::cast_from(b)))
+            }
+            (Value::Euint128(a), Value::Euint128(b)) => pair(Value::Euint128, a.div_rem(b)),
+
+            _ => unimplemented!(),
+        }
+    }
+
+    // Converts a plaintext into a trivial ciphertext of the same width
+    pub fn into_trivial(self) -> Self {
+        match self {
+            Value::Bool(val) => Value::Ebool(FheBool::encrypt_trivial(val)),
+            Value::Uint8(val) => Value::Euint8(FheUint8::encrypt_trivial(val)),
+            Value::Uint16(val) => Value::Euint16(FheUint16::encrypt_trivial(val)),
+            Value::Uint32(val) => Value::Euint32(FheUint32::encrypt_trivial(val)),
+            Value::Uint64(val) => Value::Euint64(FheUint64::encrypt_trivial(val)),
+            Value::Uint128(val) => Value::Euint128(FheUint128::encrypt_trivial(val)),
+            encrypted => encrypted.decompress(),
+        }
+    }
+
+    // Casts an encrypted integer or boolean to the encrypted integer `bits`
+    // wide. Values that already have that width are returned as they are.
+    pub fn cast_op(self, bits: u32) -> Self {
+        let value = self.decompress();
+        macro_rules! cast {
+            ($type:ident) => {
+                match value {
+                    Value::Ebool(val) => $type::cast_from(val),
+                    Value::Euint8(val) => $type::cast_from(val),
+                    Value::Euint16(val) => $type::cast_from(val),
+                    Value::Euint32(val) => $type::cast_from(val),
+                    Value::Euint64(val) => $type::cast_from(val),
+                    Value::Euint128(val) => $type::cast_from(val),
+                    _ => unimplemented!(),
+                }
+            };
+        }
+
+        if value.value_type().bits() == bits {
+            return value;
+        }
+        match bits {
+            8 => Value::Euint8(cast!(FheUint8)),
+            16 => Value::Euint16(cast!(FheUint16)),
+            32 => Value::Euint32(cast!(FheUint32)),
+            64 => Value::Euint64(cast!(FheUint64)),
+            128 => Value::Euint128(cast!(FheUint128)),
+            _ => panic!("Unsupported width {}", bits),
+        }
+    }
+}
+
+impl Value {
+    // Brings two operands to the same type: the wider of their widths, and a
+    // ciphertext if either one is encrypted. Booleans count as 0 or 1 and
+    // widen to at least 8 bits.
+    pub fn promote(self, other: Self) -> (Self, Self) {
+        let (lhs, rhs) = (self.value_type(), other.value_type());
+        let bits = lhs.bits().max(rhs.bits()).max(8);
+        let encrypted = lhs.is_encrypted() || rhs.is_encrypted();
+        (self.widen(bits, encrypted), other.widen(bits, encrypted))
+    }
+
+    fn widen(self, bits: u32, encrypted: bool) -> Self {
+        if self.value_type().is_encrypted() {
+            return self.cast_op(bits);
+        }
+        let val = ValueType::uint(bits).plain_value(self.as_scalar()).unwrap();
+        if encrypted {
+            val.into_trivial()
+        } else {
+            val
+        }
+    }
+}
+
+// Rotations keep the width of the rotated operand; the amount may be any
+// width and is taken modulo the bit width.
+macro_rules! rotate_op {
+    ($($op:ident, $method:ident);*) => {
+        impl Value {
+            $(
+                pub fn $op(self, other: Self) -> Self {
+                    match (self, other) {
+                        (Value::Compressed(a), b) => a.decompress().$op(b),
+                        (a, Value::Compressed(b)) => a.$op(b.decompress()),
+                        (Value::Uint8(a), Value::Uint8(b)) => Value::Uint8(a.$method(b as u32)),
+                        (Value::Uint8(a), Value::Uint16(b)) => Value::Uint8(a.$method(b as u32)),
+                        (Value::Uint8(a), Value::Uint32(b)) => Value::Uint8(a.$method(b as u32)),
+                        (Value::Uint8(a), Value::Uint64(b)) => Value::Uint8(a.$method(b as u32)),
+                        (Value::Uint8(a), Value::Uint128(b)) => Value::Uint8(a.$method(b as u32)),
+
+                        (Value::Uint16(a), Value::Uint8(b)) => Value::Uint16(a.$method(b as u32)),
+                        (Value::Uint16(a), Value::Uint16(b)) => Value::Uint16(a.$method(b as u32)),
+                        (Value::Uint16(a), Value::Uint32(b)) => Value::Uint16(a.$method(b as u32)),
+                        (Value::Uint16(a), Value::Uint64(b)) => Value::Uint16(a.$method(b as u32)),
+                        (Value::Uint16(a), Value::Uint128(b)) => Value::Uint16(a.$method(b as u32)),
+
+                        (Value::Uint32(a), Value::Uint8(b)) => Value::Uint32(a.$method(b as u32)),
+                        (Value::Uint32(a), Value::Uint16(b)) => Value::Uint32(a.$method(b as u32)),
+                        (Value::Uint32(a), Value::Uint32(b)) => Value::Uint32(a.$method(b as u32)),
+                        (Value::Uint32(a), Value::Uint64(b)) => Value::Uint32(a.$method(b as u32)),
+                        (Value::Uint32(a), Value::Uint128(b)) => Value::Uint32(a.$method(b as u32)),
+
+                        (Value::Uint64(a), Value::Uint8(b)) => Value::Uint64(a.$method(b as u32)),
+                        (Value::Uint64(a), Value::Uint16(b)) => Value::Uint64(a.$method(b as u32)),
+                        (Value::Uint64(a), Value::Uint32(b)) => Value::Uint64(a.$method(b as u32)),
+                        (Value::Uint64(a), Value::Uint64(b)) => Value::Uint64(a.$method(b as u32)),
+                        (Value::Uint64(a), Value::Uint128(b)) => Value::Uint64(a.$method(b as u32)),
+
+                        (Value::Uint128(a), Value::Uint8(b)) => Value::Uint128(a.$method(b as u32)),
+                        (Value::Uint128(a), Value::Uint16(b)) => Value::Uint128(a.$method(b as u32)),
+                        (Value::Uint128(a), Value::Uint32(b)) => Value::Uint128(a.$method(b as u32)),
+                        (Value::Uint128(a), Value::Uint64(b)) => Value::Uint128(a.$method(b as u32)),
+                        (Value::Uint128(a), Value::Uint128(b)) => Value::Uint128(a.$method(b as u32)),
+
+                        (Value::Uint8(a), Value::Euint8(b)) => Value::Euint8(FheUint8::encrypt_trivial(a).$method(b)),
+                        (Value::Uint8(a), Value::Euint16(b)) => Value::Euint8(FheUint8::encrypt_trivial(a).$method(FheUint8::cast_from(b))),
+                        (Value::Uint8(a), Value::Euint32(b)) => Value::Euint8(FheUint8::encrypt_trivial(a).$method(FheUint8::cast_from(b))),
+                        (Value::Uint8(a), Value::Euint64(b)) => Value::Euint8(FheUint8::encrypt_trivial(a).$method(FheUint8::cast_from(b))),
+                        (Value::Uint8(a), Value::Euint128(b)) => Value::Euint8(FheUint8::encrypt_trivial(a).$method(FheUint8::cast_from(b))),
+
+                        (Value::Uint16(a), Value::Euint8(b)) => Value::Euint16(FheUint16::encrypt_trivial(a).$method(b)),
+                        (Value::Uint16(a), Value::Euint16(b)) => Value::Euint16(FheUint16::encrypt_trivial(a).$method(b)),
+                        (Value::Uint16(a), Value::Euint32(b)) => Value::Euint16(FheUint16::encrypt_trivial(a).$method(FheUint16::cast_from(b))),
+                        (Value::Uint16(a), Value::Euint64(b)) => Value::Euint16(FheUint16::encrypt_trivial(a).$method(FheUint16::cast_from(b))),
+                        (Value::Uint16(a), Value::Euint128(b)) => Value::Euint16(FheUint16::encrypt_trivial(a).$method(FheUint16::cast_from(b))),
+
+                        (Value::Uint32(a), Value::Euint8(b)) => Value::Euint32(FheUint32::encrypt_trivial(a).$method(b)),
+                        (Value::Uint32(a), Value::Euint16(b)) => Value::Euint32(FheUint32::encrypt_trivial(a).$method(b)),
+                        (Value::Uint32(a), Value::Euint32(b)) => Value::Euint32(FheUint32::encrypt_trivial(a).$method(b)),
+                        (Value::Uint32(a), Value::Euint64(b)) => Value::Euint32(FheUint32::encrypt_trivial(a).$method(FheUint32::cast_from(b))),
+                        (Value::Uint32(a), Value::Euint128(b)) => Value::Euint32(FheUint32::encrypt_trivial(a).$method(FheUint32::cast_from(b))),
+
+                        (Value::Uint64(a), Value::Euint8(b)) => Value::Euint64(FheUint64::encrypt_trivial(a).$method(b)),
+                        (Value::Uint64(a), Value::Euint16(b)) => Value::Euint64(FheUint64::encrypt_trivial(a).$method(b)),
+                        (Value::Uint64(a), Value::Euint32(b)) => Value::Euint64(FheUint64::encrypt_trivial(a).$method(b)),
+                        (Value::Uint64(a), Value::Euint64(b)) => Value::Euint64(FheUint64::encrypt_trivial(a).$method(b)),
+                        (Value::Uint64(a), Value::Euint128(b)) => Value::Euint64(FheUint64::encrypt_trivial(a).$method(FheUint64::cast_from(b))),
+
+                        (Value::Uint128(a), Value::Euint8(b)) => Value::Euint128(FheUint128::encrypt_trivial(a).$method(b)),
+                        (Value::Uint128(a), Value::Euint16(b)) => Value::Euint128(FheUint128::encrypt_trivial(a).$method(b)),
+                        (Value::Uint128(a), Value::Euint32(b)) => Value::Euint128(FheUint128::encrypt_trivial(a).$method(b)),
+                        (Value::Uint128(a), Value::Euint64(b)) => Value::Euint128(FheUint128::encrypt_trivial(a).$method(b)),
+                        (Value::Uint128(a), Value::Euint128(b)) => Value::Euint128(FheUint128::encrypt_trivial(a).$method(b)),
+
+                        (Value::Euint8(a), Value::Uint8(b)) => Value::Euint8(a.$method(b)),
+                        (Value::Euint8(a), Value::Uint16(b)) => Value::Euint8(a.$method(b)),
+                        (Value::Euint8(a), Value::Uint32(b)) => Value::Euint8(a.$method(b)),
+                        (Value::Euint8(a), Value::Uint64(b)) => Value::Euint8(a.$method(b)),
+                        (Value::Euint8(a), Value::Uint128(b)) => Value::Euint8(a.$method(b)),
+
+                        (Value::Euint16(a), Value::Uint8(b)) => Value::Euint16(a.$method(b)),
+                        (Value::Euint16(a), Value::Uint16(b)) => Value::Euint16(a.$method(b)),
+                        (Value::Euint16(a), Value::Uint32(b)) => Value::Euint16(a.$method(b)),
+                        (Value::Euint16(a), Value::Uint64(b)) => Value::Euint16(a.$method(b)),
+                        (Value::Euint16(a), Value::Uint128(b)) => Value::Euint16(a.$method(b)),
+
+                        (Value::Euint32(a), Value::Uint8(b)) => Value::Euint32(a.$method(b)),
+                        (Value::Euint32(a), Value::Uint16(b)) => Value::Euint32(a.$method(b)),
+                        (Value::Euint32(a), Value::Uint32(b)) => Value::Euint32(a.$method(b)),
+                        (Value::Euint32(a), Value::Uint64(b)) => Value::Euint32(a.$method(b)),
+                        (Value::Euint32(a), Value::Uint128(b)) => Value::Euint32(a.$method(b)),
+
+                        (Value::Euint64(a), Value::Uint8(b)) => Value::Euint64(a.$method(b)),
+                        (Value::Euint64(a), Value::Uint16(b)) => Value::Euint64(a.$method(b)),
+                        (Value::Euint64(a), Value::Uint32(b)) => Value::Euint64(a.$method(b)),
+                        (Value::Euint64(a), Value::Uint64(b)) => Value::Euint64(a.$method(b)),
+                        (Value::Euint64(a), Value::Uint128(b)) => Value::Euint64(a.$method(b)),
+
+                        (Value::Euint128(a), Value::Uint8(b)) => Value::Euint128(a.$method(b)),
+                        (Value::Euint128(a), Value::Uint16(b)) => Value::Euint128(a.$method(b)),
+                        (Value::Euint128(a), Value::Uint32(b)) => Value::Euint128(a.$method(b)),
+                        (Value::Euint128(a), Value::Uint64(b)) => Value::Euint128(a.$method(b)),
+                        (Value::Euint128(a), Value::Uint128(b)) => Value::Euint128(a.$method(b)),
+
+                        (Value::Euint8(a), Value::Euint8(b)) => Value::Euint8(a.$method(b)),
+                        (Value::Euint8(a), Value::Euint16(b)) => Value::Euint8(a.$method(FheUint8::cast_from(b))),
+                        (Value::Euint8(a), Value::Euint32(b)) => Value::Euint8(a.$method(FheUint8::cast_from(b))),
+                        (Value::Euint8(a), Value::Euint64(b)) => Value::Euint8(a.$method(FheUint8::cast_from(b))),
+                        (Value::Euint8(a), Value::Euint128(b)) => Value::Euint8(a.$method(FheUint8::cast_from(b))),
+
+                        (Value::Euint16(a), Value::Euint8(b)) => Value::Euint16(a.$method(b)),
+                        (Value::Euint16(a), Value::Euint16(b)) => Value::Euint16(a.$method(b)),
+                        (Value::Euint16(a), Value::Euint32(b)) => Value::Euint16(a.$method(FheUint16::cast_from(b))),
+                        (Value::Euint16(a), Value::Euint64(b)) => Value::Euint16(a.$method(FheUint16::cast_from(b))),
+                        (Value::Euint16(a), Value::Euint128(b)) => Value::Euint16(a.$method(FheUint16::cast_from(b))),
+
+                        (Value::Euint32(a), Value::Euint8(b)) => Value::Euint32(a.$method(b)),
+                        (Value::Euint32(a), Value::Euint16(b)) => Value::Euint32(a.$method(b)),
+                        (Value::Euint32(a), Value::Euint32(b)) => Value::Euint32(a.$method(b)),
+                        (Value::Euint32(a), Value::Euint64(b)) => Value::Euint32(a.$method(FheUint32::cast_from(b))),
+                        (Value::Euint32(a), Value::Euint128(b)) => Value::Euint32(a.$method(FheUint32::cast_from(b))),
+
+                        (Value::Euint64(a), Value::Euint8(b)) => Value::Euint64(a.$method(b)),
+                        (Value::Euint64(a), Value::Euint16(b)) => Value::Euint64(a.$method(b)),
+                        (Value::Euint64(a), Value::Euint32(b)) => Value::Euint64(a.$method(b)),
+                        (Value::Euint64(a), Value::Euint64(b)) => Value::Euint64(a.$method(b)),
+                        (Value::Euint64(a), Value::Euint128(b)) => Value::Euint64(a.$method(FheUint64::cast_from(b))),
+
+                        (Value::Euint128(a), Value::Euint8(b)) => Value::Euint128(a.$method(b)),
+                        (Value::Euint128(a), Value::Euint16(b)) => Value::Euint128(a.$method(b)),
+                        (Value::Euint128(a), Value::Euint32(b)) => Value::Euint128(a.$method(b)),
+                        (Value::Euint128(a), Value::Euint64(b)) => Value::Euint128(a.$method(b)),
+                        (Value::Euint128(a), Value::Euint128(b)) => Value::Euint128(a.$method(b)),
+
+                        _ => unimplemented!(),
+                    }
+                }
+            )*
+        }
+    };
+}
+
+rotate_op! {
+    rotate_left_op, rotate_left;
+    rotate_right_op, rotate_right
+}
+
+// Bit counts are returned as 32-bit integers for every input width, matching
+// both tfhe and the std integer methods.
+macro_rules! bit_count_op {
+    ($($op:ident, $method:ident);*) => {
+        impl Value {
+            $(
+                pub fn $op(self) -> Self {
+                    match self {
+                        Value::Euint8(val) => Value::Euint32(val.$method()),
+                        Value::Euint16(val) => Value::Euint32(val.$method()),
+                        Value::Euint32(val) => Value::Euint32(val.$method()),
+                        Value::Euint64(val) => Value::Euint32(val.$method()),
+                        Value::Euint128(val) => Value::Euint32(val.$method()),
+                        Value::Uint8(val) => Value::Uint32(val.$method()),
+                        Value::Uint16(val) => Value::Uint32(val.$method()),
+                        Value::Uint32(val) => Value::Uint32(val.$method()),
+                        Value::Uint64(val) => Value::Uint32(val.$method()),
+                        Value::Uint128(val) => Value::Uint32(val.$method()),
+                        Value::Compressed(val) => val.decompress().$op(),
+                        _ => unimplemented!(),
+                    }
+                }
+            )*
+        }
+    };
+}
+
+bit_count_op! {
+    count_ones_op, count_ones;
+    leading_zeros_op, leading_zeros;
+    trailing_zeros_op, trailing_zeros
+}
+
+// Only the nonzero entries are matched; every other input maps to zero
+fn lookup(byte: &FheUint8, table: &Table) -> FheUint8 {
+    let matches: Vec<(u8, u8)> = (0..=u8::MAX)
+        .zip(table.iter().copied())
+        .filter(|(_, output)| *output != 0)
+        .collect();
+    if matches.is_empty() {
+        return FheUint8::encrypt_trivial(0u8);
+    }
+    let matches = MatchValues::new(matches).unwrap();
+    byte.match_value_or(&matches, 0).unwrap()
+}
+
+// Splits an encrypted integer into bytes, looks each one up and reassembles
+// the results
+macro_rules! lut_bytes {
+    ($val:expr, $type:ident, $bytes:expr, $table:expr) => {{
+        let mut result = $type::cast_from(lookup(&FheUint8::cast_from($val.clone()), $table));
+        for index in 1..$bytes {
+            let byte = FheUint8::cast_from(&$val >> (8 * index) as u32);
+            let output = $type::cast_from(lookup(&byte, $table));
+            result |= output << (8 * index) as u32;
+        }
+        result
+    }};
+}
+
+impl Value {
+    // The logarithm of zero is unspecified: tfhe returns an arbitrary value and
+    // plaintext zero yields 0.
+    pub fn ilog2_op(self) -> Self {
+        match self {
+            Value::Euint8(val) => Value::Euint32(val.ilog2()),
+            Value::Euint16(val) => Value::Euint32(val.ilog2()),
+            Value::Euint32(val) => Value::Euint32(val.ilog2()),
+            Value::Euint64(val) => Value::Euint32(val.ilog2()),
+            Value::Euint128(val) => Value::Euint32(val.ilog2()),
+            Value::Uint8(val) => Value::Uint32(val.checked_ilog2().unwrap_or(0)),
+            Value::Uint16(val) => Value::Uint32(val.checked_ilog2().unwrap_or(0)),
+            Value::Uint32(val) => Value::Uint32(val.checked_ilog2().unwrap_or(0)),
+            Value::Uint64(val) => Value::Uint32(val.checked_ilog2().unwrap_or(0)),
+            Value::Uint128(val) => Value::Uint32(val.checked_ilog2().unwrap_or(0)),
+            Value::Compressed(val) => val.decompress().ilog2_op(),
+            _ => unimplemented!(),
+        }
+    }
+
+    // Converts a boolean to 0 or 1 of the given bit width, e.g. to sum the
+    // results of comparisons. An integer is first tested for nonzero, so it
+    // also becomes 0 or 1. Panics on widths other than 8, 16, 32, 64 and 128.
+    pub fn bool_to_int_op(self, width: u32) -> Self {
+        match (self, width) {
+            (Value::Ebool(val), 8) => Value::Euint8(FheUint8::cast_from(val)),
+            (Value::Ebool(val), 16) => Value::Euint16(FheUint16::cast_from(val)),
+            (Value::Ebool(val), 32) => Value::Euint32(FheUint32::cast_from(val)),
+            (Value::Ebool(val), 64) => Value::Euint64(FheUint64::cast_from(val)),
+            (Value::Ebool(val), 128) => Value::Euint128(FheUint128::cast_from(val)),
+            (Value::Bool(val), 8) => Value::Uint8(val as u8),
+            (Value::Bool(val), 16) => Value::Uint16(val as u16),
+            (Value::Bool(val), 32) => Value::Uint32(val as u32),
+            (Value::Bool(val), 64) => Value::Uint64(val as u64),
+            (Value::Bool(val), 128) => Value::Uint128(val as u128),
+            (Value::Compressed(val), _) => val.decompress().bool_to_int_op(width),
+            (val, 8 | 16 | 32 | 64 | 128) => val.int_to_bool_op().bool_to_int_op(width),
+            (_, width) => panic!("Unsupported width {}", width),
+        }
+    }
+
+    // Nonzero test, the inverse of bool_to_int_op. Booleans pass through.
+    pub fn int_to_bool_op(self) -> Self {
+        match self {
+            Value::Ebool(_) | Value::Bool(_) => self,
+            Value::Compressed(val) => val.decompress().int_to_bool_op(),
+            Value::Euint8(val) => Value::Ebool(val.ne(0)),
+            Value::Euint16(val) => Value::Ebool(val.ne(0)),
+            Value::Euint32(val) => Value::Ebool(val.ne(0)),
+            Value::Euint64(val) => Value::Ebool(val.ne(0)),
+            Value::Euint128(val) => Value::Ebool(val.ne(0)),
+            Value::Uint8(val) => Value::Bool(val != 0),
+            Value::Uint16(val) => Value::Bool(val != 0),
+            Value::Uint32(val) => Value::Bool(val != 0),
+            Value::Uint64(val) => Value::Bool(val != 0),
+            Value::Uint128(val) => Value::Bool(val != 0),
+        }
+    }
+
+    // Applies the table to Euint8 values directly and to wider integers one
+    // byte at a time, like an S-box. Each encrypted byte is one tfhe
+    // match_value_or, which evaluates the table with programmable bootstraps.
+    pub fn lut_op(self, table: &Table) -> Self {
+        match self {
+            Value::Euint8(val) => Value::Euint8(lookup(&val, table)),
+            Value::Euint16(val) => Value::Euint16(lut_bytes!(val, FheUint16, 2, table)),
+            Value::Euint32(val) => Value::Euint32(lut_bytes!(val, FheUint32, 4, table)),
+            Value::Euint64(val) => Value::Euint64(lut_bytes!(val, FheUint64, 8, table)),
+            Value::Euint128(val) => Value::Euint128(lut_bytes!(val, FheUint128, 16, table)),
+            Value::Uint8(val) => Value::Uint8(table[val as usize]),
+            Value::Uint16(val) => Value::Uint16(u16::from_le_bytes(
+                val.to_le_bytes().map(|byte| table[byte as usize]),
+            )),
+            Value::Uint32(val) => Value::Uint32(u32::from_le_bytes(
+                val.to_le_bytes().map(|byte| table[byte as usize]),
+            )),
+            Value::Uint64(val) => Value::Uint64(u64::from_le_bytes(
+                val.to_le_bytes().map(|byte| table[byte as usize]),
+            )),
+            Value::Uint128(val) => Value::Uint128(u128::from_le_bytes(
+                val.to_le_bytes().map(|byte| table[byte as usize]),
+            )),
+            Value::Compressed(val) => val.decompress().lut_op(table),
+            _ => unimplemented!(),
+        }
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -405,104 +1437,532 @@ pub enum Value {
     Uint32(u32),
     Uint64(u64),
     Uint128(u128),
+
+    Compressed(Box<CompressedValue>), // Decompressed when the VM pops it
 }
 
-impl FheEq<Value> for Value {
-    fn eq(&self, other: Self) -> FheBool {
-        match (self, other) {
-            (Value::Ebool(a), Value::Ebool(b)) => a.eq(b),
-            (Value::Euint8(a), Value::Euint8(b)) => a.eq(b),
-            (Value::Euint16(a), Value::Euint16(b)) => a.eq(b),
-            (Value::Euint32(a), Value::Euint32(b)) => a.eq(b),
-            (Value::Euint64(a), Value::Euint64(b)) => a.eq(b),
-            (Value::Euint128(a), Value::Euint128(b)) => a.eq(b),
-            _ => unimplemented!(),
+// Seeded ciphertexts as produced by encrypting straight into tfhe's compressed
+// types. They are much smaller on disk and on the wire, but have to be
+// decompressed before any operation.
+#[derive(Clone, Serialize, Deserialize)]
+pub enum CompressedValue {
+    Ebool(CompressedFheBool),
+    Euint8(CompressedFheUint8),
+    Euint16(CompressedFheUint16),
+    Euint32(CompressedFheUint32),
+    Euint64(CompressedFheUint64),
+    Euint128(CompressedFheUint128),
+}
+
+impl CompressedValue {
+    pub fn value_type(&self) -> ValueType {
+        match self {
+            CompressedValue::Ebool(_) => ValueType::Ebool,
+            CompressedValue::Euint8(_) => ValueType::Euint8,
+            CompressedValue::Euint16(_) => ValueType::Euint16,
+            CompressedValue::Euint32(_) => ValueType::Euint32,
+            CompressedValue::Euint64(_) => ValueType::Euint64,
+            CompressedValue::Euint128(_) => ValueType::Euint128,
         }
     }
 
-    fn ne(&self, other: Value) -> FheBool {
-        match (self, other) {
-            (Value::Ebool(a), Value::Ebool(b)) => a.ne(b),
-            (Value::Euint8(a), Value::Euint8(b)) => a.ne(b),
-            (Value::Euint16(a), Value::Euint16(b)) => a.ne(b),
-            (Value::Euint32(a), Value::Euint32(b)) => a.ne(b),
-            (Value::Euint64(a), Value::Euint64(b)) => a.ne(b),
-            (Value::Euint128(a), Value::Euint128(b)) => a.ne(b),
-            _ => unimplemented!(),
+    pub fn decompress(&self) -> Value {
+        match self {
+            CompressedValue::Ebool(val) => Value::Ebool(val.decompress()),
+            CompressedValue::Euint8(val) => Value::Euint8(val.decompress()),
+            CompressedValue::Euint16(val) => Value::Euint16(val.decompress()),
+            CompressedValue::Euint32(val) => Value::Euint32(val.decompress()),
+            CompressedValue::Euint64(val) => Value::Euint64(val.decompress()),
+            CompressedValue::Euint128(val) => Value::Euint128(val.decompress()),
         }
     }
 }
 
-impl FheMax<Value> for Value {
-    type Output = Value;
-    fn max(&self, other: Value) -> Value {
-        match (self, other) {
-            (Value::Euint8(a), Value::Euint8(b)) => Value::Euint8(a.max(&b)),
-            (Value::Euint16(a), Value::Euint16(b)) => Value::Euint16(a.max(&b)),
-            (Value::Euint32(a), Value::Euint32(b)) => Value::Euint32(a.max(&b)),
-            (Value::Euint64(a), Value::Euint64(b)) => Value::Euint64(a.max(&b)),
-            (Value::Euint128(a), Value::Euint128(b)) => Value::Euint128(a.max(&b)),
-            _ => unimplemented!(),
+impl Value {
+    // Returns the value unchanged unless it is compressed
+    pub fn decompress(self) -> Self {
+        match self {
+            Value::Compressed(val) => val.decompress(),
+            val => val,
         }
     }
+
+    // Compressed copy of a ciphertext, for writing out. Plaintexts and values
+    // that are already compressed are copied as they are.
+    pub fn compress(&self) -> Self {
+        let val = match self {
+            Value::Ebool(val) => CompressedValue::Ebool(val.compress()),
+            Value::Euint8(val) => CompressedValue::Euint8(val.compress()),
+            Value::Euint16(val) => CompressedValue::Euint16(val.compress()),
+            Value::Euint32(val) => CompressedValue::Euint32(val.compress()),
+            Value::Euint64(val) => CompressedValue::Euint64(val.compress()),
+            Value::Euint128(val) => CompressedValue::Euint128(val.compress()),
+            val => return val.clone(),
+        };
+        Value::Compressed(Box::new(val))
+    }
 }
 
-impl FheMin<Value> for Value {
-    type Output = Value;
-    fn min(&self, other: Value) -> Value {
-        match (self, other) {
-            (Value::Euint8(a), Value::Euint8(b)) => Value::Euint8(a.min(&b)),
-            (Value::Euint16(a), Value::Euint16(b)) => Value::Euint16(a.min(&b)),
-            (Value::Euint32(a), Value::Euint32(b)) => Value::Euint32(a.min(&b)),
-            (Value::Euint64(a), Value::Euint64(b)) => Value::Euint64(a.min(&b)),
-            (Value::Euint128(a), Value::Euint128(b)) => Value::Euint128(a.min(&b)),
-            _ => unimplemented!(),
+impl std::fmt::Debug for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Compressed(val) => write!(f, "Compressed{:?}", val.value_type()),
+            Value::Ebool(_) => write!(f, "Ebool"),
+            Value::Euint8(_) => write!(f, "Euint8"),
+            Value::Euint16(_) => write!(f, "Euint16"),
+            Value::Euint32(_) => write!(f, "Euint32"),
+            Value::Euint64(_) => write!(f, "Euint64"),
+            Value::Euint128(_) => write!(f, "Euint128"),
+            Value::Bool(val) => write!(f, "Bool: {:?}", val),
+            Value::Uint8(val) => write!(f, "Uint8: {:?}", val),
+            Value::Uint16(val) => write!(f, "Uint16: {:?}", val),
+            Value::Uint32(val) => write!(f, "Uint32: {:?}", val),
+            Value::Uint64(val) => write!(f, "Uint64: {:?}", val),
+            Value::Uint128(val) => write!(f, "Uint128: {:?}", val),
         }
     }
 }
 
-impl FheOrd<Value> for Value {
-    fn lt(&self, other: Value) -> FheBool {
-        match (self, other) {
-            (Value::Euint8(a), Value::Euint8(b)) => a.lt(b),
-            (Value::Euint16(a), Value::Euint16(b)) => a.lt(b),
-            (Value::Euint32(a), Value::Euint32(b)) => a.lt(b),
-            (Value::Euint64(a), Value::Euint64(b)) => a.lt(b),
-            (Value::Euint128(a), Value::Euint128(b)) => a.lt(b),
-            _ => unimplemented!(),
+// The type of a Value without its contents, used where a type has to be named
+// before a value exists
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ValueType {
+    Ebool,
+    Euint8,
+    Euint16,
+    Euint32,
+    Euint64,
+    Euint128,
+
+    Bool,
+    Uint8,
+    Uint16,
+    Uint32,
+    Uint64,
+    Uint128,
+}
+
+impl ValueType {
+    pub fn is_encrypted(self) -> bool {
+        matches!(
+            self,
+            ValueType::Ebool
+                | ValueType::Euint8
+                | ValueType::Euint16
+                | ValueType::Euint32
+                | ValueType::Euint64
+                | ValueType::Euint128
+        )
+    }
+
+    pub fn is_bool(self) -> bool {
+        matches!(self, ValueType::Ebool | ValueType::Bool)
+    }
+
+    pub fn bits(self) -> u32 {
+        match self {
+            ValueType::Ebool | ValueType::Bool => 1,
+            ValueType::Euint8 | ValueType::Uint8 => 8,
+            ValueType::Euint16 | ValueType::Uint16 => 16,
+            ValueType::Euint32 | ValueType::Uint32 => 32,
+            ValueType::Euint64 | ValueType::Uint64 => 64,
+            ValueType::Euint128 | ValueType::Uint128 => 128,
         }
     }
 
-    fn le(&self, other: Value) -> FheBool {
-        match (self, other) {
-            (Value::Euint8(a), Value::Euint8(b)) => a.le(b),
-            (Value::Euint16(a), Value::Euint16(b)) => a.le(b),
-            (Value::Euint32(a), Value::Euint32(b)) => a.le(b),
-            (Value::Euint64(a), Value::Euint64(b)) => a.le(b),
-            (Value::Euint128(a), Value::Euint128(b)) => a.le(b),
-            _ => unimplemented!(),
+    // The plaintext integer type `bits` wide
+    pub fn uint(bits: u32) -> Self {
+        match bits {
+            8 => ValueType::Uint8,
+            16 => ValueType::Uint16,
+            32 => ValueType::Uint32,
+            64 => ValueType::Uint64,
+            128 => ValueType::Uint128,
+            _ => panic!("Unsupported width {}", bits),
         }
     }
 
-    fn gt(&self, other: Value) -> FheBool {
+    // The encrypted type of the same width
+    pub fn encrypted(self) -> Self {
+        match self {
+            ValueType::Bool => ValueType::Ebool,
+            ValueType::Uint8 => ValueType::Euint8,
+            ValueType::Uint16 => ValueType::Euint16,
+            ValueType::Uint32 => ValueType::Euint32,
+            ValueType::Uint64 => ValueType::Euint64,
+            ValueType::Uint128 => ValueType::Euint128,
+            encrypted => encrypted,
+        }
+    }
+
+    // The plaintext type of the same width
+    pub fn plaintext(self) -> Self {
+        match self {
+            ValueType::Ebool => ValueType::Bool,
+            ValueType::Euint8 => ValueType::Uint8,
+            ValueType::Euint16 => ValueType::Uint16,
+            ValueType::Euint32 => ValueType::Uint32,
+            ValueType::Euint64 => ValueType::Uint64,
+            ValueType::Euint128 => ValueType::Uint128,
+            plaintext => plaintext,
+        }
+    }
+
+    // Builds a plaintext value of this width, or None if `val` doesn't fit
+    pub fn plain_value(self, val: u128) -> Option<Value> {
+        if self.bits() < 128 && val >> self.bits() != 0 {
+            return None;
+        }
+        Some(match self.plaintext() {
+            ValueType::Bool => Value::Bool(val == 1),
+            ValueType::Uint8 => Value::Uint8(val as u8),
+            ValueType::Uint16 => Value::Uint16(val as u16),
+            ValueType::Uint32 => Value::Uint32(val as u32),
+            ValueType::Uint64 => Value::Uint64(val as u64),
+            _ => Value::Uint128(val),
+        })
+    }
+}
+
+impl Value {
+    pub fn value_type(&self) -> ValueType {
+        match self {
+            Value::Compressed(val) => val.value_type(),
+            Value::Ebool(_) => ValueType::Ebool,
+            Value::Euint8(_) => ValueType::Euint8,
+            Value::Euint16(_) => ValueType::Euint16,
+            Value::Euint32(_) => ValueType::Euint32,
+            Value::Euint64(_) => ValueType::Euint64,
+            Value::Euint128(_) => ValueType::Euint128,
+            Value::Bool(_) => ValueType::Bool,
+            Value::Uint8(_) => ValueType::Uint8,
+            Value::Uint16(_) => ValueType::Uint16,
+            Value::Uint32(_) => ValueType::Uint32,
+            Value::Uint64(_) => ValueType::Uint64,
+            Value::Uint128(_) => ValueType::Uint128,
+        }
+    }
+}
+
+// Comparisons promote both operands to the wider of the two widths. A
+// plaintext operand is compared through tfhe's scalar ops, and two plaintext
+// operands produce a plaintext Bool.
+macro_rules! comparison_op {
+    ($($vis:vis $op:ident, $method:ident, $flipped:ident);*) => {
+        impl Value {
+            $(
+                $vis fn $op(&self, other: &Self) -> Self {
+                    match (self, other) {
+                        // Plaintext, Plaintext
+                        (&Value::Uint8(a), &Value::Uint8(b)) => Value::Bool(a.$method(&b)),
+                        (&Value::Uint8(a), &Value::Uint16(b)) => Value::Bool((a as u16).$method(&b)),
+                        (&Value::Uint8(a), &Value::Uint32(b)) => Value::Bool((a as u32).$method(&b)),
+                        (&Value::Uint8(a), &Value::Uint64(b)) => Value::Bool((a as u64).$method(&b)),
+                        (&Value::Uint8(a), &Value::Uint128(b)) => Value::Bool((a as u128).$method(&b)),
+
+                        (&Value::Uint16(a), &Value::Uint8(b)) => Value::Bool(a.$method(&(b as u16))),
+                        (&Value::Uint16(a), &Value::Uint16(b)) => Value::Bool(a.$method(&b)),
+                        (&Value::Uint16(a), &Value::Uint32(b)) => Value::Bool((a as u32).$method(&b)),
+                        (&Value::Uint16(a), &Value::Uint64(b)) => Value::Bool((a as u64).$method(&b)),
+                        (&Value::Uint16(a), &Value::Uint128(b)) => Value::Bool((a as u128).$method(&b)),
+
+                        (&Value::Uint32(a), &Value::Uint8(b)) => Value::Bool(a.$method(&(b as u32))),
+                        (&Value::Uint32(a), &Value::Uint16(b)) => Value::Bool(a.$method(&(b as u32))),
+                        (&Value::Uint32(a), &Value::Uint32(b)) => Value::Bool(a.$method(&b)),
+                        (&Value::Uint32(a), &Value::Uint64(b)) => Value::Bool((a as u64).$method(&b)),
+                        (&Value::Uint32(a), &Value::Uint128(b)) => Value::Bool((a as u128).$method(&b)),
+
+                        (&Value::Uint64(a), &Value::Uint8(b)) => Value::Bool(a.$method(&(b as u64))),
+                        (&Value::Uint64(a), &Value::Uint16(b)) => Value::Bool(a.$method(&(b as u64))),
+                        (&Value::Uint64(a), &Value::Uint32(b)) => Value::Bool(a.$method(&(b as u64))),
+                        (&Value::Uint64(a), &Value::Uint64(b)) => Value::Bool(a.$method(&b)),
+                        (&Value::Uint64(a), &Value::Uint128(b)) => Value::Bool((a as u128).$method(&b)),
+
+                        (&Value::Uint128(a), &Value::Uint8(b)) => Value::Bool(a.$method(&(b as u128))),
+                        (&Value::Uint128(a), &Value::Uint16(b)) => Value::Bool(a.$method(&(b as u128))),
+                        (&Value::Uint128(a), &Value::Uint32(b)) => Value::Bool(a.$method(&(b as u128))),
+                        (&Value::Uint128(a), &Value::Uint64(b)) => Value::Bool(a.$method(&(b as u128))),
+                        (&Value::Uint128(a), &Value::Uint128(b)) => Value::Bool(a.$method(&b)),
+
+                        // Plaintext, Encrypted
+                        (&Value::Uint8(a), Value::Euint8(b)) => Value::Ebool(b.$flipped(a)),
+                        (&Value::Uint8(a), Value::Euint16(b)) => Value::Ebool(b.$flipped(a as u16)),
+                        (&Value::Uint8(a), Value::Euint32(b)) => Value::Ebool(b.$flipped(a as u32)),
+                        (&Value::Uint8(a), Value::Euint64(b)) => Value::Ebool(b.$flipped(a as u64)),
+                        (&Value::Uint8(a), Value::Euint128(b)) => Value::Ebool(b.$flipped(a as u128)),
+
+                        (&Value::Uint16(a), Value::Euint8(b)) => Value::Ebool(FheUint16::cast_from(b.clone()).$flipped(a)),
+                        (&Value::Uint16(a), Value::Euint16(b)) => Value::Ebool(b.$flipped(a)),
+                        (&Value::Uint16(a), Value::Euint32(b)) => Value::Ebool(b.$flipped(a as u32)),
+                        (&Value::Uint16(a), Value::Euint64(b)) => Value::Ebool(b.$flipped(a as u64)),
+                        (&Value::Uint16(a), Value::Euint128(b)) => Value::Ebool(b.$flipped(a as u128)),
+
+                        (&Value::Uint32(a), Value::Euint8(b)) => Value::Ebool(FheUint32::cast_from(b.clone()).$flipped(a)),
+                        (&Value::Uint32(a), Value::Euint16(b)) => Value::Ebool(FheUint32::cast_from(b.clone()).$flipped(a)),
+                        (&Value::Uint32(a), Value::Euint32(b)) => Value::Ebool(b.$flipped(a)),
+                        (&Value::Uint32(a), Value::Euint64(b)) => Value::Ebool(b.$flipped(a as u64)),
+                        (&Value::Uint32(a), Value::Euint128(b)) => Value::Ebool(b.$flipped(a as u128)),
+
+                        (&Value::Uint64(a), Value::Euint8(b)) => Value::Ebool(FheUint64::cast_from(b.clone()).$flipped(a)),
+                        (&Value::Uint64(a), Value::Euint16(b)) => Value::Ebool(FheUint64::cast_from(b.clone()).$flipped(a)),
+                        (&Value::Uint64(a), Value::Euint32(b)) => Value::Ebool(FheUint64::cast_from(b.clone()).$flipped(a)),
+                        (&Value::Uint64(a), Value::Euint64(b)) => Value::Ebool(b.$flipped(a)),
+                        (&Value::Uint64(a), Value::Euint128(b)) => Value::Ebool(b.$flipped(a as u128)),
+
+                        (&Value::Uint128(a), Value::Euint8(b)) => Value::Ebool(FheUint128::cast_from(b.clone()).$flipped(a)),
+                        (&Value::Uint128(a), Value::Euint16(b)) => Value::Ebool(FheUint128::cast_from(b.clone()).$flipped(a)),
+                        (&Value::Uint128(a), Value::Euint32(b)) => Value::Ebool(FheUint128::cast_from(b.clone()).$flipped(a)),
+                        (&Value::Uint128(a), Value::Euint64(b)) => Value::Ebool(FheUint128::cast_from(b.clone()).$flipped(a)),
+                        (&Value::Uint128(a), Value::Euint128(b)) => Value::Ebool(b.$flipped(a)),
+
+                        // Encrypted, Plaintext
+                        (Value::Euint8(a), &Value::Uint8(b)) => Value::Ebool(a.$method(b)),
+                        (Value::Euint8(a), &Value::Uint16(b)) => Value::Ebool(FheUint16::cast_from(a.clone()).$method(b)),
+                        (Value::Euint8(a), &Value::Uint32(b)) => Value::Ebool(FheUint32::cast_from(a.clone()).$method(b)),
+                        (Value::Euint8(a), &Value::Uint64(b)) => Value::Ebool(FheUint64::cast_from(a.clone()).$method(b)),
+                        (Value::Euint8(a), &Value::Uint128(b)) => Value::Ebool(FheUint128::cast_from(a.clone()).$method(b)),
+
+                        (Value::Euint16(a), &Value::Uint8(b)) => Value::Ebool(a.$method(b as u16)),
+                        (Value::Euint16(a), &Value::Uint16(b)) => Value::Ebool(a.$method(b)),
+                        (Value::Euint16(a), &Value::Uint32(b)) => Value::Ebool(FheUint32::cast_from(a.clone()).$method(b)),
+                        (Value::Euint16(a), &Value::Uint64(b)) => Value::Ebool(FheUint64::cast_from(a.clone()).$method(b)),
+                        (Value::Euint16(a), &Value::Uint128(b)) => Value::Ebool(FheUint128::cast_from(a.clone()).$method(b)),
+
+                        (Value::Euint32(a), &Value::Uint8(b)) => Value::Ebool(a.$method(b as u32)),
+                        (Value::Euint32(a), &Value::Uint16(b)) => Value::Ebool(a.$method(b as u32)),
+                        (Value::Euint32(a), &Value::Uint32(b)) => Value::Ebool(a.$method(b)),
+                        (Value::Euint32(a), &Value::Uint64(b)) => Value::Ebool(FheUint64::cast_from(a.clone()).$method(b)),
+                        (Value::Euint32(a), &Value::Uint128(b)) => Value::Ebool(FheUint128::cast_from(a.clone()).$method(b)),
+
+                        (Value::Euint64(a), &Value::Uint8(b)) => Value::Ebool(a.$method(b as u64)),
+                        (Value::Euint64(a), &Value::Uint16(b)) => Value::Ebool(a.$method(b as u64)),
+                        (Value::Euint64(a), &Value::Uint32(b)) => Value::Ebool(a.$method(b as u64)),
+                        (Value::Euint64(a), &Value::Uint64(b)) => Value::Ebool(a.$method(b)),
+                        (Value::Euint64(a), &Value::Uint128(b)) => Value::Ebool(FheUint128::cast_from(a.clone()).$method(b)),
+
+                        (Value::Euint128(a), &Value::Uint8(b)) => Value::Ebool(a.$method(b as u128)),
+                        (Value::Euint128(a), &Value::Uint16(b)) => Value::Ebool(a.$method(b as u128)),
+                        (Value::Euint128(a), &Value::Uint32(b)) => Value::Ebool(a.$method(b as u128)),
+                        (Value::Euint128(a), &Value::Uint64(b)) => Value::Ebool(a.$method(b as u128)),
+                        (Value::Euint128(a), &Value::Uint128(b)) => Value::Ebool(a.$method(b)),
+
+                        // Encrypted, Encrypted
+                        (Value::Euint8(a), Value::Euint8(b)) => Value::Ebool(a.$method(b)),
+                        (Value::Euint8(a), Value::Euint16(b)) => Value::Ebool(FheUint16::cast_from(a.clone()).$method(b)),
+                        (Value::Euint8(a), Value::Euint32(b)) => Value::Ebool(FheUint32::cast_from(a.clone()).$method(b)),
+                        (Value::Euint8(a), Value::Euint64(b)) => Value::Ebool(FheUint64::cast_from(a.clone()).$method(b)),
+                        (Value::Euint8(a), Value::Euint128(b)) => Value::Ebool(FheUint128::cast_from(a.clone()).$method(b)),
+
+                        (Value::Euint16(a), Value::Euint8(b)) => Value::Ebool(a.$method(&FheUint16::cast_from(b.clone()))),
+                        (Value::Euint16(a), Value::Euint16(b)) => Value::Ebool(a.$method(b)),
+                        (Value::Euint16(a), Value::Euint32(b)) => Value::Ebool(FheUint32::cast_from(a.clone()).$method(b)),
+                        (Value::Euint16(a), Value::Euint64(b)) => Value::Ebool(FheUint64::cast_from(a.clone()).$method(b)),
+                        (Value::Euint16(a), Value::Euint128(b)) => Value::Ebool(FheUint128::cast_from(a.clone()).$method(b)),
+
+                        (Value::Euint32(a), Value::Euint8(b)) => Value::Ebool(a.$method(&FheUint32::cast_from(b.clone()))),
+                        (Value::Euint32(a), Value::Euint16(b)) => Value::Ebool(a.$method(&FheUint32::cast_from(b.clone()))),
+                        (Value::Euint32(a), Value::Euint32(b)) => Value::Ebool(a.$method(b)),
+                        (Value::Euint32(a), Value::Euint64(b)) => Value::Ebool(FheUint64::cast_from(a.clone()).$method(b)),
+                        (Value::Euint32(a), Value::Euint128(b)) => Value::Ebool(FheUint128::cast_from(a.clone()).$method(b)),
+
+                        (Value::Euint64(a), Value::Euint8(b)) => Value::Ebool(a.$method(&FheUint64::cast_from(b.clone()))),
+                        (Value::Euint64(a), Value::Euint16(b)) => Value::Ebool(a.$method(&FheUint64::cast_from(b.clone()))),
+                        (Value::Euint64(a), Value::Euint32(b)) => Value::Ebool(a.$method(&FheUint64::cast_from(b.clone()))),
+                        (Value::Euint64(a), Value::Euint64(b)) => Value::Ebool(a.$method(b)),
+                        (Value::Euint64(a), Value::Euint128(b)) => Value::Ebool(FheUint128::cast_from(a.clone()).$method(b)),
+
+                        (Value::Euint128(a), Value::Euint8(b)) => Value::Ebool(a.$method(&FheUint128::cast_from(b.clone()))),
+                        (Value::Euint128(a), Value::Euint16(b)) => Value::Ebool(a.$method(&FheUint128::cast_from(b.clone()))),
+                        (Value::Euint128(a), Value::Euint32(b)) => Value::Ebool(a.$method(&FheUint128::cast_from(b.clone()))),
+                        (Value::Euint128(a), Value::Euint64(b)) => Value::Ebool(a.$method(&FheUint128::cast_from(b.clone()))),
+                        (Value::Euint128(a), Value::Euint128(b)) => Value::Ebool(a.$method(b)),
+
+                        (Value::Compressed(a), b) => a.decompress().$op(b),
+                        (a, Value::Compressed(b)) => a.$op(&b.decompress()),
+                        _ => unimplemented!(),
+                    }
+                }
+            )*
+        }
+    };
+}
+
+comparison_op! {
+    int_eq_op, eq, eq;
+    int_ne_op, ne, ne;
+    pub lt_op, lt, gt;
+    pub le_op, le, ge;
+    pub gt_op, gt, lt;
+    pub ge_op, ge, le
+}
+
+macro_rules! select_op {
+    ($($op:ident, $method:ident);*) => {
+        impl Value {
+            $(
+                pub fn $op(&self, other: &Self) -> Self {
+                    match (self, other) {
+                        // Plaintext, Plaintext
+                        (&Value::Uint8(a), &Value::Uint8(b)) => Value::Uint8(a.$method(b)),
+                        (&Value::Uint8(a), &Value::Uint16(b)) => Value::Uint16((a as u16).$method(b)),
+                        (&Value::Uint8(a), &Value::Uint32(b)) => Value::Uint32((a as u32).$method(b)),
+                        (&Value::Uint8(a), &Value::Uint64(b)) => Value::Uint64((a as u64).$method(b)),
+                        (&Value::Uint8(a), &Value::Uint128(b)) => Value::Uint128((a as u128).$method(b)),
+
+                        (&Value::Uint16(a), &Value::Uint8(b)) => Value::Uint16(a.$method(b as u16)),
+                        (&Value::Uint16(a), &Value::Uint16(b)) => Value::Uint16(a.$method(b)),
+                        (&Value::Uint16(a), &Value::Uint32(b)) => Value::Uint32((a as u32).$method(b)),
+                        (&Value::Uint16(a), &Value::Uint64(b)) => Value::Uint64((a as u64).$method(b)),
+                        (&Value::Uint16(a), &Value::Uint128(b)) => Value::Uint128((a as u128).$method(b)),
+
+                        (&Value::Uint32(a), &Value::Uint8(b)) => Value::Uint32(a.$method(b as u32)),
+                        (&Value::Uint32(a), &Value::Uint16(b)) => Value::Uint32(a.$method(b as u32)),
+                        (&Value::Uint32(a), &Value::Uint32(b)) => Value::Uint32(a.$method(b)),
+                        (&Value::Uint32(a), &Value::Uint64(b)) => Value::Uint64((a as u64).$method(b)),
+                        (&Value::Uint32(a), &Value::Uint128(b)) => Value::Uint128((a as u128).$method(b)),
+
+                        (&Value::Uint64(a), &Value::Uint8(b)) => Value::Uint64(a.$method(b as u64)),
+                        (&Value::Uint64(a), &Value::Uint16(b)) => Value::Uint64(a.$method(b as u64)),
+                        (&Value::Uint64(a), &Value::Uint32(b)) => Value::Uint64(a.$method(b as u64)),
+                        (&Value::Uint64(a), &Value::Uint64(b)) => Value::Uint64(a.$method(b)),
+                        (&Value::Uint64(a), &Value::Uint128(b)) => Value::Uint128((a as u128).$method(b)),
+
+                        (&Value::Uint128(a), &Value::Uint8(b)) => Value::Uint128(a.$method(b as u128)),
+                        (&Value::Uint128(a), &Value::Uint16(b)) => Value::Uint128(a.$method(b as u128)),
+                        (&Value::Uint128(a), &Value::Uint32(b)) => Value::Uint128(a.$method(b as u128)),
+                        (&Value::Uint128(a), &Value::Uint64(b)) => Value::Uint128(a.$method(b as u128)),
+                        (&Value::Uint128(a), &Value::Uint128(b)) => Value::Uint128(a.$method(b)),
+
+                        // Plaintext, Encrypted
+                        (&Value::Uint8(a), Value::Euint8(b)) => Value::Euint8(b.$method(a)),
+                        (&Value::Uint8(a), Value::Euint16(b)) => Value::Euint16(b.$method(a as u16)),
+                        (&Value::Uint8(a), Value::Euint32(b)) => Value::Euint32(b.$method(a as u32)),
+                        (&Value::Uint8(a), Value::Euint64(b)) => Value::Euint64(b.$method(a as u64)),
+                        (&Value::Uint8(a), Value::Euint128(b)) => Value::Euint128(b.$method(a as u128)),
+
+                        (&Value::Uint16(a), Value::Euint8(b)) => Value::Euint16(FheUint16::cast_from(b.clone()).$method(a)),
+                        (&Value::Uint16(a), Value::Euint16(b)) => Value::Euint16(b.$method(a)),
+                        (&Value::Uint16(a), Value::Euint32(b)) => Value::Euint32(b.$method(a as u32)),
+                        (&Value::Uint16(a), Value::Euint64(b)) => Value::Euint64(b.$method(a as u64)),
+                        (&Value::Uint16(a), Value::Euint128(b)) => Value::Euint128(b.$method(a as u128)),
+
+                        (&Value::Uint32(a), Value::Euint8(b)) => Value::Euint32(FheUint32::cast_from(b.clone()).$method(a)),
+                        (&Value::Uint32(a), Value::Euint16(b)) => Value::Euint32(FheUint32::cast_from(b.clone()).$method(a)),
+                        (&Value::Uint32(a), Value::Euint32(b)) => Value::Euint32(b.$method(a)),
+                        (&Value::Uint32(a), Value::Euint64(b)) => Value::Euint64(b.$method(a as u64)),
+                        (&Value::Uint32(a), Value::Euint128(b)) => Value::Euint128(b.$method(a as u128)),
+
+                        (&Value::Uint64(a), Value::Euint8(b)) => Value::Euint64(FheUint64::cast_from(b.clone()).$method(a)),
+                        (&Value::Uint64(a), Value::Euint16(b)) => Value::Euint64(FheUint64::cast_from(b.clone()).$method(a)),
+                        (&Value::Uint64(a), Value::Euint32(b)) => Value::Euint64(FheUint64::cast_from(b.clone()).$method(a)),
+                        (&Value::Uint64(a), Value::Euint64(b)) => Value::Euint64(b.$method(a)),
+                        (&Value::Uint64(a), Value::Euint128(b)) => Value::Euint128(b.$method(a as u128)),
+
+                        (&Value::Uint128(a), Value::Euint8(b)) => Value::Euint128(FheUint128::cast_from(b.clone()).$method(a)),
+                        (&Value::Uint128(a), Value::Euint16(b)) => Value::Euint128(FheUint128::cast_from(b.clone()).$method(a)),
+                        (&Value::Uint128(a), Value::Euint32(b)) => Value::Euint128(FheUint128::cast_from(b.clone()).$method(a)),
+                        (&Value::Uint128(a), Value::Euint64(b)) => Value::Euint128(FheUint128::cast_from(b.clone()).$method(a)),
+                        (&Value::Uint128(a), Value::Euint128(b)) => Value::Euint128(b.$method(a)),
+
+                        // Encrypted, Plaintext
+                        (Value::Euint8(a), &Value::Uint8(b)) => Value::Euint8(a.$method(b)),
+                        (Value::Euint8(a), &Value::Uint16(b)) => Value::Euint16(FheUint16::cast_from(a.clone()).$method(b)),
+                        (Value::Euint8(a), &Value::Uint32(b)) => Value::Euint32(FheUint32::cast_from(a.clone()).$method(b)),
+                        (Value::Euint8(a), &Value::Uint64(b)) => Value::Euint64(FheUint64::cast_from(a.clone()).$method(b)),
+                        (Value::Euint8(a), &Value::Uint128(b)) => Value::Euint128(FheUint128::cast_from(a.clone()).$method(b)),
+
+                        (Value::Euint16(a), &Value::Uint8(b)) => Value::Euint16(a.$method(b as u16)),
+                        (Value::Euint16(a), &Value::Uint16(b)) => Value::Euint16(a.$method(b)),
+                        (Value::Euint16(a), &Value::Uint32(b)) => Value::Euint32(FheUint32::cast_from(a.clone()).$method(b)),
+                        (Value::Euint16(a), &Value::Uint64(b)) => Value::Euint64(FheUint64::cast_from(a.clone()).$method(b)),
+                        (Value::Euint16(a), &Value::Uint128(b)) => Value::Euint128(FheUint128::cast_from(a.clone()).$method(b)),
+
+                        (Value::Euint32(a), &Value::Uint8(b)) => Value::Euint32(a.$method(b as u32)),
+                        (Value::Euint32(a), &Value::Uint16(b)) => Value::Euint32(a.$method(b as u32)),
+                        (Value::Euint32(a), &Value::Uint32(b)) => Value::Euint32(a.$method(b)),
+                        (Value::Euint32(a), &Value::Uint64(b)) => Value::Euint64(FheUint64::cast_from(a.clone()).$method(b)),
+                        (Value::Euint32(a), &Value::Uint128(b)) => Value::Euint128(FheUint128::cast_from(a.clone()).$method(b)),
+
+                        (Value::Euint64(a), &Value::Uint8(b)) => Value::Euint64(a.$method(b as u64)),
+                        (Value::Euint64(a), &Value::Uint16(b)) => Value::Euint64(a.$method(b as u64)),
+                        (Value::Euint64(a), &Value::Uint32(b)) => Value::Euint64(a.$method(b as u64)),
+                        (Value::Euint64(a), &Value::Uint64(b)) => Value::Euint64(a.$method(b)),
+                        (Value::Euint64(a), &Value::Uint128(b)) => Value::Euint128(FheUint128::cast_from(a.clone()).$method(b)),
+
+                        (Value::Euint128(a), &Value::Uint8(b)) => Value::Euint128(a.$method(b as u128)),
+                        (Value::Euint128(a), &Value::Uint16(b)) => Value::Euint128(a.$method(b as u128)),
+                        (Value::Euint128(a), &Value::Uint32(b)) => Value::Euint128(a.$method(b as u128)),
+                        (Value::Euint128(a), &Value::Uint64(b)) => Value::Euint128(a.$method(b as u128)),
+                        (Value::Euint128(a), &Value::Uint128(b)) => Value::Euint128(a.$method(b)),
+
+                        // Encrypted, Encrypted
+                        (Value::Euint8(a), Value::Euint8(b)) => Value::Euint8(a.$method(b)),
+                        (Value::Euint8(a), Value::Euint16(b)) => Value::Euint16(FheUint16::cast_from(a.clone()).$method(b)),
+                        (Value::Euint8(a), Value::Euint32(b)) => Value::Euint32(FheUint32::cast_from(a.clone()).$method(b)),
+                        (Value::Euint8(a), Value::Euint64(b)) => Value::Euint64(FheUint64::cast_from(a.clone()).$method(b)),
+                        (Value::Euint8(a), Value::Euint128(b)) => Value::Euint128(FheUint128::cast_from(a.clone()).$method(b)),
+
+                        (Value::Euint16(a), Value::Euint8(b)) => Value::Euint16(a.$method(&FheUint16::cast_from(b.clone()))),
+                        (Value::Euint16(a), Value::Euint16(b)) => Value::Euint16(a.$method(b)),
+                        (Value::Euint16(a), Value::Euint32(b)) => Value::Euint32(FheUint32::cast_from(a.clone()).$method(b)),
+                        (Value::Euint16(a), Value::Euint64(b)) => Value::Euint64(FheUint64::cast_from(a.clone()).$method(b)),
+                        (Value::Euint16(a), Value::Euint128(b)) => Value::Euint128(FheUint128::cast_from(a.clone()).$method(b)),
+
+                        (Value::Euint32(a), Value::Euint8(b)) => Value::Euint32(a.$method(&FheUint32::cast_from(b.clone()))),
+                        (Value::Euint32(a), Value::Euint16(b)) => Value::Euint32(a.$method(&FheUint32::cast_from(b.clone()))),
+                        (Value::Euint32(a), Value::Euint32(b)) => Value::Euint32(a.$method(b)),
+                        (Value::Euint32(a), Value::Euint64(b)) => Value::Euint64(FheUint64::cast_from(a.clone()).$method(b)),
+                        (Value::Euint32(a), Value::Euint128(b)) => Value::Euint128(FheUint128::cast_from(a.clone()).$method(b)),
+
+                        (Value::Euint64(a), Value::Euint8(b)) => Value::Euint64(a.$method(&FheUint64::cast_from(b.clone()))),
+                        (Value::Euint64(a), Value::Euint16(b)) => Value::Euint64(a.$method(&FheUint64::cast_from(b.clone()))),
+                        (Value::Euint64(a), Value::Euint32(b)) => Value::Euint64(a.$method(&FheUint64::cast_from(b.clone()))),
+                        (Value::Euint64(a), Value::Euint64(b)) => Value::Euint64(a.$method(b)),
+                        (Value::Euint64(a), Value::Euint128(b)) => Value::Euint128(FheUint128::cast_from(a.clone()).$method(b)),
+
+                        (Value::Euint128(a), Value::Euint8(b)) => Value::Euint128(a.$method(&FheUint128::cast_from(b.clone()))),
+                        (Value::Euint128(a), Value::Euint16(b)) => Value::Euint128(a.$method(&FheUint128::cast_from(b.clone()))),
+                        (Value::Euint128(a), Value::Euint32(b)) => Value::Euint128(a.$method(&FheUint128::cast_from(b.clone()))),
+                        (Value::Euint128(a), Value::Euint64(b)) => Value::Euint128(a.$method(&FheUint128::cast_from(b.clone()))),
+                        (Value::Euint128(a), Value::Euint128(b)) => Value::Euint128(a.$method(b)),
+
+                        (Value::Compressed(a), b) => a.decompress().$op(b),
+                        (a, Value::Compressed(b)) => a.$op(&b.decompress()),
+                        _ => unimplemented!(),
+                    }
+                }
+            )*
+        }
+    };
+}
+
+select_op! {
+    min_op, min;
+    max_op, max
+}
+
+impl Value {
+    pub fn eq_op(&self, other: &Self) -> Self {
         match (self, other) {
-            (Value::Euint8(a), Value::Euint8(b)) => a.gt(b),
-            (Value::Euint16(a), Value::Euint16(b)) => a.gt(b),
-            (Value::Euint32(a), Value::Euint32(b)) => a.gt(b),
-            (Value::Euint64(a), Value::Euint64(b)) => a.gt(b),
-            (Value::Euint128(a), Value::Euint128(b)) => a.gt(b),
-            _ => unimplemented!(),
+            (Value::Bool(a), Value::Bool(b)) => Value::Bool(a == b),
+            (Value::Bool(a), Value::Ebool(b)) => Value::Ebool(b.eq(*a)),
+            (Value::Ebool(a), Value::Bool(b)) => Value::Ebool(a.eq(*b)),
+            (Value::Ebool(a), Value::Ebool(b)) => Value::Ebool(a.eq(b)),
+            (Value::Compressed(a), b) => a.decompress().eq_op(b),
+            (a, Value::Compressed(b)) => a.eq_op(&b.decompress()),
+            (a, b) => a.int_eq_op(b),
         }
     }
 
-    fn ge(&self, other: Value) -> FheBool {
+    pub fn ne_op(&self, other: &Self) -> Self {
         match (self, other) {
-            (Value::Euint8(a), Value::Euint8(b)) => a.ge(b),
-            (Value::Euint16(a), Value::Euint16(b)) => a.ge(b),
-            (Value::Euint32(a), Value::Euint32(b)) => a.ge(b),
-            (Value::Euint64(a), Value::Euint64(b)) => a.ge(b),
-            (Value::Euint128(a), Value::Euint128(b)) => a.ge(b),
-            _ => unimplemented!(),
+            (Value::Bool(a), Value::Bool(b)) => Value::Bool(a != b),
+            (Value::Bool(a), Value::Ebool(b)) => Value::Ebool(b.ne(*a)),
+            (Value::Ebool(a), Value::Bool(b)) => Value::Ebool(a.ne(*b)),
+            (Value::Ebool(a), Value::Ebool(b)) => Value::Ebool(a.ne(b)),
+            (Value::Compressed(a), b) => a.decompress().ne_op(b),
+            (a, Value::Compressed(b)) => a.ne_op(&b.decompress()),
+            (a, b) => a.int_ne_op(b),
         }
     }
 }
@@ -558,6 +2018,7 @@ pub trait Neg {
 impl Neg for Value {
     fn neg(&self) -> Self {
         match self {
+            Value::Compressed(val) => val.decompress().neg(),
             Value::Ebool(val) => Value::Ebool(!val),
             Value::Euint8(val) => Value::Euint8(-val),
             Value::Euint16(val) => Value::Euint16(-val),
@@ -565,11 +2026,11 @@ impl Neg for Value {
             Value::Euint64(val) => Value::Euint64(-val),
             Value::Euint128(val) => Value::Euint128(-val),
             Value::Bool(val) => Value::Bool(!val),
-            Value::Uint8(val) => Value::Uint8(1 - val),
-            Value::Uint16(val) => Value::Uint16(1 - val),
-            Value::Uint32(val) => Value::Uint32(1 - val),
-            Value::Uint64(val) => Value::Uint64(1 - val),
-            Value::Uint128(val) => Value::Uint128(1 - val),
+            Value::Uint8(val) => Value::Uint8(val.wrapping_neg()),
+            Value::Uint16(val) => Value::Uint16(val.wrapping_neg()),
+            Value::Uint32(val) => Value::Uint32(val.wrapping_neg()),
+            Value::Uint64(val) => Value::Uint64(val.wrapping_neg()),
+            Value::Uint128(val) => Value::Uint128(val.wrapping_neg()),
         }
     }
 }
@@ -607,65 +2068,113 @@ impl Value {
                 bytes.extend(bincode::serialize(val).unwrap());
                 bytes
             }
-            Value::Bool(val) => bincode::serialize(val).unwrap(),
-            Value::Uint8(val) => bincode::serialize(val).unwrap(),
-            Value::Uint16(val) => bincode::serialize(val).unwrap(),
-            Value::Uint32(val) => bincode::serialize(val).unwrap(),
-            Value::Uint64(val) => bincode::serialize(val).unwrap(),
-            Value::Uint128(val) => bincode::serialize(val).unwrap(),
-        }
-    }
-
-    pub fn from_bytes(bytes: &[u8]) -> (Self, usize) {
-        // Returns Value and bytes consumed
-        match bytes[0] {
-            0 => {
-                let val: FheBool = bincode::deserialize(&bytes[1..]).unwrap();
-                (Value::Ebool(val), bytes.len())
+            Value::Bool(val) => {
+                let mut bytes = vec![6];
+                bytes.extend(bincode::serialize(val).unwrap());
+                bytes
             }
-            1 => {
-                let val: FheUint8 = bincode::deserialize(&bytes[1..]).unwrap();
-                (Value::Euint8(val), bytes.len())
+            Value::Uint8(val) => {
+                let mut bytes = vec![7];
+                bytes.extend(bincode::serialize(val).unwrap());
+                bytes
             }
-            2 => {
-                let val: FheUint16 = bincode::deserialize(&bytes[1..]).unwrap();
-                (Value::Euint16(val), bytes.len())
+            Value::Uint16(val) => {
+                let mut bytes = vec![8];
+                bytes.extend(bincode::serialize(val).unwrap());
+                bytes
             }
-            3 => {
-                let val: FheUint32 = bincode::deserialize(&bytes[1..]).unwrap();
-                (Value::Euint32(val), bytes.len())
+            Value::Uint32(val) => {
+                let mut bytes = vec![9];
+                bytes.extend(bincode::serialize(val).unwrap());
+                bytes
             }
-            4 => {
-                let val: FheUint64 = bincode::deserialize(&bytes[1..]).unwrap();
-                (Value::Euint64(val), bytes.len())
+            Value::Uint64(val) => {
+                let mut bytes = vec![10];
+                bytes.extend(bincode::serialize(val).unwrap());
+                bytes
             }
-            5 => {
-                let val: FheUint128 = bincode::deserialize(&bytes[1..]).unwrap();
-                (Value::Euint128(val), bytes.len())
+            Value::Uint128(val) => {
+                let mut bytes = vec![11];
+                bytes.extend(bincode::serialize(val).unwrap());
+                bytes
+            }
+            Value::Compressed(val) => {
+                let (tag, payload) = match val.as_ref() {
+                    CompressedValue::Ebool(val) => (12, bincode::serialize(val)),
+                    CompressedValue::Euint8(val) => (13, bincode::serialize(val)),
+                    CompressedValue::Euint16(val) => (14, bincode::serialize(val)),
+                    CompressedValue::Euint32(val) => (15, bincode::serialize(val)),
+                    CompressedValue::Euint64(val) => (16, bincode::serialize(val)),
+                    CompressedValue::Euint128(val) => (17, bincode::serialize(val)),
+                };
+                let mut bytes = vec![tag];
+                bytes.extend(payload.unwrap());
+                bytes
             }
-            // Handle other Value variants...
-            _ => unimplemented!(),
         }
     }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), DecodeError> {
+        // Returns Value and bytes consumed
+        let (tag, mut payload) = bytes.split_first().ok_or(DecodeError::UnexpectedEof)?;
+        let remaining = payload.len();
+        let value = match tag {
+            0 => Value::Ebool(bincode::deserialize_from(&mut payload)?),
+            1 => Value::Euint8(bincode::deserialize_from(&mut payload)?),
+            2 => Value::Euint16(bincode::deserialize_from(&mut payload)?),
+            3 => Value::Euint32(bincode::deserialize_from(&mut payload)?),
+            4 => Value::Euint64(bincode::deserialize_from(&mut payload)?),
+            5 => Value::Euint128(bincode::deserialize_from(&mut payload)?),
+            6 => Value::Bool(bincode::deserialize_from(&mut payload)?),
+            7 => Value::Uint8(bincode::deserialize_from(&mut payload)?),
+            8 => Value::Uint16(bincode::deserialize_from(&mut payload)?),
+            9 => Value::Uint32(bincode::deserialize_from(&mut payload)?),
+            10 => Value::Uint64(bincode::deserialize_from(&mut payload)?),
+            11 => Value::Uint128(bincode::deserialize_from(&mut payload)?),
+            12 => Value::Compressed(Box::new(CompressedValue::Ebool(bincode::deserialize_from(
+                &mut payload,
+            )?))),
+            13 => Value::Compressed(Box::new(CompressedValue::Euint8(
+                bincode::deserialize_from(&mut payload)?,
+            ))),
+            14 => Value::Compressed(Box::new(CompressedValue::Euint16(
+                bincode::deserialize_from(&mut payload)?,
+            ))),
+            15 => Value::Compressed(Box::new(CompressedValue::Euint32(
+                bincode::deserialize_from(&mut payload)?,
+            ))),
+            16 => Value::Compressed(Box::new(CompressedValue::Euint64(
+                bincode::deserialize_from(&mut payload)?,
+            ))),
+            17 => Value::Compressed(Box::new(CompressedValue::Euint128(
+                bincode::deserialize_from(&mut payload)?,
+            ))),
+            _ => return Err(DecodeError::UnknownValueTag(*tag)),
+        };
+        Ok((value, 1 + remaining - payload.len()))
+    }
 }
 
+// Booleans wrap in a single bit, so adding or subtracting an odd number flips
+// them.
 impl Add<u8> for Value {
     type Output = Self;
 
     fn add(self, other: u8) -> Self {
         match self {
-            Value::Ebool(_val) => unimplemented!(),
+            Value::Compressed(val) => val.decompress() + other,
+            Value::Ebool(val) => Value::Ebool(val ^ (other & 1 == 1)),
             Value::Euint8(val) => Value::Euint8(val + other),
             Value::Euint16(val) => Value::Euint16(val + other as u16),
             Value::Euint32(val) => Value::Euint32(val + other as u32),
             Value::Euint64(val) => Value::Euint64(val + other as u64),
             Value::Euint128(val) => Value::Euint128(val + other as u128),
-            Value::Bool(_val) => unimplemented!(),
-            Value::Uint8(val) => Value::Uint8(val + other),
-            Value::Uint16(val) => Value::Uint16(val + other as u16),
-            Value::Uint32(val) => Value::Uint32(val + other as u32),
-            Value::Uint64(val) => Value::Uint64(val + other as u64),
-            Value::Uint128(val) => Value::Uint128(val + other as u128),
+            Value::Bool(val) => Value::Bool(val ^ (other & 1 == 1)),
+            Value::Uint8(val) => Value::Uint8(val.wrapping_add(other)),
+            Value::Uint16(val) => Value::Uint16(val.wrapping_add(other as u16)),
+            Value::Uint32(val) => Value::Uint32(val.wrapping_add(other as u32)),
+            Value::Uint64(val) => Value::Uint64(val.wrapping_add(other as u64)),
+            Value::Uint128(val) => Value::Uint128(val.wrapping_add(other as u128)),
         }
     }
 }
@@ -675,18 +2184,19 @@ impl Sub<u8> for Value {
 
     fn sub(self, other: u8) -> Self {
         match self {
-            Value::Ebool(_val) => unimplemented!(),
+            Value::Compressed(val) => val.decompress() - other,
+            Value::Ebool(val) => Value::Ebool(val ^ (other & 1 == 1)),
             Value::Euint8(val) => Value::Euint8(val - other),
             Value::Euint16(val) => Value::Euint16(val - other as u16),
             Value::Euint32(val) => Value::Euint32(val - other as u32),
             Value::Euint64(val) => Value::Euint64(val - other as u64),
             Value::Euint128(val) => Value::Euint128(val - other as u128),
-            Value::Bool(_val) => unimplemented!(),
-            Value::Uint8(val) => Value::Uint8(val - other),
-            Value::Uint16(val) => Value::Uint16(val - other as u16),
-            Value::Uint32(val) => Value::Uint32(val - other as u32),
-            Value::Uint64(val) => Value::Uint64(val - other as u64),
-            Value::Uint128(val) => Value::Uint128(val - other as u128),
+            Value::Bool(val) => Value::Bool(val ^ (other & 1 == 1)),
+            Value::Uint8(val) => Value::Uint8(val.wrapping_sub(other)),
+            Value::Uint16(val) => Value::Uint16(val.wrapping_sub(other as u16)),
+            Value::Uint32(val) => Value::Uint32(val.wrapping_sub(other as u32)),
+            Value::Uint64(val) => Value::Uint64(val.wrapping_sub(other as u64)),
+            Value::Uint128(val) => Value::Uint128(val.wrapping_sub(other as u128)),
         }
     }
 }
@@ -696,6 +2206,7 @@ impl Rem<u8> for Value {
 
     fn rem(self, other: u8) -> Self {
         match self {
+            Value::Compressed(val) => val.decompress() % other,
             Value::Ebool(_val) => unimplemented!(),
             Value::Euint8(val) => Value::Euint8(val % other),
             Value::Euint16(val) => Value::Euint16(val % other as u16),
@@ -1,10 +1,17 @@
+use crate::error::{DecodeError, SealError};
+use crate::seal::SealedModule;
+use crate::transcript::{Footprint, Transcript};
 use crate::value::Neg;
-use crate::value::Value;
+use crate::value::{Table, Value};
+use ed25519_dalek::VerifyingKey;
+use rayon::prelude::*;
 use serde::Deserialize;
 use serde::Serialize;
+use std::collections::HashSet;
 use std::convert::TryInto;
+use std::sync::Arc;
 use tfhe::prelude::*;
-use tfhe::FheBool;
+use tfhe::{FheBool, ServerKey};
 
 #[derive(Clone, Serialize, Deserialize)]
 pub enum OpCode {
@@ -12,16 +19,34 @@ pub enum OpCode {
     Add,
     Sub,
     Mul,
+    AddChecked, // Pushes the result and an overflow flag
+    SubChecked,
+    MulChecked,
+    AddSat, // Clamps to the type maximum on overflow
+    SubSat, // Clamps to zero on underflow
+    MulSat,
+    AddImm(Value), // Immediates are plaintext scalars
+    SubImm(Value),
+    MulImm(Value),
     Div,
+    DivChecked, // Pushes the quotient and a divide-by-zero flag
     Neg,
-    Rem, // Remainder
+    Rem,    // Remainder
+    DivRem, // Pushes the quotient, then the remainder
 
     // bitwise operations
     And,
     Or,
     Xor,
+    Not,
     ShiftRight,
     ShiftLeft,
+    RotateLeft,
+    RotateRight,
+    CountOnes,
+    LeadingZeros,
+    TrailingZeros,
+    ILog2,
 
     // comparison
     Eq,
@@ -33,6 +58,10 @@ pub enum OpCode {
     Min,
     Max,
 
+    // conversion
+    BoolToInt(u32), // Casts a boolean to 0 or 1 of the given bit width
+    IntToBool,      // True if the integer is nonzero
+
     // multiplex
     Mux,
 
@@ -50,6 +79,27 @@ pub enum OpCode {
     Load(i32), // Assuming address space is indexed by i32
     Store(i32),
     Swap,
+    Drop,      // Discard the top item
+    Over,      // Copy the second item to the top
+    Rot,       // Move the third item to the top
+    Pick(u32), // Copy the item n below the top to the top
+    Roll(u32), // Move the item n below the top to the top
+    Depth,     // Push the number of items on the stack
+
+    // vector operations over memory ranges, elementwise ops run in parallel
+    VAdd(i32, i32, i32, u32), // dst, lhs, rhs, len
+    VMul(i32, i32, i32, u32),
+    VSum(i32, u32),               // Push the sum of start..start + len
+    VDot(i32, i32, u32),          // Push the dot product of lhs and rhs
+    VMap(i32, i32, u32, UnaryOp), // dst, src, len, unary op
+
+    // data-oblivious sorting over memory ranges
+    Sort(i32, u32),           // Sort start..start + len in ascending order
+    TopK(i32, i32, u32, u32), // keys, payloads, len, k
+    ArgMax(i32, u32),         // Push the maximum, then its index
+
+    // lookup tables
+    Lut(u32), // Map the top item through a table of the program's table section
 }
 
 impl std::fmt::Debug for OpCode {
@@ -58,13 +108,30 @@ impl std::fmt::Debug for OpCode {
             OpCode::Add => write!(f, "Add"),
             OpCode::Sub => write!(f, "Sub"),
             OpCode::Mul => write!(f, "Mul"),
+            OpCode::AddChecked => write!(f, "AddChecked"),
+            OpCode::SubChecked => write!(f, "SubChecked"),
+            OpCode::MulChecked => write!(f, "MulChecked"),
+            OpCode::AddSat => write!(f, "AddSat"),
+            OpCode::SubSat => write!(f, "SubSat"),
+            OpCode::MulSat => write!(f, "MulSat"),
+            OpCode::AddImm(value) => write!(f, "AddImm({:?})", value),
+            OpCode::SubImm(value) => write!(f, "SubImm({:?})", value),
+            OpCode::MulImm(value) => write!(f, "MulImm({:?})", value),
             OpCode::Div => write!(f, "Div"),
+            OpCode::DivChecked => write!(f, "DivChecked"),
             OpCode::Neg => write!(f, "Neg"),
             OpCode::And => write!(f, "And"),
             OpCode::Or => write!(f, "Or"),
             OpCode::Xor => write!(f, "Xor"),
+            OpCode::Not => write!(f, "Not"),
             OpCode::ShiftRight => write!(f, "ShiftRight"),
             OpCode::ShiftLeft => write!(f, "ShiftLeft"),
+            OpCode::RotateLeft => write!(f, "RotateLeft"),
+            OpCode::RotateRight => write!(f, "RotateRight"),
+            OpCode::CountOnes => write!(f, "CountOnes"),
+            OpCode::LeadingZeros => write!(f, "LeadingZeros"),
+            OpCode::TrailingZeros => write!(f, "TrailingZeros"),
+            OpCode::ILog2 => write!(f, "ILog2"),
             OpCode::Eq => write!(f, "Eq"),
             OpCode::Neq => write!(f, "Neq"),
             OpCode::Lt => write!(f, "Lt"),
@@ -73,45 +140,10 @@ impl std::fmt::Debug for OpCode {
             OpCode::Gte => write!(f, "Gte"),
             OpCode::Min => write!(f, "Min"),
             OpCode::Max => write!(f, "Max"),
+            OpCode::BoolToInt(width) => write!(f, "BoolToInt({})", width),
+            OpCode::IntToBool => write!(f, "IntToBool"),
             OpCode::Mux => write!(f, "Mux"),
-            OpCode::Push(value) => match value {
-                Value::Ebool(_) => {
-                    write!(f, "Push(Ebool)")
-                }
-                Value::Euint8(_) => {
-                    write!(f, "Push(Euint8)")
-                }
-                Value::Euint16(_) => {
-                    write!(f, "Push(Euint16)")
-                }
-                Value::Euint32(_) => {
-                    write!(f, "Push(Euint32)")
-                }
-                Value::Euint64(_) => {
-                    write!(f, "Push(Euint64)")
-                }
-                Value::Euint128(_) => {
-                    write!(f, "Push(Euint128)")
-                }
-                Value::Bool(val) => {
-                    write!(f, "Push(Bool: {:?})", val)
-                }
-                Value::Uint8(val) => {
-                    write!(f, "Push(Uint8: {:?})", val)
-                }
-                Value::Uint16(val) => {
-                    write!(f, "Push(Uint16: {:?})", val)
-                }
-                Value::Uint32(val) => {
-                    write!(f, "Push(Uint32: {:?})", val)
-                }
-                Value::Uint64(val) => {
-                    write!(f, "Push(Uint64: {:?})", val)
-                }
-                Value::Uint128(val) => {
-                    write!(f, "Push(Uint128: {:?})", val)
-                }
-            },
+            OpCode::Push(value) => write!(f, "Push({:?})", value),
             OpCode::Dup => write!(f, "Dup"),
             OpCode::NoOp => write!(f, "NoOp"),
             OpCode::Inc => write!(f, "Inc"),
@@ -119,12 +151,156 @@ impl std::fmt::Debug for OpCode {
             OpCode::Load(address) => write!(f, "Load({})", address),
             OpCode::Store(address) => write!(f, "Store({})", address),
             OpCode::Swap => write!(f, "Swap"),
+            OpCode::Drop => write!(f, "Drop"),
+            OpCode::Over => write!(f, "Over"),
+            OpCode::Rot => write!(f, "Rot"),
+            OpCode::Pick(n) => write!(f, "Pick({})", n),
+            OpCode::Roll(n) => write!(f, "Roll({})", n),
+            OpCode::Depth => write!(f, "Depth"),
+            OpCode::VAdd(dst, lhs, rhs, len) => {
+                write!(f, "VAdd({}, {}, {}, {})", dst, lhs, rhs, len)
+            }
+            OpCode::VMul(dst, lhs, rhs, len) => {
+                write!(f, "VMul({}, {}, {}, {})", dst, lhs, rhs, len)
+            }
+            OpCode::VSum(start, len) => write!(f, "VSum({}, {})", start, len),
+            OpCode::VDot(lhs, rhs, len) => write!(f, "VDot({}, {}, {})", lhs, rhs, len),
+            OpCode::VMap(dst, src, len, op) => {
+                write!(f, "VMap({}, {}, {}, {:?})", dst, src, len, op)
+            }
+            OpCode::Sort(start, len) => write!(f, "Sort({}, {})", start, len),
+            OpCode::TopK(keys, payloads, len, k) => {
+                write!(f, "TopK({}, {}, {}, {})", keys, payloads, len, k)
+            }
+            OpCode::ArgMax(start, len) => write!(f, "ArgMax({}, {})", start, len),
+            OpCode::Lut(table) => write!(f, "Lut({})", table),
             OpCode::Rem => write!(f, "Rem"),
+            OpCode::DivRem => write!(f, "DivRem"),
         }
     }
 }
 
+// The opcode of a VMap. It can only be built from a unary opcode, so every
+// VMap can be serialized and run.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(try_from = "OpCode", into = "OpCode")]
+pub struct UnaryOp(Box<OpCode>);
+
+impl UnaryOp {
+    pub fn new(op: OpCode) -> Option<Self> {
+        op.is_unary().then(|| UnaryOp(Box::new(op)))
+    }
+
+    pub fn op(&self) -> &OpCode {
+        &self.0
+    }
+
+    // Applies the opcode to a single value, without a stack
+    fn apply(&self, value: Arc<Value>, tables: &[Table]) -> Value {
+        self.0.apply_unary(value, tables)
+    }
+}
+
+impl TryFrom<OpCode> for UnaryOp {
+    type Error = DecodeError;
+
+    fn try_from(op: OpCode) -> Result<Self, DecodeError> {
+        if !op.is_unary() {
+            return Err(DecodeError::NotUnary(op.to_bytes()[0]));
+        }
+        Ok(UnaryOp(Box::new(op)))
+    }
+}
+
+impl From<UnaryOp> for OpCode {
+    fn from(op: UnaryOp) -> Self {
+        *op.0
+    }
+}
+
+impl std::fmt::Debug for UnaryOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
 impl OpCode {
+    // Opcodes that take one value and give one back without touching memory
+    // or the rest of the stack, the only ones VMap can apply
+    fn is_unary(&self) -> bool {
+        matches!(
+            self,
+            OpCode::AddImm(_)
+                | OpCode::SubImm(_)
+                | OpCode::MulImm(_)
+                | OpCode::Neg
+                | OpCode::Not
+                | OpCode::CountOnes
+                | OpCode::LeadingZeros
+                | OpCode::TrailingZeros
+                | OpCode::ILog2
+                | OpCode::BoolToInt(_)
+                | OpCode::IntToBool
+                | OpCode::Inc
+                | OpCode::Dec
+                | OpCode::Lut(_)
+        )
+    }
+
+    // Neg and Not borrow the value; the other opcodes take it, cloning it only
+    // if it is still shared
+    fn apply_unary(&self, value: Arc<Value>, tables: &[Table]) -> Value {
+        match self {
+            OpCode::Neg => value.neg(),
+            OpCode::Not => value.not_op(),
+            OpCode::AddImm(imm) => Arc::unwrap_or_clone(value).add_imm_op(imm),
+            OpCode::SubImm(imm) => Arc::unwrap_or_clone(value).sub_imm_op(imm),
+            OpCode::MulImm(imm) => Arc::unwrap_or_clone(value).mul_imm_op(imm),
+            OpCode::CountOnes => Arc::unwrap_or_clone(value).count_ones_op(),
+            OpCode::LeadingZeros => Arc::unwrap_or_clone(value).leading_zeros_op(),
+            OpCode::TrailingZeros => Arc::unwrap_or_clone(value).trailing_zeros_op(),
+            OpCode::ILog2 => Arc::unwrap_or_clone(value).ilog2_op(),
+            OpCode::BoolToInt(width) => Arc::unwrap_or_clone(value).bool_to_int_op(*width),
+            OpCode::IntToBool => Arc::unwrap_or_clone(value).int_to_bool_op(),
+            OpCode::Inc => Arc::unwrap_or_clone(value) + 1u8,
+            OpCode::Dec => Arc::unwrap_or_clone(value) - 1u8,
+            OpCode::Lut(id) => Arc::unwrap_or_clone(value).lut_op(&tables[*id as usize]),
+            op => unreachable!("{:?} isn't unary", op),
+        }
+    }
+
+    // Table a Lut reads, directly or as the opcode of a VMap
+    fn table(&self) -> Option<u32> {
+        match self {
+            OpCode::Lut(id) => Some(*id),
+            OpCode::VMap(_, _, _, op) => op.op().table(),
+            _ => None,
+        }
+    }
+
+    // Stack items the opcode reads, which must be there before it runs
+    pub(crate) fn depth(&self) -> usize {
+        match self {
+            OpCode::Push(_)
+            | OpCode::NoOp
+            | OpCode::Load(_)
+            | OpCode::Depth
+            | OpCode::VAdd(..)
+            | OpCode::VMul(..)
+            | OpCode::VSum(..)
+            | OpCode::VDot(..)
+            | OpCode::VMap(..)
+            | OpCode::Sort(..)
+            | OpCode::TopK(..)
+            | OpCode::ArgMax(..) => 0,
+            op if op.is_unary() => 1,
+            OpCode::Store(_) | OpCode::Drop | OpCode::Dup => 1,
+            OpCode::Mux | OpCode::Rot => 3,
+            OpCode::Pick(n) | OpCode::Roll(n) => *n as usize + 1,
+            _ => 2, // Binary operations, Swap and Over
+        }
+    }
+
     pub(crate) fn to_bytes(&self) -> Vec<u8> {
         match self {
             OpCode::Add => vec![0],
@@ -179,12 +355,125 @@ impl OpCode {
             OpCode::Swap => vec![27],
             OpCode::Neg => vec![28],
             OpCode::Rem => vec![29],
+            OpCode::Not => vec![30],
+            OpCode::DivChecked => vec![31],
+            OpCode::DivRem => vec![32],
+            OpCode::RotateLeft => vec![33],
+            OpCode::RotateRight => vec![34],
+            OpCode::CountOnes => vec![35],
+            OpCode::LeadingZeros => vec![36],
+            OpCode::TrailingZeros => vec![37],
+            OpCode::ILog2 => vec![38],
+            OpCode::AddChecked => vec![39],
+            OpCode::SubChecked => vec![40],
+            OpCode::MulChecked => vec![41],
+            OpCode::AddSat => vec![42],
+            OpCode::SubSat => vec![43],
+            OpCode::MulSat => vec![44],
+            OpCode::AddImm(value) => {
+                let mut bytes = vec![45];
+                bytes.extend(value.to_bytes());
+                bytes
+            }
+            OpCode::SubImm(value) => {
+                let mut bytes = vec![46];
+                bytes.extend(value.to_bytes());
+                bytes
+            }
+            OpCode::MulImm(value) => {
+                let mut bytes = vec![47];
+                bytes.extend(value.to_bytes());
+                bytes
+            }
+            OpCode::Drop => vec![48],
+            OpCode::Over => vec![49],
+            OpCode::Rot => vec![50],
+            OpCode::Pick(n) => {
+                let mut bytes = vec![51];
+                bytes.extend(n.to_le_bytes());
+                bytes
+            }
+            OpCode::Roll(n) => {
+                let mut bytes = vec![52];
+                bytes.extend(n.to_le_bytes());
+                bytes
+            }
+            OpCode::Depth => vec![53],
+            OpCode::BoolToInt(width) => {
+                let mut bytes = vec![54];
+                bytes.extend(width.to_le_bytes());
+                bytes
+            }
+            OpCode::IntToBool => vec![55],
+            OpCode::VAdd(dst, lhs, rhs, len) => {
+                let mut bytes = vec![56];
+                bytes.extend(dst.to_le_bytes());
+                bytes.extend(lhs.to_le_bytes());
+                bytes.extend(rhs.to_le_bytes());
+                bytes.extend(len.to_le_bytes());
+                bytes
+            }
+            OpCode::VMul(dst, lhs, rhs, len) => {
+                let mut bytes = vec![57];
+                bytes.extend(dst.to_le_bytes());
+                bytes.extend(lhs.to_le_bytes());
+                bytes.extend(rhs.to_le_bytes());
+                bytes.extend(len.to_le_bytes());
+                bytes
+            }
+            OpCode::VSum(start, len) => {
+                let mut bytes = vec![58];
+                bytes.extend(start.to_le_bytes());
+                bytes.extend(len.to_le_bytes());
+                bytes
+            }
+            OpCode::VDot(lhs, rhs, len) => {
+                let mut bytes = vec![59];
+                bytes.extend(lhs.to_le_bytes());
+                bytes.extend(rhs.to_le_bytes());
+                bytes.extend(len.to_le_bytes());
+                bytes
+            }
+            OpCode::VMap(dst, src, len, op) => {
+                let mut bytes = vec![60];
+                bytes.extend(dst.to_le_bytes());
+                bytes.extend(src.to_le_bytes());
+                bytes.extend(len.to_le_bytes());
+                bytes.extend(op.op().to_bytes());
+                bytes
+            }
+            OpCode::Sort(start, len) => {
+                let mut bytes = vec![61];
+                bytes.extend(start.to_le_bytes());
+                bytes.extend(len.to_le_bytes());
+                bytes
+            }
+            OpCode::TopK(keys, payloads, len, k) => {
+                let mut bytes = vec![62];
+                bytes.extend(keys.to_le_bytes());
+                bytes.extend(payloads.to_le_bytes());
+                bytes.extend(len.to_le_bytes());
+                bytes.extend(k.to_le_bytes());
+                bytes
+            }
+            OpCode::ArgMax(start, len) => {
+                let mut bytes = vec![63];
+                bytes.extend(start.to_le_bytes());
+                bytes.extend(len.to_le_bytes());
+                bytes
+            }
+            OpCode::Lut(table) => {
+                let mut bytes = vec![64];
+                bytes.extend(table.to_le_bytes());
+                bytes
+            }
         }
     }
 
-    fn from_bytes(bytes: &[u8]) -> (Self, usize) {
+    fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), DecodeError> {
         // Returns OpCode and bytes consumed
-        match bytes[0] {
+        let tag = *bytes.first().ok_or(DecodeError::UnexpectedEof)?;
+        let op = match tag {
             0 => (OpCode::Add, 1),
             1 => (OpCode::Sub, 1),
             2 => (OpCode::Mul, 1),
@@ -204,58 +493,235 @@ impl OpCode {
             16 => (OpCode::Max, 1),
             17 => (OpCode::Mux, 1),
             /*
-            18 => {
-                let address = i32::from_le_bytes([bytes[1], bytes[2], bytes[3], bytes[4]]);
-                (OpCode::Jmp(address), 5)
-            }
-            19 => {
-                let address = i32::from_le_bytes([bytes[1], bytes[2], bytes[3], bytes[4]]);
-                (OpCode::JmpIf(address), 5)
-            }
+            18 => (OpCode::Jmp(read_address(bytes)?), 5),
+            19 => (OpCode::JmpIf(read_address(bytes)?), 5),
             */
             20 => (OpCode::NoOp, 1),
             21 => (OpCode::Dup, 1),
             22 => {
-                let (value, size) = Value::from_bytes(&bytes[1..]);
+                let (value, size) = Value::from_bytes(&bytes[1..])?;
                 (OpCode::Push(value), size + 1)
             }
             23 => (OpCode::Inc, 1),
             24 => (OpCode::Dec, 1),
-            25 => {
-                let address = i32::from_le_bytes([bytes[1], bytes[2], bytes[3], bytes[4]]);
-                (OpCode::Load(address), 5)
-            }
-            26 => {
-                let address = i32::from_le_bytes([bytes[1], bytes[2], bytes[3], bytes[4]]);
-                (OpCode::Store(address), 5)
-            }
+            25 => (OpCode::Load(read_address(bytes)?), 5),
+            26 => (OpCode::Store(read_address(bytes)?), 5),
             27 => (OpCode::Swap, 1),
             28 => (OpCode::Neg, 1),
             29 => (OpCode::Rem, 1),
-            _ => unimplemented!(),
-        }
+            30 => (OpCode::Not, 1),
+            31 => (OpCode::DivChecked, 1),
+            32 => (OpCode::DivRem, 1),
+            33 => (OpCode::RotateLeft, 1),
+            34 => (OpCode::RotateRight, 1),
+            35 => (OpCode::CountOnes, 1),
+            36 => (OpCode::LeadingZeros, 1),
+            37 => (OpCode::TrailingZeros, 1),
+            38 => (OpCode::ILog2, 1),
+            39 => (OpCode::AddChecked, 1),
+            40 => (OpCode::SubChecked, 1),
+            41 => (OpCode::MulChecked, 1),
+            42 => (OpCode::AddSat, 1),
+            43 => (OpCode::SubSat, 1),
+            44 => (OpCode::MulSat, 1),
+            45 => {
+                let (value, size) = read_immediate(bytes)?;
+                (OpCode::AddImm(value), size)
+            }
+            46 => {
+                let (value, size) = read_immediate(bytes)?;
+                (OpCode::SubImm(value), size)
+            }
+            47 => {
+                let (value, size) = read_immediate(bytes)?;
+                (OpCode::MulImm(value), size)
+            }
+            48 => (OpCode::Drop, 1),
+            49 => (OpCode::Over, 1),
+            50 => (OpCode::Rot, 1),
+            51 => (OpCode::Pick(read_count(bytes)?), 5),
+            52 => (OpCode::Roll(read_count(bytes)?), 5),
+            53 => (OpCode::Depth, 1),
+            54 => match read_count(bytes)? {
+                width @ (8 | 16 | 32 | 64 | 128) => (OpCode::BoolToInt(width), 5),
+                width => return Err(DecodeError::InvalidWidth(width)),
+            },
+            55 => (OpCode::IntToBool, 1),
+            56 | 57 => {
+                let dst = i32::from_le_bytes(read_word(bytes, 1)?);
+                let lhs = i32::from_le_bytes(read_word(bytes, 5)?);
+                let rhs = i32::from_le_bytes(read_word(bytes, 9)?);
+                let len = u32::from_le_bytes(read_word(bytes, 13)?);
+                match tag {
+                    56 => (OpCode::VAdd(dst, lhs, rhs, len), 17),
+                    _ => (OpCode::VMul(dst, lhs, rhs, len), 17),
+                }
+            }
+            58 => {
+                let start = i32::from_le_bytes(read_word(bytes, 1)?);
+                let len = u32::from_le_bytes(read_word(bytes, 5)?);
+                (OpCode::VSum(start, len), 9)
+            }
+            59 => {
+                let lhs = i32::from_le_bytes(read_word(bytes, 1)?);
+                let rhs = i32::from_le_bytes(read_word(bytes, 5)?);
+                let len = u32::from_le_bytes(read_word(bytes, 9)?);
+                (OpCode::VDot(lhs, rhs, len), 13)
+            }
+            60 => {
+                let dst = i32::from_le_bytes(read_word(bytes, 1)?);
+                let src = i32::from_le_bytes(read_word(bytes, 5)?);
+                let len = u32::from_le_bytes(read_word(bytes, 9)?);
+                // Nested VMaps are rejected before decoding so it can't recurse
+                // without bound
+                if bytes.get(13) == Some(&60) {
+                    return Err(DecodeError::NotUnary(60));
+                }
+                let (op, size) = OpCode::from_bytes(&bytes[13..])?;
+                let op = UnaryOp::new(op).ok_or(DecodeError::NotUnary(bytes[13]))?;
+                (OpCode::VMap(dst, src, len, op), size + 13)
+            }
+            61 | 63 => {
+                let start = i32::from_le_bytes(read_word(bytes, 1)?);
+                let len = u32::from_le_bytes(read_word(bytes, 5)?);
+                match tag {
+                    61 => (OpCode::Sort(start, len), 9),
+                    _ => (OpCode::ArgMax(start, len), 9),
+                }
+            }
+            62 => {
+                let keys = i32::from_le_bytes(read_word(bytes, 1)?);
+                let payloads = i32::from_le_bytes(read_word(bytes, 5)?);
+                let len = u32::from_le_bytes(read_word(bytes, 9)?);
+                let k = u32::from_le_bytes(read_word(bytes, 13)?);
+                (OpCode::TopK(keys, payloads, len, k), 17)
+            }
+            64 => (OpCode::Lut(read_count(bytes)?), 5),
+            _ => return Err(DecodeError::UnknownOpCode(tag)),
+        };
+        Ok(op)
+    }
+}
+
+// Reads the 4-byte operand at `offset`, for opcodes with several operands
+fn read_word(bytes: &[u8], offset: usize) -> Result<[u8; 4], DecodeError> {
+    let operand = bytes
+        .get(offset..offset + 4)
+        .ok_or(DecodeError::UnexpectedEof)?;
+    Ok(operand.try_into().unwrap())
+}
+
+// Reads the little-endian i32 operand that follows an opcode tag
+fn read_address(bytes: &[u8]) -> Result<i32, DecodeError> {
+    Ok(i32::from_le_bytes(read_word(bytes, 1)?))
+}
+
+// Reads the little-endian u32 operand that follows an opcode tag
+fn read_count(bytes: &[u8]) -> Result<u32, DecodeError> {
+    Ok(u32::from_le_bytes(read_word(bytes, 1)?))
+}
+
+// Reads the plaintext value that follows an immediate opcode's tag
+fn read_immediate(bytes: &[u8]) -> Result<(Value, usize), DecodeError> {
+    let (value, size) = Value::from_bytes(&bytes[1..])?;
+    if value.value_type().is_encrypted() {
+        return Err(DecodeError::InvalidImmediate);
     }
+    Ok((value, size + 1))
 }
 
 pub fn serialize(program: &[OpCode]) -> Vec<u8> {
     program.iter().flat_map(|op| op.to_bytes()).collect()
 }
 
-pub fn deserialize(bytes: &[u8]) -> Vec<OpCode> {
+pub fn deserialize(bytes: &[u8]) -> Result<Vec<OpCode>, DecodeError> {
     let mut ops = Vec::new();
     let mut i = 0;
     while i < bytes.len() {
-        let (op, size) = OpCode::from_bytes(&bytes[i..]);
+        let (op, size) = OpCode::from_bytes(&bytes[i..])?;
         ops.push(op);
         i += size;
     }
-    ops
+    Ok(ops)
+}
+
+// A program together with its table section, the lookup tables that Lut
+// refers to by index. Encoded as the number of tables (u32, little-endian),
+// 256 bytes per table, then the code as written by serialize.
+#[derive(Clone, Default)]
+pub struct Module {
+    pub tables: Vec<Table>,
+    pub code: Vec<OpCode>,
+}
+
+impl Module {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = (self.tables.len() as u32).to_le_bytes().to_vec();
+        for table in &self.tables {
+            bytes.extend(table);
+        }
+        bytes.extend(serialize(&self.code));
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Module, DecodeError> {
+        let count = u32::from_le_bytes(read_word(bytes, 0)?) as usize;
+        let end = count
+            .checked_mul(256)
+            .and_then(|len| len.checked_add(4))
+            .filter(|end| *end <= bytes.len())
+            .ok_or(DecodeError::UnexpectedEof)?;
+        let tables = bytes[4..end]
+            .chunks_exact(256)
+            .map(|table| table.try_into().unwrap())
+            .collect();
+        let module = Module {
+            tables,
+            code: deserialize(&bytes[end..])?,
+        };
+        module.check_tables()?;
+        Ok(module)
+    }
+
+    // Every Lut must name a table in the table section
+    fn check_tables(&self) -> Result<(), DecodeError> {
+        let tables = self.tables.len();
+        match self
+            .code
+            .iter()
+            .filter_map(OpCode::table)
+            .find(|id| *id as usize >= tables)
+        {
+            Some(id) => Err(DecodeError::UnknownTable(id)),
+            None => Ok(()),
+        }
+    }
 }
 
 pub struct VM {
-    pub stack: Vec<Value>,
-    memory: Vec<Value>, // For Load and Store operations
-    ip: usize,          // Instruction pointer
+    pub stack: Vec<Arc<Value>>, // Shared so Dup and Load don't copy ciphertexts
+    memory: Vec<Arc<Value>>,    // For Load and Store operations
+    ip: usize,                  // Instruction pointer
+    server_key: Option<ServerKey>, // Installed on the threads running vector ops
+    tables: Vec<Table>,         // Table section of the running program
+    trusted_keys: Vec<VerifyingKey>, // Signers whose sealed modules may run
+    transcript: Option<Transcript>, // Kept only once enabled
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Status {
+    Finished, // Ran past the last instruction
+    Paused,   // Stopped when the instruction budget ran out
+}
+
+// Shared values are written out once per slot, so sharing isn't preserved
+// across a restore. Ciphertexts are stored compressed and expanded again the
+// first time the restored VM reads them.
+#[derive(Serialize, Deserialize)]
+struct Snapshot {
+    stack: Vec<Value>,
+    memory: Vec<Value>,
+    ip: usize,
 }
 
 impl Default for VM {
@@ -270,190 +736,725 @@ impl VM {
             stack: Vec::new(),
             ip: 0,
             memory: Vec::new(),
+            server_key: None,
+            tables: Vec::new(),
+            trusted_keys: Vec::new(),
+            transcript: None,
+        }
+    }
+
+    // Starts a fresh transcript of every instruction run from now on. Hashing
+    // each ciphertext an instruction touches costs time, so it is off by
+    // default.
+    pub fn enable_transcript(&mut self) {
+        self.transcript = Some(Transcript::new());
+    }
+
+    // Digest of the instructions run since the transcript was enabled. The
+    // transcript isn't part of a snapshot.
+    pub fn transcript(&self) -> Option<[u8; 32]> {
+        self.transcript.as_ref().map(Transcript::digest)
+    }
+
+    // Runs one instruction, adding it to the transcript if one is kept.
+    // `footprint` is only computed when it is.
+    fn traced(&mut self, footprint: Option<Footprint>, f: impl FnOnce(&mut VM)) {
+        let Some(footprint) = footprint else {
+            return f(self);
+        };
+        let base = self.stack.len().saturating_sub(footprint.consumes());
+        let consumed = self.stack[base..].to_vec();
+        f(self);
+        self.transcript.as_mut().unwrap().record(
+            self.ip,
+            &footprint,
+            &consumed,
+            &self.stack[base..],
+            &self.memory,
+        );
+    }
+
+    // Once a key is trusted, the VM only runs sealed modules signed by one of
+    // its trusted keys, and the entry points taking plain code fail with
+    // SealError::Untrusted
+    pub fn trust(&mut self, key: VerifyingKey) {
+        self.trusted_keys.push(key);
+    }
+
+    fn check_unsealed(&self) -> Result<(), SealError> {
+        if !self.trusted_keys.is_empty() {
+            return Err(SealError::Untrusted);
         }
+        Ok(())
+    }
+
+    // Checks the signature, and the signer if the VM trusts any keys, before
+    // running the module
+    pub fn execute_sealed(&mut self, sealed: &SealedModule) -> Result<(), SealError> {
+        self.ip = 0;
+        self.resume_sealed(sealed, usize::MAX)?;
+        Ok(())
+    }
+
+    pub fn resume_sealed(
+        &mut self,
+        sealed: &SealedModule,
+        budget: usize,
+    ) -> Result<Status, SealError> {
+        if !self.trusted_keys.is_empty() && !self.trusted_keys.contains(sealed.signer()) {
+            return Err(SealError::Untrusted);
+        }
+        let module = sealed.open()?;
+        self.tables = module.tables;
+        self.run(&module.code, budget)
+    }
+
+    // Sets the tables Lut reads from. Tables belong to the program rather than
+    // to the VM state, so they aren't part of a snapshot.
+    pub fn set_tables(&mut self, tables: Vec<Table>) {
+        self.tables = tables;
+    }
+
+    pub fn execute_module(&mut self, module: &Module) -> Result<(), SealError> {
+        self.check_unsealed()?;
+        module.check_tables()?;
+        self.tables = module.tables.clone();
+        self.execute(&module.code)
+    }
+
+    // tfhe keeps the server key in a thread local, which rayon's worker threads
+    // can't see. Vector opcodes only run in parallel once the VM has its own
+    // copy of the key; without one they run on the calling thread.
+    pub fn set_server_key(&mut self, key: ServerKey) {
+        self.server_key = Some(key);
     }
 
     fn push(&mut self, value: Value) {
-        self.stack.push(value);
+        self.stack.push(Arc::new(value));
     }
 
+    // Copy-on-write: the value is only cloned if it is still shared with
+    // another stack slot or memory. Compressed values are decompressed here,
+    // the first time an operation needs them.
     pub fn pop(&mut self) -> Value {
-        self.stack.pop().expect("Stack underflow")
+        Arc::unwrap_or_clone(self.stack.pop().expect("Stack underflow")).decompress()
     }
 
-    pub fn execute(&mut self, code: &[OpCode]) {
+    // Like pop, but a value that is still shared stays shared, for operations
+    // that only borrow their operands
+    fn pop_shared(&mut self) -> Arc<Value> {
+        let value = self.stack.pop().expect("Stack underflow");
+        match value.as_ref() {
+            Value::Compressed(val) => Arc::new(val.decompress()),
+            _ => value,
+        }
+    }
+
+    // Index of the item `n` below the top of the stack
+    fn slot(&self, n: u32) -> usize {
+        self.stack.len() - n as usize - 1
+    }
+
+    // Fails before an instruction runs if the stack is too shallow for it or
+    // it reads a table that was never set, so the stack and instruction
+    // pointer are left as they were
+    fn check(&self, op: &OpCode) -> Result<(), SealError> {
+        if self.stack.len() < op.depth() {
+            return Err(SealError::StackUnderflow(self.ip));
+        }
+        match op.table() {
+            Some(id) if id as usize >= self.tables.len() => {
+                Err(SealError::Decode(DecodeError::UnknownTable(id)))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn execute(&mut self, code: &[OpCode]) -> Result<(), SealError> {
+        self.check_unsealed()?;
         self.ip = 0; // Initialize IP at the start of execution
+        self.resume(code, usize::MAX)?;
+        Ok(())
+    }
+
+    // Runs at most `budget` instructions starting from the current instruction
+    // pointer, so a paused program continues where it stopped, possibly in a
+    // VM restored from a snapshot
+    pub fn resume(&mut self, code: &[OpCode], budget: usize) -> Result<Status, SealError> {
+        self.check_unsealed()?;
+        self.run(code, budget)
+    }
+
+    fn run(&mut self, code: &[OpCode], budget: usize) -> Result<Status, SealError> {
+        let mut remaining = budget;
         while self.ip < code.len() {
-            match &code[self.ip] {
-                OpCode::Push(value) => {
-                    self.stack.push(value.clone());
-                }
-                /*
-                OpCode::Jmp(target) => {
-                    self.ip = target.try_into().unwrap(); // Set IP to target, adjusting for 0-based indexing if necessary
+            if remaining == 0 {
+                return Ok(Status::Paused);
+            }
+            remaining -= 1;
+            let op = &code[self.ip];
+            self.check(op)?;
+            let footprint = self.transcript.is_some().then(|| Footprint::of(op));
+            self.traced(footprint, |vm| vm.step(op));
+            self.ip += 1; // Move to the next instruction unless jumped
+        }
+        Ok(Status::Finished)
+    }
+
+    pub fn ip(&self) -> usize {
+        self.ip
+    }
+
+    // Serializes the stack, memory and instruction pointer, compressing any
+    // ciphertexts. Compressing needs a server key, and tfhe's thread-local one
+    // can't be queried, so the VM needs its own copy (see set_server_key)
+    // whenever there is a ciphertext to compress.
+    pub fn snapshot(&self) -> Result<Vec<u8>, SealError> {
+        let cells: Vec<&Arc<Value>> = self.stack.iter().chain(&self.memory).collect();
+        let uncompressed = cells.iter().any(|value| {
+            value.value_type().is_encrypted() && !matches!(value.as_ref(), Value::Compressed(_))
+        });
+        if uncompressed && self.server_key.is_none() {
+            return Err(SealError::NoServerKey);
+        }
+        let mut stack = self.par_map(cells, |value| value.compress());
+        let memory = stack.split_off(self.stack.len());
+        let snapshot = Snapshot {
+            stack,
+            memory,
+            ip: self.ip,
+        };
+        Ok(bincode::serialize(&snapshot).unwrap())
+    }
+
+    // Replaces the stack, memory and instruction pointer with a snapshot's.
+    // Trusted keys, tables, the server key and the transcript are kept, and
+    // the VM is left unchanged if the snapshot doesn't decode.
+    pub fn restore(&mut self, bytes: &[u8]) -> Result<(), DecodeError> {
+        let snapshot: Snapshot = bincode::deserialize(bytes)?;
+        self.stack = snapshot.stack.into_iter().map(Arc::new).collect();
+        self.memory = snapshot.memory.into_iter().map(Arc::new).collect();
+        self.ip = snapshot.ip;
+        Ok(())
+    }
+
+    // Consumes the program so that pushed constants are moved onto the stack
+    // instead of being cloned
+    pub fn execute_owned(&mut self, code: Vec<OpCode>) -> Result<(), SealError> {
+        self.check_unsealed()?;
+        self.ip = 0;
+        for op in code {
+            self.check(&op)?;
+            let footprint = self.transcript.is_some().then(|| Footprint::of(&op));
+            self.traced(footprint, |vm| match op {
+                OpCode::Push(value) => vm.push(value),
+                op => vm.step(&op),
+            });
+            self.ip += 1;
+        }
+        Ok(())
+    }
+
+    fn step(&mut self, op: &OpCode) {
+        match op {
+            OpCode::Push(value) => {
+                self.push(value.clone());
+            }
+            /*
+            OpCode::Jmp(target) => {
+                self.ip = target.try_into().unwrap(); // Set IP to target, adjusting for 0-based indexing if necessary
+                continue;
+            }
+            OpCode::JmpIf(target) => {
+                let condition = self.pop().to_bool(); // Assuming a method to convert Value to bool
+                if condition {
+                    self.ip = target.try_into().unwrap();
                     continue;
                 }
-                OpCode::JmpIf(target) => {
-                    let condition = self.pop().to_bool(); // Assuming a method to convert Value to bool
-                    if condition {
-                        self.ip = target.try_into().unwrap();
-                        continue;
+            }
+            */
+            OpCode::Add => {
+                let b = self.pop_shared();
+                let a = self.pop_shared();
+                self.push(a.add_op(&b));
+            }
+            OpCode::Sub => {
+                let b = self.pop_shared();
+                let a = self.pop_shared();
+                self.push(a.sub_op(&b));
+            }
+            OpCode::Mul => {
+                let b = self.pop_shared();
+                let a = self.pop_shared();
+                self.push(a.mul_op(&b));
+            }
+            OpCode::AddChecked => {
+                let b = self.pop_shared();
+                let a = self.pop_shared();
+                let (result, overflow) = a.add_checked_op(&b);
+                self.push(result);
+                self.push(overflow);
+            }
+            OpCode::SubChecked => {
+                let b = self.pop_shared();
+                let a = self.pop_shared();
+                let (result, overflow) = a.sub_checked_op(&b);
+                self.push(result);
+                self.push(overflow);
+            }
+            OpCode::MulChecked => {
+                let b = self.pop_shared();
+                let a = self.pop_shared();
+                let (result, overflow) = a.mul_checked_op(&b);
+                self.push(result);
+                self.push(overflow);
+            }
+            OpCode::AddSat => {
+                let b = self.pop_shared();
+                let a = self.pop_shared();
+                self.push(a.add_sat_op(&b));
+            }
+            OpCode::SubSat => {
+                let b = self.pop_shared();
+                let a = self.pop_shared();
+                self.push(a.sub_sat_op(&b));
+            }
+            OpCode::MulSat => {
+                let b = self.pop_shared();
+                let a = self.pop_shared();
+                self.push(a.mul_sat_op(&b));
+            }
+            OpCode::AddImm(_)
+            | OpCode::SubImm(_)
+            | OpCode::MulImm(_)
+            | OpCode::Neg
+            | OpCode::Not
+            | OpCode::CountOnes
+            | OpCode::LeadingZeros
+            | OpCode::TrailingZeros
+            | OpCode::ILog2
+            | OpCode::BoolToInt(_)
+            | OpCode::IntToBool
+            | OpCode::Inc
+            | OpCode::Dec
+            | OpCode::Lut(_) => {
+                let a = self.pop_shared();
+                self.push(op.apply_unary(a, &self.tables));
+            }
+            OpCode::Div => {
+                let b = self.pop();
+                let a = self.pop();
+                self.push(a.div_op(b));
+            }
+            OpCode::DivChecked => {
+                let b = self.pop();
+                let a = self.pop();
+                let (quotient, divide_by_zero) = a.div_checked_op(b);
+                self.push(quotient);
+                self.push(divide_by_zero);
+            }
+            OpCode::And => {
+                let b = self.pop_shared();
+                let a = self.pop_shared();
+                self.push(a.and_op(&b));
+            }
+            OpCode::Or => {
+                let b = self.pop_shared();
+                let a = self.pop_shared();
+                self.push(a.or_op(&b));
+            }
+            OpCode::Xor => {
+                let b = self.pop_shared();
+                let a = self.pop_shared();
+                self.push(a.xor_op(&b));
+            }
+            OpCode::Eq => {
+                let b = self.pop_shared();
+                let a = self.pop_shared();
+                self.push(a.eq_op(&b));
+            }
+            OpCode::Neq => {
+                let b = self.pop_shared();
+                let a = self.pop_shared();
+                self.push(a.ne_op(&b));
+            }
+            OpCode::Lt => {
+                let b = self.pop_shared();
+                let a = self.pop_shared();
+                self.push(a.lt_op(&b));
+            }
+            OpCode::Lte => {
+                let b = self.pop_shared();
+                let a = self.pop_shared();
+                self.push(a.le_op(&b));
+            }
+            OpCode::Gt => {
+                let b = self.pop_shared();
+                let a = self.pop_shared();
+                self.push(a.gt_op(&b));
+            }
+            OpCode::Gte => {
+                let b = self.pop_shared();
+                let a = self.pop_shared();
+                self.push(a.ge_op(&b));
+            }
+            OpCode::Min => {
+                let b = self.pop_shared();
+                let a = self.pop_shared();
+                self.push(a.min_op(&b));
+            }
+            OpCode::Max => {
+                let b = self.pop_shared();
+                let a = self.pop_shared();
+                self.push(a.max_op(&b));
+            }
+            OpCode::Mux => {
+                let c = self.pop_shared();
+                let b = self.pop_shared();
+                let a = self.pop_shared();
+                self.push(VM::mux(&a, &b, &c));
+            }
+            OpCode::ShiftRight => {
+                let b = self.pop_shared();
+                let a = self.pop_shared();
+                self.push(a.shr_op(&b));
+            }
+            OpCode::ShiftLeft => {
+                let b = self.pop_shared();
+                let a = self.pop_shared();
+                self.push(a.shl_op(&b));
+            }
+            OpCode::RotateLeft => {
+                let b = self.pop();
+                let a = self.pop();
+                self.push(a.rotate_left_op(b));
+            }
+            OpCode::RotateRight => {
+                let b = self.pop();
+                let a = self.pop();
+                self.push(a.rotate_right_op(b));
+            }
+            OpCode::Dup => {
+                let value = self.stack.last_mut().expect("Stack underflow on Dup");
+                let value = Arc::clone(expand(value));
+                self.stack.push(value);
+            }
+            OpCode::NoOp => {
+                // Do nothing
+            }
+            OpCode::Load(address) => {
+                // Assume address is within bounds
+                let uaddress: usize = (*address).try_into().unwrap();
+                let value = Arc::clone(expand(&mut self.memory[uaddress]));
+                self.stack.push(value);
+            }
+            OpCode::Store(address) => {
+                let value = self.stack.pop().expect("Stack underflow on Store");
+                let uaddress: usize = (*address).try_into().unwrap();
+                self.reserve(uaddress + 1);
+                self.memory[uaddress] = value;
+            }
+            OpCode::Swap => {
+                let a = self.pop();
+                let b = self.pop();
+                self.push(a);
+                self.push(b);
+            }
+            OpCode::Drop => {
+                self.stack.pop().expect("Stack underflow on Drop");
+            }
+            OpCode::Over => {
+                let index = self.slot(1);
+                let value = Arc::clone(expand(&mut self.stack[index]));
+                self.stack.push(value);
+            }
+            OpCode::Rot => {
+                let index = self.slot(2);
+                let value = self.stack.remove(index);
+                self.stack.push(value);
+            }
+            OpCode::Pick(n) => {
+                let index = self.slot(*n);
+                let value = Arc::clone(expand(&mut self.stack[index]));
+                self.stack.push(value);
+            }
+            OpCode::Roll(n) => {
+                let index = self.slot(*n);
+                let value = self.stack.remove(index);
+                self.stack.push(value);
+            }
+            OpCode::Depth => {
+                let depth = self.stack.len() as u32;
+                self.push(Value::Uint32(depth));
+            }
+            OpCode::Rem => {
+                let b = self.pop_shared();
+                let a = self.pop_shared();
+                self.push(a.rem_op(&b));
+            }
+            OpCode::DivRem => {
+                let b = self.pop();
+                let a = self.pop();
+                let (quotient, remainder) = a.div_rem_op(b);
+                self.push(quotient);
+                self.push(remainder);
+            }
+            OpCode::VAdd(dst, lhs, rhs, len) => {
+                let lhs = self.range(*lhs, *len);
+                let pairs = lhs.into_iter().zip(self.range(*rhs, *len)).collect();
+                let sums = self.par_map(pairs, |(a, b)| Arc::new(a.add_op(&b)));
+                self.store_range(*dst, sums);
+            }
+            OpCode::VMul(dst, lhs, rhs, len) => {
+                let lhs = self.range(*lhs, *len);
+                let pairs = lhs.into_iter().zip(self.range(*rhs, *len)).collect();
+                let products = self.par_map(pairs, |(a, b)| Arc::new(a.mul_op(&b)));
+                self.store_range(*dst, products);
+            }
+            OpCode::VSum(start, len) => {
+                let values = self.range(*start, *len);
+                let sum = self.par_reduce(values, |a, b| Arc::new(a.add_op(&b)), "VSum");
+                self.stack.push(sum);
+            }
+            OpCode::VDot(lhs, rhs, len) => {
+                let lhs = self.range(*lhs, *len);
+                let pairs = lhs.into_iter().zip(self.range(*rhs, *len)).collect();
+                let products = self.par_map(pairs, |(a, b)| a.mul_op(&b));
+                let sum = self.par_reduce(products, |a, b| a.add_op(&b), "VDot");
+                self.push(sum);
+            }
+            OpCode::VMap(dst, src, len, op) => {
+                let values = self.range(*src, *len);
+                let tables = &self.tables;
+                let results = self.par_map(values, |value| Arc::new(op.apply(value, tables)));
+                self.store_range(*dst, results);
+            }
+            OpCode::Sort(start, len) => {
+                let values = self.range(*start, *len);
+                let network = sorting_network(*len as usize);
+                let sorted = self.par_network(values, &network, |a, b| {
+                    (Arc::new(a.min_op(&b)), Arc::new(a.max_op(&b)))
+                });
+                self.store_range(*start, sorted);
+            }
+            OpCode::TopK(keys, payloads, len, k) => {
+                let k = (*k).min(*len) as usize;
+                let keys_range = self.range(*keys, *len);
+                let pairs = keys_range
+                    .into_iter()
+                    .zip(self.range(*payloads, *len))
+                    .collect();
+                let network = prune_network(sorting_network(*len as usize), k);
+                // Larger keys move to the front, taking their payload along
+                let sorted = self.par_network(pairs, &network, |a, b| {
+                    let swap = b.0.gt_op(&a.0);
+                    let first_key = VM::select(&swap, &b.0, &a.0);
+                    let first = VM::select(&swap, &b.1, &a.1);
+                    let second_key = VM::select(&swap, &a.0, &b.0);
+                    let second = VM::select(&swap, &a.1, &b.1);
+                    ((first_key, first), (second_key, second))
+                });
+                let (top_keys, top_payloads) = sorted.into_iter().take(k).unzip();
+                self.store_range(*keys, top_keys);
+                self.store_range(*payloads, top_payloads);
+            }
+            OpCode::ArgMax(start, len) => {
+                let pairs = self
+                    .range(*start, *len)
+                    .into_iter()
+                    .zip((0..*len).map(|index| Arc::new(Value::Uint32(index))))
+                    .collect();
+                // The left operand covers lower indices, so ties keep the first
+                let (max, index) = self.par_reduce(
+                    pairs,
+                    |a, b| {
+                        let right = b.0.gt_op(&a.0);
+                        let max = VM::select(&right, &b.0, &a.0);
+                        (max, VM::select(&right, &b.1, &a.1))
+                    },
+                    "ArgMax",
+                );
+                self.stack.push(max);
+                self.stack.push(index);
+            }
+        }
+    }
+
+    // Grows memory to at least `len` cells, filling it with encrypted falses
+    fn reserve(&mut self, len: usize) {
+        if self.memory.len() < len {
+            let fhe_one = FheBool::try_encrypt_trivial(false).unwrap();
+            self.memory.resize(len, Arc::new(Value::Ebool(fhe_one)));
+        }
+    }
+
+    // The values in start..start + len, shared with memory rather than
+    // copied, panicking if the range runs past the end of memory
+    fn range(&mut self, start: i32, len: u32) -> Vec<Arc<Value>> {
+        let start: usize = start.try_into().unwrap();
+        self.memory[start..start + len as usize]
+            .iter_mut()
+            .map(|cell| Arc::clone(expand(cell)))
+            .collect()
+    }
+
+    fn store_range(&mut self, start: i32, values: Vec<Arc<Value>>) {
+        let start: usize = start.try_into().unwrap();
+        self.reserve(start + values.len());
+        for (cell, value) in self.memory[start..].iter_mut().zip(values) {
+            *cell = value;
+        }
+    }
+
+    fn par_map<T, R, F>(&self, items: Vec<T>, f: F) -> Vec<R>
+    where
+        T: Send,
+        R: Send,
+        F: Fn(T) -> R + Send + Sync,
+    {
+        match &self.server_key {
+            Some(key) => items
+                .into_par_iter()
+                .map_init(|| tfhe::set_server_key(key.clone()), |_, item| f(item))
+                .collect(),
+            None => items.into_iter().map(f).collect(),
+        }
+    }
+
+    // Combines neighbours level by level, so reducing n values takes log2(n)
+    // rounds of parallel operations
+    fn par_reduce<T, F>(&self, mut values: Vec<T>, f: F, opcode: &str) -> T
+    where
+        T: Send,
+        F: Fn(T, T) -> T + Send + Sync,
+    {
+        assert!(!values.is_empty(), "Empty range on {}", opcode);
+        while values.len() > 1 {
+            let odd = (values.len() % 2 == 1).then(|| values.pop().unwrap());
+            let mut pairs = Vec::with_capacity(values.len() / 2);
+            let mut iter = values.into_iter();
+            while let (Some(a), Some(b)) = (iter.next(), iter.next()) {
+                pairs.push((a, b));
+            }
+            values = self.par_map(pairs, |(a, b)| f(a, b));
+            values.extend(odd);
+        }
+        values.pop().unwrap()
+    }
+
+    // Runs a comparator network. The comparators of a stage touch distinct
+    // positions, so each stage runs in parallel.
+    fn par_network<T, F>(&self, items: Vec<T>, network: &[Vec<(usize, usize)>], f: F) -> Vec<T>
+    where
+        T: Send,
+        F: Fn(T, T) -> (T, T) + Send + Sync,
+    {
+        let mut items: Vec<Option<T>> = items.into_iter().map(Some).collect();
+        for stage in network {
+            let pairs = stage
+                .iter()
+                .map(|&(i, j)| (items[i].take().unwrap(), items[j].take().unwrap()))
+                .collect();
+            let results = self.par_map(pairs, |(a, b)| f(a, b));
+            for (&(i, j), (a, b)) in stage.iter().zip(results) {
+                items[i] = Some(a);
+                items[j] = Some(b);
+            }
+        }
+        items.into_iter().map(Option::unwrap).collect()
+    }
+
+    // Like mux, but a plaintext condition picks a branch without encrypting
+    // or copying anything
+    fn select(condition: &Value, a: &Arc<Value>, b: &Arc<Value>) -> Arc<Value> {
+        match condition {
+            Value::Bool(true) => Arc::clone(a),
+            Value::Bool(false) => Arc::clone(b),
+            condition => Arc::new(VM::mux(condition, a, b)),
+        }
+    }
+
+    // An encrypted condition selects between ciphertexts of the same width in
+    // place. Otherwise plaintext operands are trivially encrypted and branches
+    // of different widths are promoted to the wider one, as binary_op! does
+    // for two ciphertexts.
+    fn mux(a: &Value, b: &Value, c: &Value) -> Value {
+        macro_rules! if_then_else {
+            ($a:expr, $b:expr, $c:expr, $fallback:expr) => {
+                match ($a, $b, $c) {
+                    (Value::Ebool(a), Value::Euint8(b), Value::Euint8(c)) => {
+                        Value::Euint8(a.if_then_else(b, c))
                     }
-                }
-                */
-                OpCode::Add => {
-                    let b = self.pop();
-                    let a = self.pop();
-                    self.push(a.add_op(b));
-                }
-                OpCode::Sub => {
-                    let b = self.pop();
-                    let a = self.pop();
-                    self.push(a.sub_op(b));
-                }
-                OpCode::Mul => {
-                    let b = self.pop();
-                    let a = self.pop();
-                    self.push(a.mul_op(b));
-                }
-                OpCode::Div => {
-                    let b = self.pop();
-                    let a = self.pop();
-                    self.push(a.div_op(b));
-                }
-                OpCode::And => {
-                    let b = self.pop();
-                    let a = self.pop();
-                    self.push(a.and_op(b));
-                }
-                OpCode::Or => {
-                    let b = self.pop();
-                    let a = self.pop();
-                    self.push(a.or_op(b));
-                }
-                OpCode::Xor => {
-                    let b = self.pop();
-                    let a = self.pop();
-                    self.push(a.xor_op(b));
-                }
-                OpCode::Eq => {
-                    let b = self.pop();
-                    let a = self.pop();
-                    let val = Value::Ebool(a.eq(b));
-                    self.push(val);
-                }
-                OpCode::Neq => {
-                    let b = self.pop();
-                    let a = self.pop();
-                    let val = Value::Ebool(a.ne(b));
-                    self.push(val);
-                }
-                OpCode::Lt => {
-                    let b = self.pop();
-                    let a = self.pop();
-                    let val = Value::Ebool(a.lt(b));
-                    self.push(val);
-                }
-                OpCode::Lte => {
-                    let b = self.pop();
-                    let a = self.pop();
-                    let val = Value::Ebool(a.le(b));
-                    self.push(val);
-                }
-                OpCode::Gt => {
-                    let b = self.pop();
-                    let a = self.pop();
-                    let val = Value::Ebool(a.gt(b));
-                    self.push(val);
-                }
-                OpCode::Gte => {
-                    let b = self.pop();
-                    let a = self.pop();
-                    let val = Value::Ebool(a.ge(b));
-                    self.push(val);
-                }
-                OpCode::Min => {
-                    let b = self.pop();
-                    let a = self.pop();
-                    self.push(a.min(b));
-                }
-                OpCode::Max => {
-                    let b = self.pop();
-                    let a = self.pop();
-                    self.push(a.max(b));
-                }
-                OpCode::Mux => {
-                    let c = self.pop();
-                    let b = self.pop();
-                    let a = self.pop();
-                    let result = self.mux(a, b, c);
-                    self.push(result);
-                }
-                OpCode::ShiftRight => {
-                    let b = self.pop();
-                    let a = self.pop();
-                    self.push(a.shr_op(b));
-                }
-                OpCode::ShiftLeft => {
-                    let b = self.pop();
-                    let a = self.pop();
-                    self.push(a.shl_op(b));
-                }
-                OpCode::Dup => {
-                    let value = self.stack.last().expect("Stack underflow on Dup");
-                    self.stack.push(value.clone());
-                }
-                OpCode::NoOp => {
-                    // Do nothing
-                }
-                OpCode::Inc => {
-                    let a = self.pop();
-                    self.push(a + 1u8);
-                }
-                OpCode::Dec => {
-                    let a = self.pop();
-                    self.push(a - 1u8);
-                }
-                OpCode::Load(address) => {
-                    // Assume memory is a Vec<Value>, and address is within bounds
-                    let uaddress: usize = (*address).try_into().unwrap();
-                    let value = &self.memory[uaddress];
-                    self.stack.push(value.clone());
-                }
-                OpCode::Store(address) => {
-                    let value = self.pop();
-                    // Ensure memory is large enough to handle address
-                    let uaddress: usize = (*address).try_into().unwrap();
-                    if self.memory.len() <= uaddress {
-                        let fhe_one = FheBool::try_encrypt_trivial(false).unwrap();
-                        self.memory.resize(uaddress + 1, Value::Ebool(fhe_one));
+                    (Value::Ebool(a), Value::Euint16(b), Value::Euint16(c)) => {
+                        Value::Euint16(a.if_then_else(b, c))
                     }
-                    self.memory[uaddress] = value;
-                }
-                OpCode::Swap => {
-                    let a = self.pop();
-                    let b = self.pop();
-                    self.push(a);
-                    self.push(b);
-                }
-                OpCode::Neg => {
-                    let a = self.pop();
-                    self.push(a.neg());
+                    (Value::Ebool(a), Value::Euint32(b), Value::Euint32(c)) => {
+                        Value::Euint32(a.if_then_else(b, c))
+                    }
+                    (Value::Ebool(a), Value::Euint64(b), Value::Euint64(c)) => {
+                        Value::Euint64(a.if_then_else(b, c))
+                    }
+                    (Value::Ebool(a), Value::Euint128(b), Value::Euint128(c)) => {
+                        Value::Euint128(a.if_then_else(b, c))
+                    }
+                    _ => $fallback,
                 }
-                OpCode::Rem => {
-                    let b = self.pop();
-                    let a = self.pop();
-                    self.push(a.rem_op(b));
+            };
+        }
+
+        if_then_else!(a, b, c, {
+            let a = a.clone().into_trivial();
+            let (b, c) = (b.clone().into_trivial(), c.clone().into_trivial());
+            let bits = b.value_type().bits().max(c.value_type().bits());
+            if_then_else!(&a, &b.cast_op(bits), &c.cast_op(bits), unimplemented!())
+        })
+    }
+}
+
+// Decompresses a stack slot or memory cell in place the first time it is read,
+// so later reads and the copies shared from it get the full ciphertext
+fn expand(cell: &mut Arc<Value>) -> &Arc<Value> {
+    if let Value::Compressed(val) = cell.as_ref() {
+        *cell = Arc::new(val.decompress());
+    }
+    cell
+}
+
+// Batcher's odd-even merge sort for any n, as stages of comparators. Each
+// comparator puts the smaller item at the lower position.
+fn sorting_network(n: usize) -> Vec<Vec<(usize, usize)>> {
+    let mut stages = Vec::new();
+    let mut p = 1;
+    while p < n {
+        let mut k = p;
+        while k >= 1 {
+            let mut stage = Vec::new();
+            let mut j = k % p;
+            while j + k < n {
+                for i in 0..k.min(n - j - k) {
+                    if (i + j) / (2 * p) == (i + j + k) / (2 * p) {
+                        stage.push((i + j, i + j + k));
+                    }
                 }
+                j += 2 * k;
             }
-            self.ip += 1; // Move to the next instruction unless jumped
+            stages.push(stage);
+            k /= 2;
         }
+        p *= 2;
     }
+    stages
+}
 
-    fn mux(&self, a: Value, b: Value, c: Value) -> Value {
-        let a = a.as_ebool();
-        let b = b.as_eint8();
-        let c = c.as_eint8();
-        Value::Euint8(a.if_then_else(b, c))
+// Keeps only the comparators that can affect the first `k` positions
+fn prune_network(mut network: Vec<Vec<(usize, usize)>>, k: usize) -> Vec<Vec<(usize, usize)>> {
+    let mut needed: HashSet<usize> = (0..k).collect();
+    for stage in network.iter_mut().rev() {
+        stage.retain(|(i, j)| needed.contains(i) || needed.contains(j));
+        for &(i, j) in stage.iter() {
+            needed.insert(i);
+            needed.insert(j);
+        }
     }
+    network
 }